@@ -0,0 +1,39 @@
+/* C interface of dyn-timeout, built with the `ffi` feature. */
+#ifndef DYN_TIMEOUT_H
+#define DYN_TIMEOUT_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct DynTimeout DynTimeout;
+
+/* Executed in the timeout thread with the user data given on creation. */
+typedef void (*dyn_timeout_callback)(void *user_data);
+
+/* Call `callback` with `user_data` after `millis` milliseconds. Return NULL
+ * if `callback` is NULL. */
+DynTimeout *dyn_timeout_new(uint64_t millis, dyn_timeout_callback callback, void *user_data);
+
+/* Return 0 on success, -1 if the timeout is already reached or if the
+ * extended deadline overflows. */
+int dyn_timeout_add(const DynTimeout *timeout, uint64_t millis);
+/* Return 0 on success, -1 if the timeout is already reached. A delay longer
+ * than the remaining time reaches the timeout immediately, the timeouts are
+ * created with `SubPolicy::Saturate` and never fail like with
+ * `SubPolicy::Error`. */
+int dyn_timeout_sub(const DynTimeout *timeout, uint64_t millis);
+
+/* Dismiss the callback, the timeout still has to be freed. */
+int dyn_timeout_cancel(DynTimeout *timeout);
+
+/* Wait for the callback, unless cancelled, and release the timeout. */
+void dyn_timeout_free(DynTimeout *timeout);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,168 @@
+//! Measure how late the timeouts fire on the current hardware, with random
+//! extensions like in a real workload
+use crate::{config::TimeoutConfig, std_thread, tokio_impl};
+use std::{collections::hash_map::RandomState, fmt, hash::BuildHasher, thread, time::Duration};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Upper bounds of the buckets of `Histogram::buckets`, the last bucket
+/// takes the rest.
+const BOUNDS: [Duration; 9] = [
+    Duration::from_micros(10),
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+];
+
+/// Overshoots of the timeouts, the delays between their deadlines and the
+/// moments they were reached.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Histogram {
+    /// Sorted overshoots.
+    samples: Vec<Duration>,
+}
+
+impl Histogram {
+    /// Create a histogram from measured overshoots, in any order.
+    pub fn from_samples(mut samples: Vec<Duration>) -> Self {
+        samples.sort_unstable();
+        Self { samples }
+    }
+    /// Number of timeouts measured.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+    pub fn min(&self) -> Option<Duration> {
+        self.samples.first().copied()
+    }
+    pub fn max(&self) -> Option<Duration> {
+        self.samples.last().copied()
+    }
+    /// Average overshoot, none without sample.
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.samples.len()).ok().filter(|&n| n > 0)?;
+        Some(self.samples.iter().sum::<Duration>() / count)
+    }
+    /// Overshoot under which `percent` percents of the timeouts fired, the
+    /// percentage is clamped between zero and a hundred.
+    ///
+    /// # Return
+    /// Return none without sample.
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        let last = self.samples.len().checked_sub(1)?;
+        let rank = (percent.clamp(0.0, 100.0) / 100.0 * last as f64).round() as usize;
+        Some(self.samples[rank])
+    }
+    /// Number of timeouts per bucket, each bucket is given with its upper
+    /// bound (inclusive), none for the last one.
+    pub fn buckets(&self) -> Vec<(Option<Duration>, usize)> {
+        let mut buckets: Vec<_> = BOUNDS.iter().map(|&bound| (Some(bound), 0)).collect();
+        buckets.push((None, 0));
+        for sample in &self.samples {
+            let index = BOUNDS
+                .iter()
+                .position(|bound| sample <= bound)
+                .unwrap_or(BOUNDS.len());
+            buckets[index].1 += 1;
+        }
+        buckets
+    }
+}
+
+/// Table of the buckets followed by the percentiles, ready to be pasted in
+/// an issue.
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (bound, count) in self.buckets() {
+            match bound {
+                Some(bound) => writeln!(f, "<= {:>9?} {}", bound, count)?,
+                None => writeln!(f, " > {:>9?} {}", BOUNDS[BOUNDS.len() - 1], count)?,
+            }
+        }
+        for percent in [50.0, 90.0, 99.0, 100.0] {
+            if let Some(overshoot) = self.percentile(percent) {
+                writeln!(f, "p{} {:?}", percent, overshoot)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Random duration between zero and `max`.
+fn random(max: Duration) -> Duration {
+    max.mul_f64(RandomState::new().hash_one(()) as f64 / u64::MAX as f64)
+}
+
+/// Arm `count` std timeouts with the given configuration, extend each one
+/// by a random duration up to its initial duration halfway through, and
+/// measure how late they fire. A thread is spawned per timeout unless the
+/// configuration reuses them, see `TimeoutConfig::reuse_thread`.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::{bench, config::TimeoutConfig};
+///
+/// let histogram = bench::run(10, &TimeoutConfig::new(Duration::from_millis(20)));
+/// assert_eq!(histogram.len(), 10);
+/// println!("{}", histogram);
+/// ```
+pub fn run(count: usize, config: &TimeoutConfig) -> Histogram {
+    let mut timeouts: Vec<_> = (0..count)
+        .map(|_| std_thread::DynTimeout::with_config(config, || {}))
+        .collect();
+    thread::sleep(config.duration / 2);
+    for timeout in &timeouts {
+        let _ = timeout.add(random(config.duration));
+    }
+    let samples = timeouts
+        .iter_mut()
+        .filter_map(|timeout| {
+            timeout.wait().ok()?;
+            timeout.stats().lateness
+        })
+        .collect();
+    Histogram::from_samples(samples)
+}
+
+/// Same as `run` with tokio timeouts, a task per timeout.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use std::time::Duration;
+/// use dyn_timeout::{bench, config::TimeoutConfig};
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let config = TimeoutConfig::new(Duration::from_millis(20));
+///    let histogram = bench::run_tokio(10, &config).await;
+///    println!("p99 {:?}", histogram.percentile(99.0));
+/// });
+/// ```
+pub async fn run_tokio(count: usize, config: &TimeoutConfig) -> Histogram {
+    let mut timeouts: Vec<_> = (0..count)
+        .map(|_| tokio_impl::DynTimeout::with_config(config, || {}))
+        .collect();
+    tokio::time::sleep(config.duration / 2).await;
+    for timeout in &timeouts {
+        let _ = timeout.add(random(config.duration)).await;
+    }
+    let mut samples = Vec::with_capacity(count);
+    for timeout in &mut timeouts {
+        if timeout.wait().await.is_ok() {
+            samples.extend(timeout.stats().await.lateness);
+        }
+    }
+    Histogram::from_samples(samples)
+}
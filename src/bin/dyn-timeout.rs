@@ -0,0 +1,138 @@
+//! Run a command with a deadline adjusted live
+//!
+//! ```text
+//! dyn-timeout [--socket <path>] <duration> <command> [args...]
+//! ```
+//!
+//! The command is killed when the deadline is reached, and `dyn-timeout`
+//! exits with the status 124 like `timeout`. While the command runs, each
+//! line read on the standard input (or on a connection to the Unix socket)
+//! adjusts the deadline: `+5s` adds five seconds, `-2s` removes two seconds
+//! and `cancel` lets the command run until its end.
+use anyhow::{bail, Context, Result};
+use dyn_timeout::{
+    command::Adjust,
+    tokio_impl::{DynTimeout, SharedDynTimeout},
+};
+use std::{
+    env,
+    process::{exit, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, BufReader},
+    process::Command,
+};
+
+const USAGE: &str = "usage: dyn-timeout [--socket <path>] <duration> <command> [args...]";
+/// Exit status when the command is killed, the same as `timeout`.
+const TIMED_OUT: i32 = 124;
+
+#[tokio::main]
+async fn main() {
+    match run().await {
+        Ok(code) => exit(code),
+        Err(err) => {
+            eprintln!("dyn-timeout: {:#}", err);
+            exit(125)
+        }
+    }
+}
+
+async fn run() -> Result<i32> {
+    let mut args = env::args().skip(1);
+    let mut socket = None;
+    let duration = loop {
+        match args.next() {
+            Some(arg) if arg == "--socket" => socket = Some(args.next().context(USAGE)?),
+            Some(arg) => break humantime::parse_duration(&arg)?,
+            None => bail!(USAGE),
+        }
+    };
+    let program = args.next().context(USAGE)?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let timeout = DynTimeout::new(duration, || {}).into_shared();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let stdin = BufReader::new(tokio::io::stdin());
+    tokio::spawn(listen(stdin, timeout.clone(), cancelled.clone()));
+    let _socket = match socket {
+        Some(path) => Some(serve(path, timeout.clone(), cancelled.clone())?),
+        None => None,
+    };
+
+    let status = tokio::select! {
+        status = child.wait() => status?,
+        _ = timeout.wait() => {
+            if !cancelled.load(Ordering::Relaxed) {
+                child.kill().await?;
+                return Ok(TIMED_OUT);
+            }
+            child.wait().await?
+        }
+    };
+    let _ = timeout.cancel().await;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Apply the commands read line by line.
+async fn listen<R>(input: R, timeout: SharedDynTimeout, cancelled: Arc<AtomicBool>)
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut lines = input.lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let applied = match line.parse() {
+            Ok(Adjust::Add(dur)) => timeout.add(dur).await,
+            Ok(Adjust::Sub(dur)) => timeout.sub(dur).await,
+            Ok(Adjust::Cancel) => {
+                cancelled.store(true, Ordering::Relaxed);
+                timeout.cancel().await
+            }
+            Err(err) => Err(err),
+        };
+        if let Err(err) = applied {
+            eprintln!("dyn-timeout: {:#}", err);
+        }
+    }
+}
+
+/// Socket file, removed on drop.
+struct Socket(String);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Apply the commands of each connection to the Unix socket.
+#[cfg(unix)]
+fn serve(path: String, timeout: SharedDynTimeout, cancelled: Arc<AtomicBool>) -> Result<Socket> {
+    let listener = tokio::net::UnixListener::bind(&path)?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(listen(
+                BufReader::new(stream),
+                timeout.clone(),
+                cancelled.clone(),
+            ));
+        }
+    });
+    Ok(Socket(path))
+}
+
+#[cfg(not(unix))]
+fn serve(_: String, _: SharedDynTimeout, _: Arc<AtomicBool>) -> Result<Socket> {
+    bail!("The socket is only available on Unix")
+}
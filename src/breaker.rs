@@ -0,0 +1,161 @@
+//! Reset timer of a circuit breaker, built on the std thread implementation
+use crate::{
+    config::{DropBehavior, TimeoutConfig},
+    std_thread::DynTimeout,
+};
+use anyhow::{bail, Result};
+use std::{
+    sync::{Arc, Mutex, MutexGuard, PoisonError, Weak},
+    time::Duration,
+};
+
+/// State of a circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BreakerState {
+    /// The calls go through.
+    #[default]
+    Closed,
+    /// The calls are rejected until the end of the cooldown.
+    Open,
+    /// The cooldown is over, a trial call decides whether the breaker
+    /// closes or opens again.
+    HalfOpen,
+}
+
+struct Breaker {
+    state: BreakerState,
+    /// Number of trips, so the cooldown of a previous trip doesn't change
+    /// the state anymore.
+    trips: u64,
+    cooldown: Option<DynTimeout>,
+}
+
+/// State machine of a circuit breaker whose Open to HalfOpen transition is
+/// a dynamic timeout: the cooldown can be extended on repeated failures or
+/// shortened on an external health signal while it runs.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::breaker::{BreakerState, BreakerTimer};
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let breaker = BreakerTimer::new(TWENTY * 2);
+/// breaker.trip();
+/// assert_eq!(breaker.state(), BreakerState::Open);
+/// // the service is back, probe it sooner
+/// breaker.shorten(TWENTY).unwrap();
+/// std::thread::sleep(TWENTY * 2);
+/// assert_eq!(breaker.state(), BreakerState::HalfOpen);
+/// breaker.success();
+/// assert_eq!(breaker.state(), BreakerState::Closed);
+/// ```
+pub struct BreakerTimer {
+    breaker: Arc<Mutex<Breaker>>,
+    cooldown: Duration,
+}
+
+impl BreakerTimer {
+    /// Create a closed breaker, opened for `cooldown` on each trip.
+    pub fn new(cooldown: Duration) -> Self {
+        Self {
+            breaker: Arc::new(Mutex::new(Breaker {
+                state: BreakerState::Closed,
+                trips: 0,
+                cooldown: None,
+            })),
+            cooldown,
+        }
+    }
+    /// Current state of the breaker.
+    pub fn state(&self) -> BreakerState {
+        lock(&self.breaker).state
+    }
+    /// Open the breaker and start the cooldown, after a failure of a call
+    /// or of the trial call. A breaker already open keeps its cooldown, see
+    /// `extend`.
+    pub fn trip(&self) {
+        let mut breaker = lock(&self.breaker);
+        if breaker.state == BreakerState::Open {
+            return;
+        }
+        breaker.state = BreakerState::Open;
+        breaker.trips += 1;
+        let trips = breaker.trips;
+        let weak = Arc::downgrade(&self.breaker);
+        let config = TimeoutConfig::new(self.cooldown).drop_behavior(DropBehavior::Cancel);
+        // the cooldown of the previous trip is already over
+        breaker.cooldown = Some(DynTimeout::spawn(&config, true, move |_| {
+            half_open(&weak, trips)
+        }));
+    }
+    /// Close the breaker after a successful trial call. An open breaker
+    /// stays open until the end of its cooldown.
+    ///
+    /// # Return
+    /// Return false if the breaker is still open.
+    pub fn success(&self) -> bool {
+        let mut breaker = lock(&self.breaker);
+        if breaker.state == BreakerState::Open {
+            return false;
+        }
+        breaker.state = BreakerState::Closed;
+        true
+    }
+    /// Close the breaker whatever its state, the cooldown in progress is
+    /// cancelled.
+    pub fn reset(&self) {
+        let mut breaker = lock(&self.breaker);
+        breaker.state = BreakerState::Closed;
+        breaker.cooldown = None;
+    }
+    /// Extend the cooldown in progress, on repeated failures.
+    ///
+    /// # Return
+    /// Return a result with an error if the breaker isn't open or if the
+    /// cooldown is already over.
+    /// Otherwise it return an empty success.
+    pub fn extend(&self, dur: Duration) -> Result<()> {
+        match &lock(&self.breaker).cooldown {
+            Some(cooldown) => cooldown.add(dur),
+            None => bail!("Breaker not open"),
+        }
+    }
+    /// Shorten the cooldown in progress, on an external health signal. The
+    /// breaker is half-open immediately if `dur` is greater than the time
+    /// left.
+    ///
+    /// # Return
+    /// Return a result with an error if the breaker isn't open or if the
+    /// cooldown is already over.
+    /// Otherwise it return an empty success.
+    pub fn shorten(&self, dur: Duration) -> Result<()> {
+        match &lock(&self.breaker).cooldown {
+            Some(cooldown) => cooldown.sub(dur),
+            None => bail!("Breaker not open"),
+        }
+    }
+    /// Time left before the breaker is half-open, none if it isn't open.
+    pub fn remaining(&self) -> Option<Duration> {
+        let breaker = lock(&self.breaker);
+        if breaker.state != BreakerState::Open {
+            return None;
+        }
+        breaker.cooldown.as_ref()?.remaining().ok()
+    }
+}
+
+/// End of the cooldown of the given trip.
+fn half_open(breaker: &Weak<Mutex<Breaker>>, trips: u64) {
+    if let Some(breaker) = breaker.upgrade() {
+        let mut breaker = lock(&breaker);
+        if breaker.trips == trips && breaker.state == BreakerState::Open {
+            breaker.state = BreakerState::HalfOpen;
+        }
+    }
+}
+
+fn lock(breaker: &Mutex<Breaker>) -> MutexGuard<'_, Breaker> {
+    breaker.lock().unwrap_or_else(PoisonError::into_inner)
+}
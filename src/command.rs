@@ -0,0 +1,65 @@
+//! Commands to control a timeout through a channel
+use std::time::Duration;
+#[cfg(feature = "humantime")]
+use {
+    anyhow::{bail, Error, Result},
+    std::str::FromStr,
+};
+
+/// Adjustment sent to a timeout created with `DynTimeout::actor`. The
+/// commands are applied in the order they are received, a command failing
+/// (the timeout already reached for example) is dismissed.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::{command::Adjust, config::TimeoutConfig, std_thread::DynTimeout};
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let commands = DynTimeout::actor(&TimeoutConfig::new(TWENTY), || {
+///    println!("after forty milliseconds");
+/// });
+/// let remote = commands.clone();
+/// std::thread::spawn(move || remote.send(Adjust::Add(TWENTY)).unwrap());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjust {
+    /// Increase the delay, see `DynTimeout::add`.
+    Add(Duration),
+    /// Decrease the delay, see `DynTimeout::sub`.
+    Sub(Duration),
+    /// Dismiss the callback, see `DynTimeout::cancel`.
+    Cancel,
+}
+
+/// Parse a command written by a human: `+5s` to add five seconds, `-2s` to
+/// remove two seconds, or `cancel`.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::command::Adjust;
+///
+/// let command: Adjust = "+1m30s".parse().unwrap();
+/// assert_eq!(command, Adjust::Add(Duration::from_secs(90)));
+/// ```
+#[cfg(feature = "humantime")]
+impl FromStr for Adjust {
+    type Err = Error;
+
+    fn from_str(command: &str) -> Result<Self> {
+        let command = command.trim();
+        if command == "cancel" {
+            return Ok(Adjust::Cancel);
+        }
+        match command.split_at_checked(1) {
+            Some(("+", dur)) => Ok(Adjust::Add(humantime::parse_duration(dur.trim())?)),
+            Some(("-", dur)) => Ok(Adjust::Sub(humantime::parse_duration(dur.trim())?)),
+            _ => bail!(
+                "Unknown command {:?}, expected +<duration>, -<duration> or cancel",
+                command
+            ),
+        }
+    }
+}
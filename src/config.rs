@@ -0,0 +1,708 @@
+//! Configuration of a dynamic timeout
+use crate::{
+    error::{Panicked, TimeoutError},
+    wake::WakeStrategy,
+};
+use anyhow::{bail, Result};
+use std::{
+    any::Any,
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    panic::{self, AssertUnwindSafe},
+    process,
+    sync::{atomic::Ordering, Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// What to do with a pending timeout when it's dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DropBehavior {
+    /// Wait for the timeout and the callback execution. The tokio
+    /// implementation can't block on drop, the task is detached instead.
+    #[default]
+    Join,
+    /// Dismiss the callback and stop the waiting thread (or task).
+    Cancel,
+    /// Let the thread (or task) run in background, the callback is still
+    /// executed when the timeout is reached.
+    Detach,
+}
+
+/// What `sub` does when it removes more than the remaining time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SubPolicy {
+    /// Clamp the remaining time to zero, the timeout is reached immediately.
+    /// The time removed beyond zero is still deducted from an `add` applied
+    /// in the meantime, so concurrent adjustments sum up in any order.
+    #[default]
+    Saturate,
+    /// Return `TimeoutError::ExceedsRemaining` and leave the timeout
+    /// unchanged.
+    Error,
+    /// Keep at least the given remaining time, so the timeout is never
+    /// reached by a `sub`. A remaining time already shorter is unchanged.
+    Clamp(Duration),
+}
+
+impl SubPolicy {
+    /// Duration to remove from the remaining time when `dur` is requested.
+    pub(crate) fn apply(self, dur: Duration, remaining: Duration) -> Result<Duration> {
+        match self {
+            SubPolicy::Saturate => Ok(dur),
+            SubPolicy::Error if dur > remaining => bail!(TimeoutError::ExceedsRemaining),
+            SubPolicy::Error => Ok(dur),
+            SubPolicy::Clamp(min) => Ok(dur.min(remaining.saturating_sub(min))),
+        }
+    }
+}
+
+/// What `add` does when the timeout is already reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AddPolicy {
+    /// Return an error, the timeout can't be increased anymore.
+    #[default]
+    Error,
+    /// Re-arm the timeout with the given duration, the callback is executed
+    /// again when it's reached. Useful for keep-alive handlers that don't
+    /// care whether the previous cycle fired.
+    Rearm,
+}
+
+/// What `+=` and `-=` do on a std timeout when the adjustment fails, the
+/// timeout is already reached for instance. Unlike `add` and `sub`, the
+/// operators can't return the error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AssignPolicy {
+    /// Panic with the error, like an overflowing `Instant += Duration`.
+    #[default]
+    Panic,
+    /// Dismiss the adjustment, for the protocol loops extending a timeout
+    /// which may expire concurrently.
+    Ignore,
+}
+
+impl AssignPolicy {
+    #[track_caller]
+    pub(crate) fn handle(self, result: Result<()>) {
+        if let (AssignPolicy::Panic, Err(err)) = (self, result) {
+            panic!("dyn-timeout adjustment failed: {}", err)
+        }
+    }
+}
+
+/// Memory ordering of the flags shared by a timeout and its thread (or
+/// task): the claim deciding between the callback and a cancellation, and
+/// the stop of a re-armable timeout. The deadline itself is behind a mutex
+/// and the wake ups go through channels, they are ordered in both modes.
+/// The statistics counters, like `DynTimeout::wakeups`, are relaxed in both
+/// modes: they only count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum MemoryOrdering {
+    /// Acquire and release. A successful `cancel` synchronizes with the
+    /// thread: the callback is never started afterward, and the writes made
+    /// before the cancellation are visible to the thread when it notices it.
+    /// When the callback is claimed, the writes made before the claim
+    /// (the adjustments of the deadline) are visible to the callback. A
+    /// `stop` is visible to the thread at its next wake up.
+    #[default]
+    AcquireRelease,
+    /// Sequentially consistent. Same guarantees, and all the operations on
+    /// the flags of the timeouts in this mode follow a single total order
+    /// seen by every thread: two threads can't observe a cancellation of
+    /// one timeout and the claim of another in opposite orders. For the
+    /// systems reviewed against this model, at the cost of full fences on
+    /// some platforms.
+    SeqCst,
+}
+
+impl MemoryOrdering {
+    pub(crate) fn load(self) -> Ordering {
+        match self {
+            MemoryOrdering::AcquireRelease => Ordering::Acquire,
+            MemoryOrdering::SeqCst => Ordering::SeqCst,
+        }
+    }
+    pub(crate) fn store(self) -> Ordering {
+        match self {
+            MemoryOrdering::AcquireRelease => Ordering::Release,
+            MemoryOrdering::SeqCst => Ordering::SeqCst,
+        }
+    }
+    /// Ordering of a read-modify-write.
+    pub(crate) fn update(self) -> Ordering {
+        match self {
+            MemoryOrdering::AcquireRelease => Ordering::AcqRel,
+            MemoryOrdering::SeqCst => Ordering::SeqCst,
+        }
+    }
+}
+
+/// Where the callback of a timeout is executed. Inline in the timer gives the
+/// lowest latency, for a circuit breaker, while a spawned callback is
+/// isolated from the timer, for a long cleanup job. The tokio implementation
+/// executes the callback in the timer task unless it's `Spawned`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CallbackExecution {
+    /// In the thread of the timeout, a join on drop waits for the end of the
+    /// callback.
+    #[default]
+    TimerThread,
+    /// In a small thread pool shared by all the timeouts, so long callbacks
+    /// don't keep the timeout threads alive and the number of callbacks
+    /// running at the same time stays bounded. The timeout thread ends, and
+    /// the timeouts created with `then` start, as soon as the callback is
+    /// queued: a join on drop doesn't wait for it.
+    SharedPool,
+    /// In a thread (or a tokio task) spawned for each execution. The timer
+    /// doesn't wait for the callback, a join on drop and `wait` neither,
+    /// and a panic of the callback doesn't reach the timer. The handle
+    /// given to the callback can't re-arm the timeout.
+    Spawned,
+}
+
+/// What happens when the callback of a timeout panics. The intermediate
+/// callbacks of `escalate` follow the same policy.
+// the handlers are compared by address, like the stages of a configuration
+#[allow(unpredictable_function_pointer_comparisons)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PanicPolicy {
+    /// Let the panic unwind: the std thread ends and a join on drop panics
+    /// too, the tokio task ends and `close` returns `Outcome::Panicked`.
+    #[default]
+    Unwind,
+    /// Catch the panic and keep its message, see `DynTimeout::panicked`.
+    /// The timeout goes on as if the callback returned.
+    Record,
+    /// Catch and record the panic like `Record`, the next `wait` returns it
+    /// as a `Panicked` error.
+    Propagate,
+    /// Abort the process, for a callback which can't fail safely.
+    Abort,
+    /// Catch the panic and call the handler with its message.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Handler(fn(&str)),
+}
+
+impl PanicPolicy {
+    /// Execute the callback, a panic is handled according to the policy.
+    pub(crate) fn call(self, panicked: &Mutex<Option<String>>, callback: impl FnOnce()) {
+        if self == PanicPolicy::Unwind {
+            return callback();
+        }
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(callback)) {
+            self.handle(payload, panicked)
+        }
+    }
+    /// Handle a panic caught in a callback.
+    pub(crate) fn handle(self, payload: Box<dyn Any + Send>, panicked: &Mutex<Option<String>>) {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => "Box<dyn Any>".to_string(),
+            },
+        };
+        match self {
+            PanicPolicy::Unwind => panic::resume_unwind(payload),
+            PanicPolicy::Record | PanicPolicy::Propagate => {
+                *panicked.lock().unwrap_or_else(PoisonError::into_inner) = Some(message)
+            }
+            PanicPolicy::Abort => process::abort(),
+            PanicPolicy::Handler(handler) => handler(&message),
+        }
+    }
+    /// Error returned by `wait` if a panic was recorded with `Propagate`,
+    /// the panic is returned once.
+    pub(crate) fn propagate(self, panicked: &Mutex<Option<String>>) -> Result<()> {
+        if self != PanicPolicy::Propagate {
+            return Ok(());
+        }
+        match panicked
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            Some(message) => bail!(Panicked(message)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// What a tokio timeout created with `with_sender_policy` does when the
+/// channel is full at expiry.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SendPolicy {
+    /// Wait until the receiver makes room, the task doesn't end before.
+    #[default]
+    Wait,
+    /// Dismiss the notification.
+    Drop,
+    /// Wait at most the given duration, then dismiss the notification.
+    WaitFor(Duration),
+    /// Execute the callback instead of sending the notification.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Fallback(fn()),
+}
+
+/// Accuracy required when the timeout fires, see `TimeoutConfig::precision`
+/// and `DynTimeout::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Precision {
+    /// Sleep until the deadline, the lateness depends on the scheduler.
+    #[default]
+    BestEffort,
+    /// Sleep until a millisecond before the deadline, then yield until it.
+    /// Fired at most a millisecond late.
+    Millisecond,
+    /// Sleep until a millisecond before the deadline, then spin until it
+    /// (a tokio task yields instead). Fired at most a tenth of a
+    /// millisecond late, at the cost of a busy core.
+    SubMillisecond,
+}
+
+impl Precision {
+    /// Maximum lateness of the contract, none for `BestEffort`.
+    pub fn tolerance(&self) -> Option<Duration> {
+        match self {
+            Precision::BestEffort => None,
+            Precision::Millisecond => Some(Duration::from_millis(1)),
+            Precision::SubMillisecond => Some(Duration::from_micros(100)),
+        }
+    }
+    /// Return true if a timeout fired `lateness` after its deadline meets
+    /// the contract.
+    pub fn is_met(&self, lateness: Duration) -> bool {
+        self.tolerance()
+            .is_none_or(|tolerance| lateness <= tolerance)
+    }
+    /// Time before the deadline when the thread stops sleeping.
+    pub(crate) fn margin(&self) -> Duration {
+        match self {
+            Precision::BestEffort => Duration::ZERO,
+            _ => Duration::from_millis(1),
+        }
+    }
+    /// Wait a moment in the thread, close to the deadline.
+    pub(crate) fn spin(&self) {
+        match self {
+            Precision::SubMillisecond => std::hint::spin_loop(),
+            _ => std::thread::yield_now(),
+        }
+    }
+}
+
+/// Channel waking up the task of a tokio timeout when it's adjusted, see
+/// `TimeoutConfig::wake_channel`. A oneshot channel isn't proposed, the
+/// task is woken up at each adjustment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum WakeChannel {
+    /// A tokio mpsc channel of the given capacity, at least one. The wake
+    /// ups beyond the capacity are dismissed, one pending is enough.
+    Mpsc(usize),
+    /// A tokio `Notify` and a count of the senders, the lightest per
+    /// timeout.
+    Notify,
+}
+
+impl Default for WakeChannel {
+    fn default() -> Self {
+        WakeChannel::Mpsc(1)
+    }
+}
+
+/// Policies of a dynamic timeout, used with `DynTimeout::with_config`. The
+/// setters can be chained as a builder, and with the `serde` feature the
+/// configuration can be deserialized from a configuration file.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::{config::{DropBehavior, TimeoutConfig}, std_thread::DynTimeout};
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let config = TimeoutConfig::new(TWENTY)
+///     .max_total(TWENTY * 10)
+///     .jitter(TWENTY)
+///     .drop_behavior(DropBehavior::Cancel);
+/// let dyn_timeout = DynTimeout::with_config(&config, || {
+///    println!("never append, cancelled on drop");
+/// });
+/// dyn_timeout.add(TWENTY).unwrap();
+/// ```
+// the wake strategies are compared by address, like the stages
+#[allow(unpredictable_function_pointer_comparisons)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimeoutConfig {
+    /// Initial delay before the timeout.
+    pub duration: Duration,
+    /// Maximum time we can wait, the `add` calls are dismissed once the
+    /// remaining time reach this value.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_total: Option<Duration>,
+    /// Upper bound of a random delay added to the initial duration, useful
+    /// to avoid that several timeouts are reached at the same time.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub jitter: Option<Duration>,
+    /// What to do when the timeout is dropped before being reached.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub drop_behavior: DropBehavior,
+    /// What to do when `sub` removes more than the remaining time.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sub_policy: SubPolicy,
+    /// What to do when `add` is called after the timeout is reached.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub add_policy: AddPolicy,
+    /// What `+=` and `-=` do when the adjustment fails.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub assign_policy: AssignPolicy,
+    /// Memory ordering of the flags shared with the thread (or task).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub memory_ordering: MemoryOrdering,
+    /// Where the callback is executed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub callback_execution: CallbackExecution,
+    /// Record every adjustment of the timeout, see `DynTimeout::history`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub record_history: bool,
+    /// What to do when the callback panics.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub panic_policy: PanicPolicy,
+    /// Run the timeout in a thread parked by a previous std timeout, see
+    /// `reuse_thread`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reuse_thread: bool,
+    /// Name of the timeout in its snapshots, see `DynTimeout::snapshot`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub label: Option<String>,
+    /// How the tokio task is woken up when the timeout is adjusted.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub wake_channel: WakeChannel,
+    /// Accuracy required when the timeout fires.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub precision: Precision,
+    /// Longest execution of the callback of a tokio timeout, see
+    /// `max_callback_runtime`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_callback_runtime: Option<Duration>,
+    /// Callbacks executed before the final one, each followed by a new
+    /// delay to wait, see `escalate`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) stages: Vec<(fn(), Duration)>,
+    /// Create the wake up mechanism of the std thread, see `wake_strategy`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) wake_strategy: Option<fn() -> Arc<dyn WakeStrategy>>,
+}
+
+impl TimeoutConfig {
+    /// Create a configuration with the given initial delay and the default
+    /// policies.
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            max_total: None,
+            jitter: None,
+            drop_behavior: DropBehavior::default(),
+            sub_policy: SubPolicy::default(),
+            add_policy: AddPolicy::default(),
+            assign_policy: AssignPolicy::default(),
+            memory_ordering: MemoryOrdering::default(),
+            callback_execution: CallbackExecution::default(),
+            record_history: false,
+            panic_policy: PanicPolicy::default(),
+            reuse_thread: false,
+            label: None,
+            wake_channel: WakeChannel::default(),
+            precision: Precision::default(),
+            max_callback_runtime: None,
+            stages: vec![],
+            wake_strategy: None,
+        }
+    }
+    /// Set a maximum time we can wait, dismiss the `add` call if overflow.
+    pub fn max_total(mut self, max_total: Duration) -> Self {
+        self.max_total = Some(max_total);
+        self
+    }
+    /// Add a random delay between zero and `jitter` to the initial duration.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = Some(jitter);
+        self
+    }
+    /// Set what to do when the timeout is dropped before being reached.
+    pub fn drop_behavior(mut self, drop_behavior: DropBehavior) -> Self {
+        self.drop_behavior = drop_behavior;
+        self
+    }
+    /// Set what to do when `sub` removes more than the remaining time.
+    pub fn sub_policy(mut self, sub_policy: SubPolicy) -> Self {
+        self.sub_policy = sub_policy;
+        self
+    }
+    /// Set what to do when `add` is called after the timeout is reached.
+    pub fn add_policy(mut self, add_policy: AddPolicy) -> Self {
+        self.add_policy = add_policy;
+        self
+    }
+    /// Set what `+=` and `-=` do on a std timeout when the adjustment fails.
+    pub fn assign_policy(mut self, assign_policy: AssignPolicy) -> Self {
+        self.assign_policy = assign_policy;
+        self
+    }
+    /// Set the memory ordering of the flags shared with the thread (or
+    /// task), see `MemoryOrdering` for the guarantees of each mode.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::{MemoryOrdering, TimeoutConfig}, std_thread::DynTimeout};
+    ///
+    /// let config = TimeoutConfig::new(Duration::from_millis(20))
+    ///     .memory_ordering(MemoryOrdering::SeqCst);
+    /// let mut dyn_timeout = DynTimeout::with_config(&config, || {
+    ///    println!("never after a successful cancel");
+    /// });
+    /// dyn_timeout.cancel().unwrap();
+    /// ```
+    pub fn memory_ordering(mut self, memory_ordering: MemoryOrdering) -> Self {
+        self.memory_ordering = memory_ordering;
+        self
+    }
+    /// Set where the callback is executed.
+    pub fn callback_execution(mut self, callback_execution: CallbackExecution) -> Self {
+        self.callback_execution = callback_execution;
+        self
+    }
+    /// Record every adjustment of the timeout with its date and its caller.
+    pub fn record_history(mut self, record_history: bool) -> Self {
+        self.record_history = record_history;
+        self
+    }
+    /// Set what to do when the callback panics.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::{PanicPolicy, TimeoutConfig}, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).panic_policy(PanicPolicy::Record);
+    /// let mut dyn_timeout = DynTimeout::with_config(&config, || {
+    ///    panic!("cleanup failed");
+    /// });
+    /// dyn_timeout.wait().unwrap();
+    /// assert_eq!(dyn_timeout.panicked().as_deref(), Some("cleanup failed"));
+    /// ```
+    pub fn panic_policy(mut self, panic_policy: PanicPolicy) -> Self {
+        self.panic_policy = panic_policy;
+        self
+    }
+    /// Run the std timeout in a thread parked by a previous timeout instead
+    /// of spawning a new thread, useful when short timeouts are created one
+    /// after the other like per-request timeouts in a threaded server. Once
+    /// the timeout ended, the thread is parked again for ten seconds waiting
+    /// for the next one. `DynTimeout::join_handle` is none in that case, the
+    /// timeout is still joined on drop. Ignored by the tokio timeouts.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::TimeoutConfig, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).reuse_thread(true);
+    /// for _ in 0..3 {
+    ///     let mut request = DynTimeout::with_config(&config, || {
+    ///        println!("request too long");
+    ///     });
+    ///     // handle the request, then the thread waits for the next one
+    ///     request.cancel().unwrap();
+    /// }
+    /// ```
+    pub fn reuse_thread(mut self, reuse_thread: bool) -> Self {
+        self.reuse_thread = reuse_thread;
+        self
+    }
+    /// Name the timeout, to recognize it in the snapshots dumped by a debug
+    /// endpoint.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+    /// Execute the `warning` callback when the duration is reached, and start
+    /// a grace period. The timeout callback is executed only if the grace
+    /// period also lapses. The grace period can still be increased or
+    /// decreased like the initial delay.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::TimeoutConfig, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).grace(TWENTY, || {
+    ///     println!("you will be logged out in twenty milliseconds");
+    /// });
+    /// let session = DynTimeout::with_config(&config, || {
+    ///    println!("logged out");
+    /// });
+    /// ```
+    pub fn grace(self, grace: Duration, warning: fn()) -> Self {
+        self.escalate(warning, grace)
+    }
+    /// Add a threshold: when the current delay is reached, the `callback` is
+    /// executed and the timeout waits `then` more before the next threshold,
+    /// or before the timeout callback after the last one. Increasing or
+    /// decreasing the timeout moves the current threshold and pushes all the
+    /// next ones.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::TimeoutConfig, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY)
+    ///     .escalate(|| println!("notify"), TWENTY)
+    ///     .escalate(|| println!("degrade"), TWENTY);
+    /// let dyn_timeout = DynTimeout::with_config(&config, || {
+    ///    println!("kill");
+    /// });
+    /// // notify, degrade and kill twenty milliseconds later
+    /// dyn_timeout.add(TWENTY).unwrap();
+    /// ```
+    pub fn escalate(mut self, callback: fn(), then: Duration) -> Self {
+        self.stages.push((callback, then));
+        self
+    }
+    /// Set how the std thread is woken up when the timeout is adjusted, the
+    /// `factory` is called for each timeout. A mutex and a condition
+    /// variable are used by default, see `wake::WakeStrategy`. Ignored by
+    /// the tokio timeouts.
+    pub fn wake_strategy(mut self, factory: fn() -> Arc<dyn WakeStrategy>) -> Self {
+        self.wake_strategy = Some(factory);
+        self
+    }
+    /// Set how the tokio task is woken up when the timeout is adjusted, a
+    /// mpsc channel of capacity one by default. With millions of timers,
+    /// `WakeChannel::Notify` saves the memory of a channel per timeout.
+    /// Ignored by the std timeouts, see `wake_strategy`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use std::time::Duration;
+    /// use dyn_timeout::{
+    ///     config::{TimeoutConfig, WakeChannel},
+    ///     tokio_impl::DynTimeout,
+    /// };
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let config = TimeoutConfig::new(TWENTY).wake_channel(WakeChannel::Notify);
+    ///    let dyn_timeout = DynTimeout::with_config(&config, || {
+    ///        println!("after ten milliseconds");
+    ///    });
+    ///    dyn_timeout.sub(TWENTY / 2).await.unwrap();
+    /// });
+    /// ```
+    pub fn wake_channel(mut self, wake_channel: WakeChannel) -> Self {
+        self.wake_channel = wake_channel;
+        self
+    }
+    /// Set the accuracy required when the timeout fires, the thread (or
+    /// task) ends its wait by spinning or yielding instead of relying on the
+    /// sleep alone. `DynTimeout::stats` tells whether the contract was met.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{
+    ///     config::{Precision, TimeoutConfig},
+    ///     std_thread::DynTimeout,
+    /// };
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).precision(Precision::Millisecond);
+    /// let mut dyn_timeout = DynTimeout::with_config(&config, || {
+    ///    println!("after twenty milliseconds, give or take one");
+    /// });
+    /// dyn_timeout.wait().unwrap();
+    /// let stats = dyn_timeout.stats();
+    /// println!("late of {:?}, met: {:?}", stats.lateness, stats.contract_met);
+    /// ```
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+    /// Abandon the callback of a tokio timeout still running after `limit`,
+    /// so a hanging expiry handling doesn't hold the task. The callback
+    /// future is dropped at its current await point, the cycle ends with
+    /// `Outcome::Overrun` and `DynTimeout::overruns` counts it. Ignored by
+    /// the std timeouts, a thread can't be aborted.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::TimeoutConfig, event::Outcome, tokio_impl::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let config = TimeoutConfig::new(TWENTY).max_callback_runtime(TWENTY);
+    ///    let dyn_timeout = DynTimeout::new_async(&config, || async {
+    ///        // a flush stuck on a dead connection
+    ///        std::future::pending::<()>().await
+    ///    });
+    ///    assert_eq!(dyn_timeout.completion().wait().await, Outcome::Overrun);
+    ///    assert_eq!(dyn_timeout.overruns(), 1);
+    /// });
+    /// ```
+    pub fn max_callback_runtime(mut self, limit: Duration) -> Self {
+        self.max_callback_runtime = Some(limit);
+        self
+    }
+    /// Initial delay with the jitter applied.
+    pub(crate) fn initial_duration(&self) -> Duration {
+        match self.jitter {
+            Some(jitter) => {
+                let random = RandomState::new().hash_one(()) as f64 / u64::MAX as f64;
+                self.duration.saturating_add(jitter.mul_f64(random))
+            }
+            None => self.duration,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Deadline of the current tokio task, inherited by the tasks it spawns
+use crate::{error::Elapsed, tokio_impl::SharedDynTimeout};
+use std::future::Future;
+use tokio::task::JoinHandle;
+
+tokio::task_local! {
+    /// Deadline of the task, set by `deadline_scope`.
+    static DEADLINE: SharedDynTimeout;
+}
+
+/// Run the future until the timeout is reached, with the timeout as deadline
+/// of the task: `current_deadline` returns it and the tasks spawned with
+/// `spawn_with_deadline` inherit it, like a Go `context.WithDeadline`.
+///
+/// # Return
+/// Return the output of the future, or `Err(Elapsed)` if the timeout was
+/// reached first.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::{context, tokio_impl::DynTimeout};
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let request = DynTimeout::new(TWENTY * 5, || {}).into_shared();
+///    let output = context::deadline_scope(request, async {
+///        assert!(context::current_deadline().is_some());
+///        42
+///    })
+///    .await;
+///    assert_eq!(output, Ok(42));
+/// });
+/// ```
+pub async fn deadline_scope<F: Future>(
+    timeout: SharedDynTimeout,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    DEADLINE
+        .scope(timeout.clone(), timeout.with_deadline(future))
+        .await
+}
+
+/// Deadline of the current task, none outside of a `deadline_scope`.
+pub fn current_deadline() -> Option<SharedDynTimeout> {
+    DEADLINE.try_with(SharedDynTimeout::clone).ok()
+}
+
+/// Spawn a task inheriting the deadline of the current task. The task is
+/// aborted when the deadline is reached, even if the parent task is already
+/// done, and an adjustment of the deadline applies to both. Without a
+/// deadline, the task is spawned like with `tokio::spawn`.
+///
+/// # Return
+/// Return the handle of the task, its output is `Err(Elapsed)` if the
+/// deadline was reached first.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::{context, error::Elapsed, tokio_impl::DynTimeout};
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let request = DynTimeout::new(TWENTY, || {}).into_shared();
+///    let fetch = context::deadline_scope(request, async {
+///        context::spawn_with_deadline(tokio::time::sleep(TWENTY * 10))
+///    })
+///    .await
+///    .unwrap();
+///    assert_eq!(fetch.await.unwrap(), Err(Elapsed));
+/// });
+/// ```
+pub fn spawn_with_deadline<F>(future: F) -> JoinHandle<Result<F::Output, Elapsed>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let deadline = current_deadline();
+    tokio::spawn(async move {
+        match deadline {
+            Some(timeout) => deadline_scope(timeout, future).await,
+            None => Ok(future.await),
+        }
+    })
+}
@@ -0,0 +1,53 @@
+//! Human readable remaining time, for command line tools and logs
+use std::{fmt, time::Duration};
+
+/// Remaining time displayed as a countdown, like `2m 13s` or `1h 5s`. The
+/// time is rounded up to the second so the countdown shows `1s` until the
+/// end, and to the millisecond under a second (`450ms`).
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::countdown::Countdown;
+///
+/// let countdown = Countdown(Duration::from_millis(132_400));
+/// assert_eq!(countdown.to_string(), "2m 13s");
+/// println!("aborting in {}", countdown);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Countdown(pub Duration);
+
+impl From<Duration> for Countdown {
+    fn from(dur: Duration) -> Self {
+        Self(dur)
+    }
+}
+
+impl fmt::Display for Countdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_zero() {
+            return write!(f, "0s");
+        }
+        if self.0 < Duration::from_secs(1) {
+            return write!(f, "{}ms", self.0.as_micros().div_ceil(1000));
+        }
+        let secs = self.0.as_nanos().div_ceil(1_000_000_000);
+        let units = [
+            (secs / 86400, "d"),
+            (secs / 3600 % 24, "h"),
+            (secs / 60 % 60, "m"),
+            (secs % 60, "s"),
+        ];
+        let mut first = true;
+        for (value, unit) in units {
+            if value > 0 {
+                if !first {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}{}", value, unit)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
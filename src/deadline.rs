@@ -0,0 +1,428 @@
+//! Deadline waited by a timeout thread (or task)
+use crate::{
+    config::{MemoryOrdering, Precision},
+    error::TimeoutError,
+    event::Expiration,
+    snapshot::TimeoutState,
+};
+use std::{
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+
+enum State {
+    /// Not started yet, see `DynTimeout::then`.
+    Pending(Duration),
+    Running(Instant),
+    /// Armed with `Duration::MAX`, never reached until a deadline is set.
+    Infinite,
+    /// Reached or cancelled.
+    Reached,
+}
+
+/// What the thread has to do next.
+pub(crate) enum Wait {
+    /// Sleep until the deadline, or until it's woken up.
+    For(Duration),
+    /// Sleep until it's woken up.
+    Signal,
+    /// The deadline is too close to sleep, see `Precision`.
+    Spin(Precision),
+    /// The deadline is passed, the timeout is reached.
+    Reached,
+}
+
+/// Deadline of a timeout. The thread sleeps until the deadline and checks it
+/// again each time it's woken up, so the deadline can be moved in both
+/// directions while the thread is sleeping.
+pub(crate) struct Deadline {
+    state: State,
+    initial: Duration,
+    /// Time removed from a pending deadline beyond zero, deducted from the
+    /// next additions or from the deadline once started.
+    debt: Duration,
+    /// Last time the deadline was reached.
+    expiration: Option<Expiration>,
+    /// Accuracy required when the deadline is reached, see `Precision`.
+    precision: Precision,
+    /// Channel publishing each change, created by the first `watch`.
+    watch: Option<watch::Sender<Option<Instant>>>,
+}
+
+impl Deadline {
+    pub(crate) fn new(dur: Duration, started: bool) -> Self {
+        Self {
+            state: match started {
+                true => running(dur),
+                false => State::Pending(dur),
+            },
+            initial: dur,
+            debt: Duration::ZERO,
+            expiration: None,
+            precision: Precision::default(),
+            watch: None,
+        }
+    }
+    pub(crate) fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+    pub(crate) fn precision(&self) -> Precision {
+        self.precision
+    }
+    /// Change the accuracy of a live deadline. Return true if the thread
+    /// has to be woken up, its sleep depends on the precision.
+    pub(crate) fn set_precision(&mut self, precision: Precision) -> bool {
+        self.precision = precision;
+        matches!(self.state, State::Running(_))
+    }
+    /// Start the countdown of a timeout not started yet.
+    pub(crate) fn start(&mut self) {
+        if let State::Pending(dur) = self.state {
+            self.state = match running(dur) {
+                State::Running(at) => State::Running(at.checked_sub(self.debt).unwrap_or(at)),
+                state => state,
+            };
+            self.debt = Duration::ZERO;
+            self.publish();
+        }
+    }
+    /// Wait again the initial duration. Return true if the thread has to be
+    /// woken up.
+    pub(crate) fn restart(&mut self) -> bool {
+        match self.state {
+            State::Pending(_) => {
+                self.state = State::Pending(self.initial);
+                self.debt = Duration::ZERO;
+            }
+            State::Running(_) | State::Infinite => self.state = running(self.initial),
+            State::Reached => return false,
+        }
+        self.publish();
+        true
+    }
+    /// Start again a reached timeout with the given duration. Return true if
+    /// the thread has to be woken up.
+    pub(crate) fn rearm(&mut self, dur: Duration) -> bool {
+        if let State::Reached = self.state {
+            self.state = running(dur);
+            self.publish();
+            return true;
+        }
+        false
+    }
+    /// Replace the deadline by one `dur` from now, or the delay of a timeout
+    /// not started yet. Return true if the thread has to be woken up.
+    pub(crate) fn set(&mut self, dur: Duration) -> bool {
+        match self.state {
+            State::Pending(_) => {
+                self.state = State::Pending(dur);
+                self.debt = Duration::ZERO;
+                false
+            }
+            State::Running(_) | State::Infinite => {
+                self.state = running(dur);
+                self.publish();
+                true
+            }
+            State::Reached => false,
+        }
+    }
+    /// Called by the thread each time it wakes up.
+    pub(crate) fn next_wait(&mut self, keep_alive: &KeepAliveState) -> Wait {
+        let precision = self.precision;
+        match self.state {
+            State::Pending(_) | State::Infinite => Wait::Signal,
+            State::Running(at) => {
+                let now = Instant::now();
+                if at > now + precision.margin() {
+                    Wait::For(at - now - precision.margin())
+                } else if at > now {
+                    Wait::Spin(precision)
+                } else if keep_alive.is_held() {
+                    Wait::Signal
+                } else {
+                    self.state = State::Reached;
+                    self.expiration = Some(Expiration {
+                        seq: self.expiration.map_or(1, |last| last.seq + 1),
+                        scheduled: at,
+                        fired_at: now,
+                    });
+                    self.publish();
+                    Wait::Reached
+                }
+            }
+            State::Reached => Wait::Reached,
+        }
+    }
+    /// Time left before the deadline.
+    pub(crate) fn remaining(&self) -> Duration {
+        match self.state {
+            State::Pending(dur) => dur,
+            State::Running(at) => at.saturating_duration_since(Instant::now()),
+            State::Infinite => Duration::MAX,
+            State::Reached => Duration::ZERO,
+        }
+    }
+    /// Move the deadline further. Return false if the new deadline can't be
+    /// represented, the deadline is left unchanged.
+    ///
+    /// The additions and subtractions sum up in any order until the deadline
+    /// is reached: a deadline moved before now isn't clamped, so a
+    /// concurrent `add` only gives back what exceeds the time removed.
+    pub(crate) fn add(&mut self, dur: Duration) -> bool {
+        match &mut self.state {
+            State::Pending(pending) => {
+                let repaid = dur.min(self.debt);
+                match pending.checked_add(dur - repaid) {
+                    Some(later) => *pending = later,
+                    None => return false,
+                }
+                self.debt -= repaid;
+            }
+            State::Running(at) => match at.checked_add(dur) {
+                Some(later) => *at = later,
+                None => return false,
+            },
+            // already `Duration::MAX`
+            State::Infinite => return dur.is_zero(),
+            State::Reached => {}
+        }
+        self.publish();
+        true
+    }
+    /// Push the deadline to `Duration::MAX`, when an `add` overflows. A
+    /// running deadline becomes infinite.
+    pub(crate) fn saturate(&mut self) {
+        self.state = match self.state {
+            State::Pending(_) => State::Pending(Duration::MAX),
+            State::Running(_) | State::Infinite => State::Infinite,
+            State::Reached => return,
+        };
+        self.publish();
+    }
+    /// Move the deadline closer, possibly before now, see `add`. Return true
+    /// if the thread has to be woken up.
+    pub(crate) fn sub(&mut self, dur: Duration) -> bool {
+        match &mut self.state {
+            State::Pending(pending) => {
+                self.debt = self.debt.saturating_add(dur.saturating_sub(*pending));
+                *pending = pending.saturating_sub(dur);
+                false
+            }
+            State::Running(at) => {
+                // only clamped if the instant can't be represented
+                *at = at
+                    .checked_sub(dur)
+                    .unwrap_or_else(|| Instant::now().min(*at));
+                self.publish();
+                true
+            }
+            // counted from `Duration::MAX`, the delay left is waited
+            State::Infinite if dur > Duration::ZERO => {
+                self.state = running(Duration::MAX - dur);
+                self.publish();
+                true
+            }
+            State::Infinite | State::Reached => false,
+        }
+    }
+    pub(crate) fn clear(&mut self) {
+        self.state = State::Reached;
+        self.publish();
+    }
+    pub(crate) fn expiration(&self) -> Option<Expiration> {
+        self.expiration
+    }
+    pub(crate) fn is_reached(&self) -> bool {
+        matches!(self.state, State::Reached)
+    }
+    /// Instant of the deadline, none if not started, reached or cancelled.
+    pub(crate) fn at(&self) -> Option<Instant> {
+        match self.state {
+            State::Running(at) => Some(at),
+            _ => None,
+        }
+    }
+    /// Key ordering the timeouts by the instant of their deadline, the last
+    /// one reached for an expired timeout. The ones without a deadline (not
+    /// started, infinite or cancelled) are last.
+    pub(crate) fn order(&self) -> (bool, Option<Instant>) {
+        let at = match self.state {
+            State::Running(at) => Some(at),
+            State::Reached => self.expiration.map(|expiration| expiration.scheduled),
+            State::Pending(_) | State::Infinite => None,
+        };
+        (at.is_none(), at)
+    }
+    /// State of the timeout in a snapshot, the claim tells whether a reached
+    /// deadline was cancelled.
+    pub(crate) fn state(&self, cancelled: bool) -> TimeoutState {
+        match self.state {
+            State::Pending(_) => TimeoutState::Pending,
+            State::Running(_) | State::Infinite => TimeoutState::Running,
+            State::Reached if cancelled => TimeoutState::Cancelled,
+            State::Reached => TimeoutState::Reached,
+        }
+    }
+    /// Receive the instant of the deadline each time it changes.
+    pub(crate) fn watch(&mut self) -> watch::Receiver<Option<Instant>> {
+        let at = self.at();
+        self.watch
+            .get_or_insert_with(|| watch::channel(at).0)
+            .subscribe()
+    }
+    fn publish(&self) {
+        if let Some(watch) = &self.watch {
+            let at = self.at();
+            watch.send_if_modified(|published| {
+                let modified = *published != at;
+                *published = at;
+                modified
+            });
+        }
+    }
+}
+
+/// Keep-alive guards of a timeout. The timeout is suspended while there is
+/// a guard alive, and restart when the last one is dropped.
+#[derive(Default)]
+pub(crate) struct KeepAliveState {
+    count: AtomicUsize,
+}
+
+impl KeepAliveState {
+    /// Called with the deadline locked, so the thread can't be reached in
+    /// the meantime.
+    pub(crate) fn acquire(&self) {
+        self.count.fetch_add(1, Ordering::AcqRel);
+    }
+    /// Return true if it was the last guard, the timeout has to restart.
+    pub(crate) fn release(&self) -> bool {
+        self.count.fetch_sub(1, Ordering::AcqRel) == 1
+    }
+    fn is_held(&self) -> bool {
+        self.count.load(Ordering::Acquire) > 0
+    }
+}
+
+const ARMED: u8 = 0;
+const FIRED: u8 = 1;
+const CANCELLED: u8 = 2;
+
+/// Decide between the callback and a cancellation, whichever claims the
+/// timeout first wins so the callback is never started once a cancel
+/// returned successfully.
+pub(crate) struct Claim {
+    state: AtomicU8,
+    ordering: MemoryOrdering,
+}
+
+impl Claim {
+    pub(crate) fn new(ordering: MemoryOrdering) -> Self {
+        Self {
+            state: AtomicU8::new(ARMED),
+            ordering,
+        }
+    }
+    /// Claimed by the thread before executing the callback. Return false if
+    /// the timeout was cancelled first.
+    pub(crate) fn fire(&self) -> bool {
+        self.transition(ARMED, FIRED)
+    }
+    /// Cancel the timeout. Return false if the callback was already claimed,
+    /// the timeout is cancelled anyway and won't fire again.
+    pub(crate) fn cancel(&self) -> bool {
+        self.state.swap(CANCELLED, self.ordering.update()) != FIRED
+    }
+    /// Allow the callback again once the timeout is re-armed, unless it was
+    /// cancelled in the meantime.
+    pub(crate) fn rearm(&self) {
+        self.transition(FIRED, ARMED);
+    }
+    /// Claimed by the thread, the callback runs or already ran.
+    pub(crate) fn is_fired(&self) -> bool {
+        self.state.load(self.ordering.load()) == FIRED
+    }
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.state.load(self.ordering.load()) == CANCELLED
+    }
+    fn transition(&self, from: u8, to: u8) -> bool {
+        self.state
+            .compare_exchange(from, to, self.ordering.update(), self.ordering.load())
+            .is_ok()
+    }
+}
+
+/// Stop of a re-armable timeout, the thread ends after the cycle in
+/// progress.
+pub(crate) struct Stop {
+    stopped: AtomicBool,
+    ordering: MemoryOrdering,
+}
+
+impl Stop {
+    pub(crate) fn new(ordering: MemoryOrdering) -> Self {
+        Self {
+            stopped: AtomicBool::new(false),
+            ordering,
+        }
+    }
+    pub(crate) fn stop(&self) {
+        self.stopped.store(true, self.ordering.store());
+    }
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopped.load(self.ordering.load())
+    }
+}
+
+/// Longest delay waited from now, a longer one is clamped so the deadline
+/// can be represented on every platform.
+pub(crate) const FOREVER: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+pub(crate) fn from_now(dur: Duration) -> Instant {
+    Instant::now() + dur.min(FOREVER)
+}
+
+/// Tokio deadline `dur` after `from`, a far deadline for a delay which
+/// can't be represented, like `Duration::MAX`.
+pub(crate) fn from(from: tokio::time::Instant, dur: Duration) -> tokio::time::Instant {
+    from.checked_add(dur)
+        .or_else(|| from.checked_add(FOREVER))
+        .unwrap_or(from)
+}
+
+/// Deadline `dur` from now, infinite for `Duration::MAX`.
+fn running(dur: Duration) -> State {
+    match dur {
+        Duration::MAX => State::Infinite,
+        dur => State::Running(from_now(dur)),
+    }
+}
+
+/// Fraction of a duration, used to extend or shrink a timeout
+/// proportionally.
+pub(crate) fn scale(dur: Duration, fraction: f64) -> Result<Duration, TimeoutError> {
+    Duration::try_from_secs_f64(dur.as_secs_f64() * fraction)
+        .map_err(|_| TimeoutError::InvalidDuration)
+}
+
+/// Part of the remaining time given to the next step of a budget, the
+/// fraction of the whole budget is scaled to the fraction not allocated yet.
+/// Return none if the fraction exceeds what's left of the budget.
+pub(crate) fn share(remaining: Duration, fraction: f64, allocated: f64) -> Option<Duration> {
+    // tolerate the rounding of fractions summing to one
+    if !(fraction > 0.0 && allocated + fraction <= 1.0 + 1e-9) {
+        return None;
+    }
+    scale(remaining, (fraction / (1.0 - allocated)).min(1.0)).ok()
+}
+
+/// Duration from now to the given date, zero if the date is already passed.
+#[cfg(feature = "chrono")]
+pub(crate) fn until(deadline: chrono::DateTime<chrono::Utc>) -> Duration {
+    (deadline - chrono::Utc::now())
+        .to_std()
+        .unwrap_or(Duration::ZERO)
+}
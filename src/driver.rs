@@ -0,0 +1,1302 @@
+//! Shared driver, run many keyed dynamic timeouts on a single thread
+use crate::{deadline::from_now, error::TimeoutError, event::Expiration, std_thread};
+use anyhow::{bail, Result};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+type Handler<K, P> = Box<dyn Fn(K, P, Expiration) + Send>;
+
+/// Default granularity of the coarse lane, see `Driver::with_tick`.
+pub const DEFAULT_TICK: Duration = Duration::from_millis(100);
+
+/// Precision of a timeout in the driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Lane {
+    /// Reached at its deadline.
+    #[default]
+    Precise,
+    /// Reached on the first tick after its deadline, so all the coarse
+    /// timeouts expiring in the same tick are reached with a single wake up.
+    Coarse,
+    /// Reached between its deadline and the deadline plus the given
+    /// tolerance: the driver wakes up for it at the end of the window, but
+    /// reaches it with the other timeouts if it wakes up earlier within the
+    /// window. Like the timer leeway of the mobile platforms, the wake ups
+    /// are batched to save CPU and battery.
+    Tolerant(Duration),
+}
+
+/// Priority of a timeout in the driver. The timeouts expired at the same
+/// time, in the same tick or while the driver was late, are reached by
+/// decreasing priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Priority {
+    /// Bulk work, like session cleanups.
+    Low,
+    #[default]
+    Normal,
+    /// Reached before the other expired timeouts, like circuit-breaker
+    /// trips.
+    High,
+}
+
+/// Last key given by `Driver::arm`, the keys are unique across all the
+/// drivers.
+static LAST_KEY: AtomicU64 = AtomicU64::new(0);
+
+/// Key of a timeout armed with `Driver::arm`. The key is a copyable integer,
+/// the caller can keep it instead of a handle to the timeout and it's never
+/// reused for another timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimeoutKey(u64);
+
+struct Timer<P> {
+    deadline: Instant,
+    /// Deadline rounded to the granularity of the lane.
+    wake: Instant,
+    seq: u64,
+    lane: Lane,
+    priority: Priority,
+    /// Tags given with `Driver::tag`.
+    tags: Vec<String>,
+    payload: P,
+}
+
+struct State<K, P> {
+    timers: HashMap<K, Timer<P>>,
+    queue: BTreeMap<(Instant, u64), K>,
+    seq: u64,
+    epoch: Instant,
+    tick: Duration,
+    /// Largest tolerance armed, bound of the window searched for the
+    /// tolerant timeouts.
+    tolerance: Duration,
+    /// Set by `Driver::strict_ordering`.
+    strict: bool,
+    shutdown: bool,
+    fired: u64,
+    overshoot: Duration,
+}
+
+/// Statistics of a driver since its creation, see `Driver::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DriverStats {
+    /// Number of pending timeouts.
+    pub active: usize,
+    /// Number of timeouts reached.
+    pub fired: u64,
+    /// Sum of the delays between the deadlines of the timeouts reached and
+    /// their handling, the coarse timeouts are late from their rounded
+    /// deadline.
+    pub overshoot: Duration,
+}
+
+impl DriverStats {
+    /// Average delay between the deadline of a timeout and its handling.
+    pub fn average_overshoot(&self) -> Duration {
+        match self.fired {
+            0 => Duration::ZERO,
+            fired => {
+                // below the total, so the seconds fit in a u64
+                let nanos = self.overshoot.as_nanos() / fired as u128;
+                Duration::new(
+                    (nanos / 1_000_000_000) as u64,
+                    (nanos % 1_000_000_000) as u32,
+                )
+            }
+        }
+    }
+}
+
+struct Shared<K, P> {
+    state: Mutex<State<K, P>>,
+    condvar: Condvar,
+}
+
+/// Dynamic timeouts identified by a key and sharing a single thread. When a
+/// timeout is reached, the handler is called in the driver thread with the
+/// key and the payload given on insertion. Dropping the driver dismiss all
+/// the pending timeouts and join the thread. A driver created with `polled`
+/// has no thread, the expired timeouts are collected by batches instead.
+///
+/// The timeouts are reached in the order of their deadlines, and the ones
+/// with the same deadline in the order they were armed. The timeouts expired
+/// together, when the driver wakes up, are reached by decreasing priority
+/// (see `insert_with`). A timeout armed with
+/// `Duration::ZERO`, or decreased to zero, is never reached in the calling
+/// thread: the handler is called promptly in the driver thread, before any
+/// longer timeout armed afterward.
+///
+/// The handler is called for one timeout at a time, the next call starts
+/// when the previous one returned. Two precise timeouts with the same
+/// priority and the deadlines T1 < T2 are always reached in this order, even
+/// when the driver is late and reaches them in the same batch. The lanes and
+/// the priorities can reorder the other timeouts, a driver built with
+/// `strict_ordering` reaches all its timeouts in the order of their
+/// deadlines.
+///
+/// The timeouts armed with `insert_coarse` are in a low resolution lane,
+/// their deadlines are rounded up to the driver tick. Thousands of coarse
+/// timeouts, like session timeouts, only wake up the thread once per tick
+/// while a few precise timeouts are still reached on time. A timeout armed
+/// with `insert_tolerant` accepts to be late by a tolerance, it's reached
+/// with the other timeouts when the driver wakes up within the window, out
+/// of the order of the deadlines.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::driver::Driver;
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let driver = Driver::new(|key: &str, payload: u32| {
+///     println!("{} expired with {}", key, payload);
+/// });
+/// driver.insert("session", TWENTY, 42);
+/// driver.add(&"session", TWENTY).unwrap();
+/// ```
+pub struct Driver<K, P> {
+    shared: Arc<Shared<K, P>>,
+    thread: Option<JoinHandle<()>>,
+    /// Handler of a driver created with `manual`, called by `on_wake`.
+    manual: Mutex<Option<Handler<K, P>>>,
+}
+
+impl<K, P> Driver<K, P>
+where
+    K: Hash + Eq + Clone + Send + 'static,
+    P: Send + 'static,
+{
+    /// Create a new driver and its thread. The `handler` is called in the
+    /// driver thread for each timeout reached.
+    ///
+    /// # Example
+    /// ```
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// let driver = Driver::new(|key: u64, _payload: ()| {
+    ///     println!("timeout {} reached", key);
+    /// });
+    /// ```
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(K, P) + Send + 'static,
+    {
+        Self::with_tick(DEFAULT_TICK, handler)
+    }
+    /// Create a new driver whose coarse lane has the given granularity.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// let driver = Driver::with_tick(Duration::from_secs(1), |key: u64, _payload: ()| {
+    ///     println!("session {} expired", key);
+    /// });
+    /// driver.insert_coarse(1, Duration::from_secs(30), ());
+    /// ```
+    pub fn with_tick<F>(tick: Duration, handler: F) -> Self
+    where
+        F: Fn(K, P) + Send + 'static,
+    {
+        Self::spawn(tick, Box::new(move |key, payload, _| handler(key, payload)))
+    }
+    /// Create a new driver whose handler also gets the expiration of each
+    /// timeout: its deadline and the moment the driver reached it, so the
+    /// handler can compensate for the lateness when it arms the next one.
+    /// The deadline is the one after the adjustments, not rounded to the
+    /// tick for a coarse timeout.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{driver::Driver, event::Expiration};
+    ///
+    /// let driver = Driver::with_expiration(|key: u64, _: (), expiration: Expiration| {
+    ///     println!("timeout {} late of {:?}", key, expiration.lateness());
+    /// });
+    /// driver.insert(1, Duration::from_millis(20), ());
+    /// ```
+    pub fn with_expiration<F>(handler: F) -> Self
+    where
+        F: Fn(K, P, Expiration) + Send + 'static,
+    {
+        Self::spawn(DEFAULT_TICK, Box::new(handler))
+    }
+    fn spawn(tick: Duration, handler: Handler<K, P>) -> Self {
+        let mut driver = Self::without_thread(tick);
+        let thread_shared = driver.shared.clone();
+        driver.thread = Some(thread::spawn(move || run(thread_shared, handler)));
+        driver
+    }
+    /// Create a driver without thread and handler, the expired timeouts are
+    /// collected by batches with `poll_expired_batch`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::polled();
+    /// driver.insert("connection", TWENTY, ());
+    /// std::thread::sleep(TWENTY);
+    /// let mut expired = vec![];
+    /// driver.poll_expired_batch(&mut expired);
+    /// assert_eq!(expired, vec![("connection", ())]);
+    /// ```
+    pub fn polled() -> Self {
+        Self::without_thread(DEFAULT_TICK)
+    }
+    /// Reach all the timeouts strictly in the order of their deadlines, and
+    /// the ones with the same deadline in the order they were armed, as the
+    /// protocols with ordered retransmissions or leases require. When the
+    /// driver wakes up for a timeout, the coarse and tolerant timeouts with
+    /// an earlier deadline are reached before it, earlier than their lane
+    /// allows, and the priorities are ignored.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::{Driver, Lane, Priority};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::polled().strict_ordering();
+    /// driver.insert_with("lease", TWENTY, Lane::Coarse, Priority::Low, ());
+    /// driver.insert_with("retransmit", TWENTY * 2, Lane::Precise, Priority::High, ());
+    /// std::thread::sleep(TWENTY * 3);
+    /// let mut expired = vec![];
+    /// driver.poll_expired_batch(&mut expired);
+    /// assert_eq!(expired, vec![("lease", ()), ("retransmit", ())]);
+    /// ```
+    pub fn strict_ordering(self) -> Self {
+        self.state().strict = true;
+        self
+    }
+    fn without_thread(tick: Duration) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    timers: HashMap::new(),
+                    queue: BTreeMap::new(),
+                    seq: 0,
+                    epoch: Instant::now(),
+                    tick,
+                    tolerance: Duration::ZERO,
+                    strict: false,
+                    shutdown: false,
+                    fired: 0,
+                    overshoot: Duration::ZERO,
+                }),
+                condvar: Condvar::new(),
+            }),
+            thread: None,
+            manual: Mutex::new(None),
+        }
+    }
+    /// Create a driver without thread, run by the event loop of the
+    /// embedder (a game loop, a GUI frame tick...): the loop sleeps until
+    /// `next_wake` and calls `on_wake`, which calls the `handler` for each
+    /// timeout reached. The timeouts are adjusted like with a driver
+    /// created with `new`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::manual(|key: &str, _: ()| println!("{} expired", key));
+    /// driver.insert("respawn", TWENTY, ());
+    /// driver.add(&"respawn", TWENTY).unwrap();
+    /// // the frame loop
+    /// while let Some(wake) = driver.next_wake() {
+    ///     std::thread::sleep(wake.saturating_duration_since(Instant::now()));
+    ///     driver.on_wake(Instant::now());
+    /// }
+    /// ```
+    pub fn manual<F>(handler: F) -> Self
+    where
+        F: Fn(K, P) + Send + 'static,
+    {
+        let driver = Self::without_thread(DEFAULT_TICK);
+        let handler: Handler<K, P> = Box::new(move |key, payload, _| handler(key, payload));
+        *driver.manual.lock().unwrap() = Some(handler);
+        driver
+    }
+    /// Next instant the driver has to be woken up, none if no timeout is
+    /// pending. The instant moves when the timeouts are adjusted, an event
+    /// loop reads it again before each sleep.
+    pub fn next_wake(&self) -> Option<Instant> {
+        self.state()
+            .queue
+            .first_key_value()
+            .map(|((wake, _), _)| *wake)
+    }
+    /// Reach the timeouts expired at `now` and call the handler of a driver
+    /// created with `manual` for each one, in the order they are reached.
+    /// The driver isn't locked while the handler runs, the handler can arm
+    /// and adjust timeouts but not call `on_wake` again. Do nothing with a
+    /// driver created otherwise.
+    ///
+    /// # Return
+    /// Return the number of timeouts reached.
+    pub fn on_wake(&self, now: Instant) -> usize {
+        let handler = self.manual.lock().unwrap_or_else(PoisonError::into_inner);
+        let handler = match handler.as_ref() {
+            Some(handler) => handler,
+            None => return 0,
+        };
+        let expired: Vec<_> = self.state().take_expired(now).collect();
+        let reached = expired.len();
+        for (key, payload, expiration) in expired {
+            handler(key, payload, expiration);
+        }
+        reached
+    }
+    /// Move all the expired timeouts into `batch` with their payloads, in
+    /// the order they are reached, under a single lock of the driver. With a
+    /// driver created with `new`, the timeouts are given to the handler as
+    /// soon as they expire, the batch gets the ones not handled yet.
+    ///
+    /// # Return
+    /// Return the number of timeouts added to the batch.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// let driver = Driver::polled();
+    /// for connection in 0..1000 {
+    ///     driver.insert(connection, Duration::ZERO, ());
+    /// }
+    /// let mut idle = Vec::with_capacity(1000);
+    /// assert_eq!(driver.poll_expired_batch(&mut idle), 1000);
+    /// ```
+    pub fn poll_expired_batch(&self, batch: &mut Vec<(K, P)>) -> usize {
+        let len = batch.len();
+        let expired = self.state().take_expired(Instant::now());
+        batch.extend(expired.map(|(key, payload, _)| (key, payload)));
+        batch.len() - len
+    }
+    /// Same as `poll_expired_batch`, with the expiration of each timeout:
+    /// its deadline and the moment it was collected.
+    ///
+    /// # Return
+    /// Return the number of timeouts added to the batch.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::polled();
+    /// driver.insert("connection", TWENTY, ());
+    /// std::thread::sleep(TWENTY * 2);
+    /// let mut expired = vec![];
+    /// driver.poll_expired_with(&mut expired);
+    /// let (_, _, expiration) = expired[0];
+    /// assert!(expiration.lateness() >= TWENTY);
+    /// ```
+    pub fn poll_expired_with(&self, batch: &mut Vec<(K, P, Expiration)>) -> usize {
+        let len = batch.len();
+        batch.extend(self.state().take_expired(Instant::now()));
+        batch.len() - len
+    }
+    /// Arm a timeout for the given key, the handler will be called with the
+    /// payload after `dur`. If a timeout with the same key was pending, it's
+    /// replaced and its payload is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|_: u64, _: ()| {});
+    /// assert!(driver.insert(1, TWENTY, ()).is_none());
+    /// assert!(driver.insert(1, TWENTY, ()).is_some());
+    /// ```
+    pub fn insert(&self, key: K, dur: Duration, payload: P) -> Option<P> {
+        self.insert_with(key, dur, Lane::Precise, Priority::Normal, payload)
+    }
+    /// Arm a timeout in the coarse lane, it's reached on the first tick
+    /// after `dur`. The `add` and `sub` calls keep the timeout in its lane.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::with_tick(TWENTY, |_: u64, _: ()| {});
+    /// driver.insert_coarse(1, TWENTY * 10, ());
+    /// assert!(driver.remaining(&1).unwrap() <= TWENTY * 11);
+    /// ```
+    pub fn insert_coarse(&self, key: K, dur: Duration, payload: P) -> Option<P> {
+        self.insert_with(key, dur, Lane::Coarse, Priority::Normal, payload)
+    }
+    /// Arm a timeout reached at most `tolerance` after `dur`, see
+    /// `Lane::Tolerant`. The `add` and `sub` calls keep the tolerance.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|key: &str, _: ()| println!("{} reached", key));
+    /// driver.insert("flush", TWENTY * 2, ());
+    /// // reached with the flush, twenty milliseconds before its deadline
+    /// driver.insert_tolerant("sync", TWENTY, TWENTY * 2, ());
+    /// ```
+    pub fn insert_tolerant(
+        &self,
+        key: K,
+        dur: Duration,
+        tolerance: Duration,
+        payload: P,
+    ) -> Option<P> {
+        self.insert_with(
+            key,
+            dur,
+            Lane::Tolerant(tolerance),
+            Priority::Normal,
+            payload,
+        )
+    }
+    /// Arm a timeout in the given lane and with the given priority. The
+    /// `add` and `sub` calls keep its lane and its priority.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::{Driver, Lane, Priority};
+    ///
+    /// let driver = Driver::polled();
+    /// driver.insert_with("session", Duration::ZERO, Lane::Coarse, Priority::Low, ());
+    /// driver.insert_with("breaker", Duration::ZERO, Lane::Precise, Priority::High, ());
+    /// std::thread::sleep(Duration::from_millis(200));
+    /// let mut expired = vec![];
+    /// driver.poll_expired_batch(&mut expired);
+    /// assert_eq!(expired, vec![("breaker", ()), ("session", ())]);
+    /// ```
+    pub fn insert_with(
+        &self,
+        key: K,
+        dur: Duration,
+        lane: Lane,
+        priority: Priority,
+        payload: P,
+    ) -> Option<P> {
+        let mut state = self.state();
+        let old = state.remove(&key);
+        state.arm(key, from_now(dur), Armed::new(lane, priority, payload));
+        self.shared.condvar.notify_one();
+        old.map(|timer| timer.payload)
+    }
+    /// Move a std timeout, waited by its own thread, to the driver under
+    /// the given key, its remaining time is kept. The std timeout is stopped
+    /// without executing its callback, the handler of the driver is called
+    /// with the payload instead. Armed in the precise lane, see `set_lane`.
+    ///
+    /// # Return
+    /// Return a result with an error if the std timeout already appened or
+    /// isn't started yet.
+    /// Otherwise it return the payload of the timeout replaced under the
+    /// key, if any.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{driver::Driver, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let session = DynTimeout::new(TWENTY * 10, || println!("session expired"));
+    /// let driver = Driver::new(|key: &str, _: ()| println!("{} expired", key));
+    /// // the service becomes idle, its timeouts share a single thread
+    /// driver.take_over("session", session, ()).unwrap();
+    /// assert!(driver.remaining(&"session").unwrap() <= TWENTY * 10);
+    /// ```
+    #[track_caller]
+    pub fn take_over(
+        &self,
+        key: K,
+        timeout: std_thread::DynTimeout,
+        payload: P,
+    ) -> Result<Option<P>> {
+        let (record, _) = timeout.hand_over()?;
+        let remaining = record.config().duration;
+        Ok(self.insert(key, remaining, payload))
+    }
+    /// Move the timeout of the given key to another lane, its deadline is
+    /// kept. With `Lane::Coarse` when the service is idle, the driver wakes
+    /// up once per tick for all of them, and back to `Lane::Precise` when
+    /// busy.
+    ///
+    /// # Return
+    /// Return a result with an error if there is no pending timeout for the
+    /// key, it may have been reached or cancelled.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::{Driver, Lane};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::with_tick(TWENTY, |_: u64, _: ()| {});
+    /// driver.insert(1, TWENTY * 10, ());
+    /// driver.set_lane(&1, Lane::Coarse).unwrap();
+    /// assert!(driver.remaining(&1).unwrap() <= TWENTY * 11);
+    /// ```
+    pub fn set_lane(&self, key: &K, lane: Lane) -> Result<()> {
+        let mut state = self.state();
+        let timer = match state.remove(key) {
+            Some(timer) => timer,
+            None => bail!("Timeout not found"),
+        };
+        let deadline = timer.deadline;
+        let mut timer: Armed<P> = timer.into();
+        timer.lane = lane;
+        state.arm(key.clone(), deadline, timer);
+        self.shared.condvar.notify_one();
+        Ok(())
+    }
+    /// Move all the pending timeouts to the given lane, see `set_lane`.
+    ///
+    /// # Return
+    /// Return the number of timeouts moved.
+    pub fn set_all_lanes(&self, lane: Lane) -> usize {
+        let mut state = self.state();
+        let keys: Vec<_> = state.queue.values().cloned().collect();
+        for key in &keys {
+            let timer = state.remove(key).unwrap();
+            let deadline = timer.deadline;
+            let mut timer: Armed<P> = timer.into();
+            timer.lane = lane;
+            state.arm(key.clone(), deadline, timer);
+        }
+        self.shared.condvar.notify_one();
+        keys.len()
+    }
+    /// Increase the delay before the timeout of the given key.
+    ///
+    /// # Return
+    /// Return a result with an error if there is no pending timeout for the
+    /// key, it may have been reached or cancelled.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|_: u64, _: ()| {});
+    /// driver.insert(1, TWENTY, ());
+    /// driver.add(&1, TWENTY).unwrap();
+    /// assert!(driver.add(&2, TWENTY).is_err());
+    /// ```
+    pub fn add(&self, key: &K, dur: Duration) -> Result<()> {
+        self.adjust(key, |deadline| deadline.checked_add(dur))
+    }
+    /// Increase the delay before the timeout of the given key, or arm a
+    /// timeout for `dur` if there is none, with a single lookup. It's the
+    /// upsert of a debounce map deduplicating the events by key. An
+    /// extended timeout keeps its payload, lane and priority, the given
+    /// payload is dropped.
+    ///
+    /// # Return
+    /// Return a result with `TimeoutError::InvalidDuration` if the extended
+    /// deadline overflows.
+    /// Otherwise it return true if a pending timeout was extended, false if
+    /// a new one was armed.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|path: &str, _: ()| println!("{} changed", path));
+    /// // a burst of events for the same file
+    /// assert!(!driver.insert_or_add("config.toml", TWENTY, ()).unwrap());
+    /// assert!(driver.insert_or_add("config.toml", TWENTY, ()).unwrap());
+    /// assert_eq!(driver.len(), 1);
+    /// ```
+    pub fn insert_or_add(&self, key: K, dur: Duration, payload: P) -> Result<bool> {
+        let mut state = self.state();
+        let extended = match state.remove(&key) {
+            Some(timer) => {
+                let deadline = timer.deadline.checked_add(dur);
+                state.arm(key, deadline.unwrap_or(timer.deadline), timer.into());
+                if deadline.is_none() {
+                    bail!(TimeoutError::InvalidDuration)
+                }
+                true
+            }
+            None => {
+                let timer = Armed::new(Lane::Precise, Priority::Normal, payload);
+                state.arm(key, from_now(dur), timer);
+                false
+            }
+        };
+        self.shared.condvar.notify_one();
+        Ok(extended)
+    }
+    /// Decrease the delay before the timeout of the given key. If `dur` is
+    /// greater than the remaining time, the timeout is reached immediately,
+    /// after the timeouts already expired.
+    ///
+    /// # Return
+    /// Return a result with an error if there is no pending timeout for the
+    /// key, it may have been reached or cancelled.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    /// const TEN: Duration = Duration::from_millis(10);
+    ///
+    /// let driver = Driver::new(|_: u64, _: ()| {});
+    /// driver.insert(1, TWENTY, ());
+    /// driver.sub(&1, TEN).unwrap();
+    /// ```
+    pub fn sub(&self, key: &K, dur: Duration) -> Result<()> {
+        self.adjust(key, |deadline| {
+            let now = Instant::now();
+            // an expired deadline is kept, so it's not reached after the
+            // timeouts armed in the meantime
+            let earlier = deadline
+                .checked_sub(dur)
+                .map_or(now, |earlier| earlier.max(now));
+            Some(earlier.min(deadline))
+        })
+    }
+    /// Dismiss the timeout of the given key.
+    ///
+    /// # Return
+    /// Return the payload of the timeout, or none if there was no pending
+    /// timeout for this key.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|_: u64, _: &str| panic!("never append"));
+    /// driver.insert(1, TWENTY, "payload");
+    /// assert_eq!(driver.cancel(&1), Some("payload"));
+    /// ```
+    pub fn cancel(&self, key: &K) -> Option<P> {
+        let timer = self.state().remove(key);
+        timer.map(|timer| timer.payload)
+    }
+    /// Time left before the timeout of the given key is reached, or none if
+    /// there is no pending timeout for this key.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|_: u64, _: ()| {});
+    /// driver.insert(1, TWENTY, ());
+    /// assert!(driver.remaining(&1).unwrap() <= TWENTY);
+    /// ```
+    pub fn remaining(&self, key: &K) -> Option<Duration> {
+        let state = self.state();
+        let timer = state.timers.get(key)?;
+        Some(timer.wake.saturating_duration_since(Instant::now()))
+    }
+    /// Call `f` with the payload of the timeout of the given key, or return
+    /// none if there is no pending timeout for this key.
+    #[cfg(feature = "durable")]
+    pub(crate) fn with_payload<R>(&self, key: &K, f: impl FnOnce(&P) -> R) -> Option<R> {
+        let state = self.state();
+        state.timers.get(key).map(|timer| f(&timer.payload))
+    }
+    /// Tag the timeout of the given key, like `"tenant=acme"`, so it can
+    /// be extended or cancelled with the other timeouts of the tag in a
+    /// single call. A timeout has any number of tags, they are kept by
+    /// `add`, `sub`, `insert_or_add` and the snapshots, and dropped when
+    /// the key is armed again with an `insert`.
+    ///
+    /// # Return
+    /// Return a result with an error if there is no pending timeout for the
+    /// key, it may have been reached or cancelled.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|_: &str, _: ()| {});
+    /// driver.insert("acme/session/1", TWENTY, ());
+    /// driver.tag(&"acme/session/1", "tenant=acme").unwrap();
+    /// assert_eq!(driver.tagged("tenant=acme"), vec!["acme/session/1"]);
+    /// ```
+    pub fn tag(&self, key: &K, tag: impl Into<String>) -> Result<()> {
+        let mut state = self.state();
+        let timer = match state.timers.get_mut(key) {
+            Some(timer) => timer,
+            None => bail!("Timeout not found"),
+        };
+        let tag = tag.into();
+        if !timer.tags.contains(&tag) {
+            timer.tags.push(tag);
+        }
+        Ok(())
+    }
+    /// Remove a tag from the timeout of the given key.
+    ///
+    /// # Return
+    /// Return true if the timeout was pending and had the tag.
+    pub fn untag(&self, key: &K, tag: &str) -> bool {
+        let mut state = self.state();
+        let Some(timer) = state.timers.get_mut(key) else {
+            return false;
+        };
+        let len = timer.tags.len();
+        timer.tags.retain(|other| other != tag);
+        timer.tags.len() != len
+    }
+    /// Keys of the pending timeouts with the given tag, ordered by
+    /// deadline.
+    pub fn tagged(&self, tag: &str) -> Vec<K> {
+        self.state().tagged(tag)
+    }
+    /// Increase the delay before all the timeouts with the given tag, to
+    /// freeze the timeouts of a tenant during its migration for instance.
+    /// The pending timeouts are scanned, the cost grows with their number.
+    ///
+    /// # Return
+    /// Return a result with `TimeoutError::InvalidDuration` if an extended
+    /// deadline overflows, the other timeouts of the tag are extended
+    /// anyway.
+    /// Otherwise it return the number of timeouts extended.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|_: u64, _: ()| {});
+    /// for key in 0..3 {
+    ///     driver.insert(key, TWENTY, ());
+    ///     driver.tag(&key, if key < 2 { "tenant=acme" } else { "tenant=initech" }).unwrap();
+    /// }
+    /// assert_eq!(driver.add_tagged("tenant=acme", Duration::from_secs(3600)).unwrap(), 2);
+    /// assert!(driver.remaining(&0).unwrap() > TWENTY);
+    /// assert!(driver.remaining(&2).unwrap() <= TWENTY);
+    /// ```
+    pub fn add_tagged(&self, tag: &str, dur: Duration) -> Result<usize> {
+        let mut state = self.state();
+        let keys = state.tagged(tag);
+        let mut overflow = false;
+        for key in &keys {
+            let timer = state.remove(key).unwrap();
+            let deadline = timer.deadline.checked_add(dur);
+            overflow |= deadline.is_none();
+            state.arm(
+                key.clone(),
+                deadline.unwrap_or(timer.deadline),
+                timer.into(),
+            );
+        }
+        self.shared.condvar.notify_one();
+        if overflow {
+            bail!(TimeoutError::InvalidDuration)
+        }
+        Ok(keys.len())
+    }
+    /// Dismiss all the timeouts with the given tag.
+    ///
+    /// # Return
+    /// Return the keys and the payloads of the timeouts dismissed, ordered
+    /// by deadline.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|_: u64, _: &str| panic!("never append"));
+    /// driver.insert(1, TWENTY, "payload");
+    /// driver.tag(&1, "tenant=acme").unwrap();
+    /// assert_eq!(driver.cancel_tagged("tenant=acme"), vec![(1, "payload")]);
+    /// assert!(driver.is_empty());
+    /// ```
+    pub fn cancel_tagged(&self, tag: &str) -> Vec<(K, P)> {
+        let mut state = self.state();
+        let keys = state.tagged(tag);
+        keys.into_iter()
+            .map(|key| {
+                let timer = state.remove(&key).unwrap();
+                (key, timer.payload)
+            })
+            .collect()
+    }
+    /// Number of pending timeouts.
+    pub fn len(&self) -> usize {
+        self.state().timers.len()
+    }
+    /// Return true if there is no pending timeout.
+    pub fn is_empty(&self) -> bool {
+        self.state().timers.is_empty()
+    }
+    /// Statistics of the driver, to monitor the number of timers and how
+    /// late they are handled. See the `prometheus` module to export them.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// let driver = Driver::new(|_: u64, _: ()| {});
+    /// driver.insert(1, Duration::from_secs(60), ());
+    /// assert_eq!(driver.stats().active, 1);
+    /// ```
+    pub fn stats(&self) -> DriverStats {
+        let state = self.state();
+        DriverStats {
+            active: state.timers.len(),
+            fired: state.fired,
+            overshoot: state.overshoot,
+        }
+    }
+    /// Take a snapshot of all the pending timeouts with their remaining time
+    /// and a copy of their payloads. The snapshot can be serialized with the
+    /// `serde` feature and given to `restore` after a restart.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|_: u64, _: &str| {});
+    /// driver.insert(1, TWENTY, "payload");
+    /// let snapshot = driver.snapshot();
+    /// assert_eq!(snapshot.timers.len(), 1);
+    /// assert_eq!(snapshot.timers[0].payload, "payload");
+    /// ```
+    pub fn snapshot(&self) -> Snapshot<K, P>
+    where
+        P: Clone,
+    {
+        let state = self.state();
+        let now = Instant::now();
+        Snapshot {
+            taken_at: SystemTime::now(),
+            timers: state
+                .queue
+                .values()
+                .map(|key| {
+                    let timer = &state.timers[key];
+                    SnapshotEntry {
+                        key: key.clone(),
+                        remaining: timer.deadline.saturating_duration_since(now),
+                        lane: timer.lane,
+                        priority: timer.priority,
+                        tags: timer.tags.clone(),
+                        payload: timer.payload.clone(),
+                    }
+                })
+                .collect(),
+        }
+    }
+    /// Re-arm all the timeouts of a snapshot. The time elapsed since the
+    /// snapshot was taken is deduced from the remaining durations, so the
+    /// timeouts expired in the meantime are reached immediately.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::Driver;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|_: u64, _: &str| {});
+    /// driver.insert(1, TWENTY, "payload");
+    /// let snapshot = driver.snapshot();
+    /// drop(driver);
+    ///
+    /// let driver = Driver::new(|_: u64, payload: &str| println!("{}", payload));
+    /// driver.restore(snapshot);
+    /// ```
+    pub fn restore(&self, snapshot: Snapshot<K, P>) {
+        let elapsed = snapshot.taken_at.elapsed().unwrap_or_default();
+        let mut state = self.state();
+        for entry in snapshot.timers {
+            state.remove(&entry.key);
+            let remaining = entry.remaining.saturating_sub(elapsed);
+            let timer = Armed {
+                lane: entry.lane,
+                priority: entry.priority,
+                tags: entry.tags,
+                payload: entry.payload,
+            };
+            state.arm(entry.key, from_now(remaining), timer);
+        }
+        self.shared.condvar.notify_one();
+    }
+    fn adjust<F>(&self, key: &K, f: F) -> Result<()>
+    where
+        F: FnOnce(Instant) -> Option<Instant>,
+    {
+        let mut state = self.state();
+        let timer = match state.remove(key) {
+            Some(timer) => timer,
+            None => bail!("Timeout not found"),
+        };
+        let deadline = f(timer.deadline);
+        state.arm(
+            key.clone(),
+            deadline.unwrap_or(timer.deadline),
+            timer.into(),
+        );
+        if deadline.is_none() {
+            bail!(TimeoutError::InvalidDuration)
+        }
+        self.shared.condvar.notify_one();
+        Ok(())
+    }
+    fn state(&self) -> MutexGuard<'_, State<K, P>> {
+        // The lock is never held while running the handler, the state can't
+        // be left inconsistent by a panic.
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<P> Driver<TimeoutKey, P>
+where
+    P: Send + 'static,
+{
+    /// Arm a timeout under a new key, the handler will be called with the
+    /// key and the payload after `dur`. The other methods of the driver take
+    /// the returned key.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::driver::{Driver, TimeoutKey};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let driver = Driver::new(|key: TimeoutKey, peer: &str| {
+    ///     println!("{:?} for {} expired", key, peer);
+    /// });
+    /// let key = driver.arm(TWENTY, "127.0.0.1:4000");
+    /// driver.extend(key, TWENTY).unwrap();
+    /// assert_eq!(driver.cancel(&key), Some("127.0.0.1:4000"));
+    /// ```
+    pub fn arm(&self, dur: Duration, payload: P) -> TimeoutKey {
+        let key = TimeoutKey(LAST_KEY.fetch_add(1, Ordering::Relaxed) + 1);
+        self.insert(key, dur, payload);
+        key
+    }
+    /// Arm a timeout in the coarse lane under a new key, see
+    /// `insert_coarse`.
+    pub fn arm_coarse(&self, dur: Duration, payload: P) -> TimeoutKey {
+        let key = TimeoutKey(LAST_KEY.fetch_add(1, Ordering::Relaxed) + 1);
+        self.insert_coarse(key, dur, payload);
+        key
+    }
+    /// Increase the delay before the timeout of the given key, see `add`.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout was reached or
+    /// cancelled.
+    /// Otherwise it return an empty success.
+    pub fn extend(&self, key: TimeoutKey, dur: Duration) -> Result<()> {
+        self.add(&key, dur)
+    }
+}
+
+/// What is kept of a timer when it's re-armed with another deadline.
+struct Armed<P> {
+    lane: Lane,
+    priority: Priority,
+    tags: Vec<String>,
+    payload: P,
+}
+
+impl<P> Armed<P> {
+    fn new(lane: Lane, priority: Priority, payload: P) -> Self {
+        Self {
+            lane,
+            priority,
+            tags: vec![],
+            payload,
+        }
+    }
+}
+
+impl<P> From<Timer<P>> for Armed<P> {
+    fn from(timer: Timer<P>) -> Self {
+        Self {
+            lane: timer.lane,
+            priority: timer.priority,
+            tags: timer.tags,
+            payload: timer.payload,
+        }
+    }
+}
+
+impl<P> Timer<P> {
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|other| other == tag)
+    }
+    /// Whether a tolerant timeout passed its deadline, the other ones are
+    /// due at their wake up.
+    fn is_due(&self, now: Instant) -> bool {
+        matches!(self.lane, Lane::Tolerant(_)) && self.deadline <= now
+    }
+}
+
+impl<K, P> State<K, P>
+where
+    K: Hash + Eq + Clone,
+{
+    fn arm(&mut self, key: K, deadline: Instant, timer: Armed<P>) {
+        let Armed {
+            lane,
+            priority,
+            tags,
+            payload,
+        } = timer;
+        self.seq += 1;
+        let seq = self.seq;
+        let wake = match lane {
+            Lane::Precise => deadline,
+            Lane::Coarse => self.round(deadline),
+            Lane::Tolerant(tolerance) => {
+                self.tolerance = self.tolerance.max(tolerance);
+                deadline.checked_add(tolerance).unwrap_or(deadline)
+            }
+        };
+        self.queue.insert((wake, seq), key.clone());
+        self.timers.insert(
+            key,
+            Timer {
+                deadline,
+                wake,
+                seq,
+                lane,
+                priority,
+                tags,
+                payload,
+            },
+        );
+    }
+    /// Keys of the timeouts with the given tag, ordered by deadline.
+    fn tagged(&self, tag: &str) -> Vec<K> {
+        self.queue
+            .values()
+            .filter(|key| self.timers[*key].has_tag(tag))
+            .cloned()
+            .collect()
+    }
+    fn remove(&mut self, key: &K) -> Option<Timer<P>> {
+        let timer = self.timers.remove(key)?;
+        self.queue.remove(&(timer.wake, timer.seq));
+        Some(timer)
+    }
+    /// Remove the timeouts expired at `now`, by decreasing priority and in
+    /// the order they are reached for the same priority.
+    fn take_expired(&mut self, now: Instant) -> impl Iterator<Item = (K, P, Expiration)> {
+        let mut expired = vec![];
+        // the tolerant timeouts woken up later than `now` but already
+        // passed their deadline are reached in the same batch
+        let window = now.checked_add(self.tolerance).unwrap_or(now);
+        let mut due: Vec<_> = self
+            .queue
+            .range(..=(window, u64::MAX))
+            .filter(|((wake, _), key)| *wake <= now || self.timers[*key].is_due(now))
+            .map(|(entry, _)| *entry)
+            .collect();
+        // in strict mode, the timeouts of the other lanes with a deadline
+        // before the last one reached are reached in the same batch
+        let last = due
+            .iter()
+            .map(|entry| self.timers[&self.queue[entry]].deadline)
+            .max();
+        if let (true, Some(last)) = (self.strict, last) {
+            let window = last
+                .checked_add(self.tick + self.tolerance)
+                .unwrap_or(last)
+                .max(window);
+            due = self
+                .queue
+                .range(..=(window, u64::MAX))
+                .filter(|((wake, _), key)| {
+                    let timer = &self.timers[*key];
+                    *wake <= now || timer.is_due(now) || timer.deadline <= last
+                })
+                .map(|(entry, _)| *entry)
+                .collect();
+        }
+        for entry in due {
+            let key = self.queue.remove(&entry).unwrap();
+            let timer = self.timers.remove(&key).unwrap();
+            self.count_fired(timer.wake, now);
+            // a keyed timeout expires once, a new one is armed under the key
+            let expiration = Expiration {
+                seq: 1,
+                scheduled: timer.deadline,
+                fired_at: now,
+            };
+            expired.push((timer.priority, timer.seq, key, timer.payload, expiration));
+        }
+        if self.strict {
+            expired.sort_by_key(|(_, seq, _, _, expiration)| (expiration.scheduled, *seq));
+        } else {
+            // stable, the order of the deadlines is kept
+            expired.sort_by_key(|(priority, ..)| Reverse(*priority));
+        }
+        expired
+            .into_iter()
+            .map(|(_, _, key, payload, expiration)| (key, payload, expiration))
+    }
+    fn count_fired(&mut self, wake: Instant, now: Instant) {
+        self.fired += 1;
+        self.overshoot += now.saturating_duration_since(wake);
+    }
+    /// Round up the deadline to the next tick.
+    fn round(&self, deadline: Instant) -> Instant {
+        let tick = self.tick.as_nanos();
+        if tick == 0 {
+            return deadline;
+        }
+        let since = deadline.saturating_duration_since(self.epoch).as_nanos();
+        let ticks = since.div_ceil(tick);
+        // the driver would have to run for centuries to overflow
+        self.epoch + Duration::from_nanos((ticks * tick) as u64)
+    }
+}
+
+fn run<K, P>(shared: Arc<Shared<K, P>>, handler: Handler<K, P>)
+where
+    K: Hash + Eq + Clone,
+{
+    let mut state = shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+    while !state.shutdown {
+        let now = Instant::now();
+        let next = state
+            .queue
+            .first_key_value()
+            .map(|((deadline, _), _)| *deadline);
+        state = match next {
+            Some(deadline) if deadline <= now => {
+                let expired = state.take_expired(now);
+                drop(state);
+                for (key, payload, expiration) in expired {
+                    handler(key, payload, expiration);
+                }
+                shared.state.lock().unwrap_or_else(PoisonError::into_inner)
+            }
+            Some(deadline) => {
+                let (state, _) = shared
+                    .condvar
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(PoisonError::into_inner);
+                state
+            }
+            None => shared
+                .condvar
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner),
+        };
+    }
+}
+
+impl<K, P> Drop for Driver<K, P> {
+    fn drop(&mut self) {
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .shutdown = true;
+        self.shared.condvar.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Pending timeouts of a driver at a given time, see `Driver::snapshot`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot<K, P> {
+    /// Wall clock time of the snapshot.
+    pub taken_at: SystemTime,
+    /// Pending timeouts, ordered by deadline.
+    pub timers: Vec<SnapshotEntry<K, P>>,
+}
+
+/// A pending timeout in a snapshot.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnapshotEntry<K, P> {
+    pub key: K,
+    pub remaining: Duration,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lane: Lane,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: Priority,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tags: Vec<String>,
+    pub payload: P,
+}
@@ -0,0 +1,386 @@
+//! Durable driver, keep the keyed timeouts in a write-ahead log so they
+//! survive a restart
+use crate::{deadline::FOREVER, driver::Driver, error::TimeoutError};
+use anyhow::{bail, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    hash::Hash,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Line of the log. Each `Arm` has a new generation, the other records of
+/// a timeout carry the generation of its `Arm`: a key re-armed while its
+/// previous timeout is reached keeps the new one. The logs
+/// written without generations are read with the generation zero.
+#[derive(Serialize, Deserialize)]
+enum Record<K, P> {
+    Arm {
+        key: K,
+        #[serde(default)]
+        generation: u64,
+        deadline: SystemTime,
+        payload: P,
+    },
+    Add {
+        key: K,
+        #[serde(default)]
+        generation: u64,
+        dur: Duration,
+    },
+    Sub {
+        key: K,
+        #[serde(default)]
+        generation: u64,
+        dur: Duration,
+    },
+    Cancel {
+        key: K,
+        #[serde(default)]
+        generation: u64,
+    },
+    Fired {
+        key: K,
+        #[serde(default)]
+        generation: u64,
+    },
+}
+
+/// Pending timeout of the log: generation, deadline and payload.
+type Pending<P> = (u64, SystemTime, P);
+
+#[derive(Clone)]
+struct Log(Arc<Mutex<File>>);
+
+impl Log {
+    /// Lock the log, the operations hold it while they write their record
+    /// and apply it to the driver so they're logged in the order applied.
+    fn lock(&self) -> MutexGuard<'_, File> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    fn write<K: Serialize, P: Serialize>(&self, record: &Record<K, P>) -> Result<()> {
+        append(&mut self.lock(), record)
+    }
+}
+
+fn append<K: Serialize, P: Serialize>(file: &mut File, record: &Record<K, P>) -> Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// A shared driver which writes every operation in a log file. On `open`, the
+/// log is replayed and the timeouts not reached yet are re-armed. A timeout is
+/// marked as fired in the log only after the handler returned, so a timeout
+/// reached while the process crashed is fired again on the next `open`
+/// (at-least-once execution).
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::durable::DurableDriver;
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let path = std::env::temp_dir().join("dyn-timeout-doc.log");
+/// let driver = DurableDriver::open(&path, |key: String, payload: u32| {
+///     println!("{} expired with {}", key, payload);
+/// })
+/// .unwrap();
+/// driver.insert("job".to_string(), TWENTY, 42).unwrap();
+/// driver.add(&"job".to_string(), TWENTY).unwrap();
+/// # drop(driver);
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub struct DurableDriver<K, P> {
+    /// The payloads are given with the generation of their `Arm`.
+    driver: Driver<K, (u64, P)>,
+    log: Log,
+    /// Last generation written in the log.
+    generation: AtomicU64,
+}
+
+impl<K, P> DurableDriver<K, P>
+where
+    K: Hash + Eq + Clone + Send + Serialize + DeserializeOwned + 'static,
+    P: Send + Serialize + DeserializeOwned + 'static,
+{
+    /// Open the log at the given path, or create it, and re-arm the pending
+    /// timeouts it contains. The timeouts expired while the process wasn't
+    /// running are reached immediately. The log is compacted so it only
+    /// contains the pending timeouts.
+    ///
+    /// # Return
+    /// Return a result with an error if the log can't be read or contains a
+    /// corrupted record before its last line, the log is left untouched.
+    /// A truncated last line, from a crash while writing, is ignored.
+    ///
+    /// # Example
+    /// ```
+    /// use dyn_timeout::durable::DurableDriver;
+    ///
+    /// let path = std::env::temp_dir().join("dyn-timeout-doc-open.log");
+    /// let driver = DurableDriver::open(&path, |key: u64, _payload: ()| {
+    ///     println!("job {} expired", key);
+    /// })
+    /// .unwrap();
+    /// # drop(driver);
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn open<F>(path: impl AsRef<Path>, handler: F) -> Result<Self>
+    where
+        F: Fn(K, P) + Send + 'static,
+    {
+        let path = path.as_ref();
+        let (pending, generation) = replay::<K, P>(path)?;
+        let log = Log(Arc::new(Mutex::new(compact(path, &pending)?)));
+        let handler_log = log.clone();
+        let driver = Driver::new(move |key: K, (generation, payload)| {
+            let fired = Record::<_, ()>::Fired {
+                key: key.clone(),
+                generation,
+            };
+            handler(key, payload);
+            if let Err(err) = handler_log.write(&fired) {
+                eprintln!("dyn-timeout failed to write in the log: {}", err);
+            }
+        });
+        // re-armed in the order of their deadlines, so the expired timeouts
+        // are reached in the same order as without the restart
+        let mut pending: Vec<_> = pending.into_iter().collect();
+        pending.sort_by_key(|(_, (_, deadline, _))| *deadline);
+        let now = SystemTime::now();
+        for (key, (generation, deadline, payload)) in pending {
+            let remaining = deadline.duration_since(now).unwrap_or_default();
+            driver.insert(key, remaining, (generation, payload));
+        }
+        Ok(Self {
+            driver,
+            log,
+            generation: AtomicU64::new(generation),
+        })
+    }
+    /// Arm a timeout for the given key, see `Driver::insert`.
+    ///
+    /// # Return
+    /// Return a result with an error if the deadline can't be represented
+    /// (`TimeoutError::InvalidDuration`) or if the operation couldn't be
+    /// written in the log, the timeout isn't armed in that case.
+    /// Otherwise it return the payload of the replaced timeout if any.
+    pub fn insert(&self, key: K, dur: Duration, payload: P) -> Result<Option<P>> {
+        let Some(deadline) = SystemTime::now().checked_add(dur) else {
+            bail!(TimeoutError::InvalidDuration);
+        };
+        let mut log = self.log.lock();
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        append(
+            &mut log,
+            &Record::Arm {
+                key: &key,
+                generation,
+                deadline,
+                payload: &payload,
+            },
+        )?;
+        let old = self.driver.insert(key, dur, (generation, payload));
+        Ok(old.map(|(_, payload)| payload))
+    }
+    /// Increase the delay before the timeout of the given key, see
+    /// `Driver::add`.
+    ///
+    /// # Return
+    /// Return a result with an error if there is no pending timeout for the
+    /// key, if the deadline overflows (`TimeoutError::InvalidDuration`) or
+    /// if the operation couldn't be written in the log, the timeout is left
+    /// untouched in that case.
+    /// Otherwise it return an empty success.
+    pub fn add(&self, key: &K, dur: Duration) -> Result<()> {
+        let mut log = self.log.lock();
+        let generation = self.generation_of(key)?;
+        let deadline = self
+            .driver
+            .remaining(key)
+            .and_then(|remaining| remaining.checked_add(dur))
+            .and_then(|remaining| Instant::now().checked_add(remaining));
+        if deadline.is_none() {
+            bail!(TimeoutError::InvalidDuration);
+        }
+        append(
+            &mut log,
+            &Record::<_, ()>::Add {
+                key,
+                generation,
+                dur,
+            },
+        )?;
+        self.driver.add(key, dur)
+    }
+    /// Decrease the delay before the timeout of the given key, see
+    /// `Driver::sub`.
+    ///
+    /// # Return
+    /// Return a result with an error if there is no pending timeout for the
+    /// key or if the operation couldn't be written in the log, the timeout
+    /// is left untouched in that case.
+    /// Otherwise it return an empty success.
+    pub fn sub(&self, key: &K, dur: Duration) -> Result<()> {
+        let mut log = self.log.lock();
+        let generation = self.generation_of(key)?;
+        append(
+            &mut log,
+            &Record::<_, ()>::Sub {
+                key,
+                generation,
+                dur,
+            },
+        )?;
+        self.driver.sub(key, dur)
+    }
+    /// Dismiss the timeout of the given key, see `Driver::cancel`.
+    ///
+    /// # Return
+    /// Return a result with an error if the operation couldn't be written in
+    /// the log, the timeout is left pending in that case. Otherwise it
+    /// return the payload of the timeout if it was pending.
+    pub fn cancel(&self, key: &K) -> Result<Option<P>> {
+        let mut log = self.log.lock();
+        let Ok(generation) = self.generation_of(key) else {
+            return Ok(None);
+        };
+        append(&mut log, &Record::<_, ()>::Cancel { key, generation })?;
+        // a timeout reached in the meantime is logged as fired
+        Ok(self.driver.cancel(key).map(|(_, payload)| payload))
+    }
+    /// Time left before the timeout of the given key is reached, see
+    /// `Driver::remaining`.
+    pub fn remaining(&self, key: &K) -> Option<Duration> {
+        self.driver.remaining(key)
+    }
+    /// Number of pending timeouts.
+    pub fn len(&self) -> usize {
+        self.driver.len()
+    }
+    /// Return true if there is no pending timeout.
+    pub fn is_empty(&self) -> bool {
+        self.driver.is_empty()
+    }
+    /// Generation of the pending timeout of the given key.
+    fn generation_of(&self, key: &K) -> Result<u64> {
+        match self.driver.with_payload(key, |(generation, _)| *generation) {
+            Some(generation) => Ok(generation),
+            None => bail!("Timeout not found"),
+        }
+    }
+}
+
+/// Pending timeouts of the log, and the last generation written.
+fn replay<K, P>(path: &Path) -> Result<(HashMap<K, Pending<P>>, u64)>
+where
+    K: Hash + Eq + DeserializeOwned,
+    P: DeserializeOwned,
+{
+    let mut pending: HashMap<K, Pending<P>> = HashMap::new();
+    let mut last = 0;
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok((pending, last)),
+        Err(err) => return Err(err.into()),
+    };
+    let mut lines = BufReader::new(file).lines().enumerate().peekable();
+    while let Some((number, line)) = lines.next() {
+        let line = line?;
+        let record = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            // A crash while writing can leave the last line truncated.
+            Err(_) if lines.peek().is_none() => break,
+            // the log isn't compacted, the following records would be lost
+            Err(err) => bail!("Corrupted log at line {}: {}", number + 1, err),
+        };
+        match record {
+            Record::Arm {
+                key,
+                generation,
+                deadline,
+                payload,
+            } => {
+                last = last.max(generation);
+                pending.insert(key, (generation, deadline, payload));
+            }
+            Record::Add {
+                key,
+                generation,
+                dur,
+            } => {
+                if let Some((_, deadline, _)) = armed(&mut pending, &key, generation) {
+                    // an overflow saturates to a far deadline
+                    *deadline = deadline
+                        .checked_add(dur)
+                        .or_else(|| deadline.checked_add(FOREVER))
+                        .unwrap_or(*deadline);
+                }
+            }
+            Record::Sub {
+                key,
+                generation,
+                dur,
+            } => {
+                if let Some((_, deadline, _)) = armed(&mut pending, &key, generation) {
+                    *deadline = deadline.checked_sub(dur).unwrap_or(SystemTime::UNIX_EPOCH);
+                }
+            }
+            // the key may have been armed again in the meantime
+            Record::Cancel { key, generation } | Record::Fired { key, generation } => {
+                if armed(&mut pending, &key, generation).is_some() {
+                    pending.remove(&key);
+                }
+            }
+        }
+    }
+    Ok((pending, last))
+}
+
+/// Pending timeout of the key if it's still the one of the given generation.
+fn armed<'a, K: Hash + Eq, P>(
+    pending: &'a mut HashMap<K, Pending<P>>,
+    key: &K,
+    generation: u64,
+) -> Option<&'a mut Pending<P>> {
+    pending
+        .get_mut(key)
+        .filter(|(armed, ..)| *armed == generation)
+}
+
+fn compact<K, P>(path: &Path, pending: &HashMap<K, Pending<P>>) -> Result<File>
+where
+    K: Serialize,
+    P: Serialize,
+{
+    let mut tmp = PathBuf::from(path);
+    tmp.set_extension("tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        for (key, (generation, deadline, payload)) in pending {
+            let record = Record::Arm {
+                key,
+                generation: *generation,
+                deadline: *deadline,
+                payload,
+            };
+            let mut line = serde_json::to_vec(&record)?;
+            line.push(b'\n');
+            file.write_all(&line)?;
+        }
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)?;
+    Ok(OpenOptions::new().append(true).open(path)?)
+}
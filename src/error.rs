@@ -0,0 +1,75 @@
+//! Errors returned by the timeouts, in the `anyhow::Error` of the methods so
+//! they can be told apart with `downcast_ref`.
+//!
+//! ```
+//! use std::time::Duration;
+//! use dyn_timeout::{error::TimeoutError, std_thread::DynTimeout};
+//!
+//! let dyn_timeout = DynTimeout::new(Duration::ZERO, || {}).into_shared();
+//! std::thread::sleep(Duration::from_millis(20));
+//! let err = dyn_timeout.cancel().unwrap_err();
+//! assert_eq!(err.downcast_ref(), Some(&TimeoutError::AlreadyFired));
+//! ```
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutError {
+    /// The callback was already started when the timeout was cancelled.
+    AlreadyFired,
+    /// The timeout already ended, reached or cancelled, when it was
+    /// adjusted: an `add` which lost the race against the expiry. Nothing
+    /// was changed.
+    Reached,
+    /// The adjusted delay can't be represented, see `Duration::checked_add`.
+    InvalidDuration,
+    /// More than the remaining time was removed with `SubPolicy::Error`.
+    ExceedsRemaining,
+    /// The timeout was adjusted, cancelled or waited from its own callback,
+    /// executed in the timeout thread (or task). The callback re-arms the
+    /// timeout with its `TimeoutHandle` instead.
+    ReentrantAdjust,
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeoutError::AlreadyFired => write!(f, "Timeout already fired"),
+            TimeoutError::Reached => write!(f, "Timeout already reached"),
+            TimeoutError::InvalidDuration => write!(f, "Invalid duration"),
+            TimeoutError::ExceedsRemaining => {
+                write!(f, "Cannot remove more than the remaining time")
+            }
+            TimeoutError::ReentrantAdjust => {
+                write!(f, "Cannot adjust the timeout from its own callback")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+/// The deadline was reached before the end of the operation, see
+/// `with_deadline!`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Deadline elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// The callback of a timeout panicked, returned by `wait` with
+/// `PanicPolicy::Propagate`. It holds the message of the panic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Panicked(pub String);
+
+impl fmt::Display for Panicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Callback panicked: {}", self.0)
+    }
+}
+
+impl std::error::Error for Panicked {}
@@ -0,0 +1,56 @@
+//! Events of the timeouts, delivered to their callbacks or to their owners
+use std::time::{Duration, Instant};
+
+/// Expiration of a timeout, given to the callback by
+/// `TimeoutHandle::expiration`, and to the handler of a driver created with
+/// `Driver::with_expiration`. The sequence number counts the expirations
+/// of the timeout, so a recurring timer can detect that it was late and
+/// that ticks were coalesced.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::{config::TimeoutConfig, std_thread::DynTimeout};
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let heartbeat = DynTimeout::with_handle(&TimeoutConfig::new(TWENTY), |handle| {
+///    let expiration = handle.expiration().unwrap();
+///    println!("tick {} late of {:?}", expiration.seq, expiration.lateness());
+///    if expiration.seq < 3 {
+///        handle.rearm(TWENTY).unwrap();
+///    }
+/// });
+/// std::thread::sleep(TWENTY * 6);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expiration {
+    /// Number of the expiration, starting at one.
+    pub seq: u64,
+    /// Deadline of the timeout when it was reached.
+    pub scheduled: Instant,
+    /// Moment the timeout was observed as reached.
+    pub fired_at: Instant,
+}
+
+impl Expiration {
+    /// Delay between the deadline and the moment it was reached.
+    pub fn lateness(&self) -> Duration {
+        self.fired_at.saturating_duration_since(self.scheduled)
+    }
+}
+
+/// How a timeout ended, returned by `DynTimeout::close`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The timeout was reached and its callback executed.
+    Fired,
+    /// The timeout was cancelled before being reached, the callback wasn't
+    /// executed.
+    Cancelled,
+    /// The callback panicked.
+    Panicked,
+    /// The callback ran longer than `TimeoutConfig::max_callback_runtime`
+    /// and was abandoned.
+    Overrun,
+}
@@ -0,0 +1,106 @@
+//! C interface of the std thread implementation, see `include/dyn_timeout.h`
+//!
+//! The durations are given in milliseconds and the functions returning an
+//! `int` return `0` on success and `-1` on error.
+use crate::{config::TimeoutConfig, std_thread::DynTimeout};
+use std::{
+    os::raw::{c_int, c_void},
+    time::Duration,
+};
+
+/// Callback executed in the timeout thread with the user data given on
+/// creation.
+pub type DynTimeoutCallback = extern "C" fn(user_data: *mut c_void);
+
+/// User data given back to the callback, the caller is responsible for its
+/// thread safety.
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Create a new dynamic timeout calling `callback` with `user_data` after
+/// `millis` milliseconds. The timeout is released with `dyn_timeout_free`.
+/// Return null if `callback` is null.
+#[no_mangle]
+pub extern "C" fn dyn_timeout_new(
+    millis: u64,
+    callback: Option<DynTimeoutCallback>,
+    user_data: *mut c_void,
+) -> *mut DynTimeout {
+    let Some(callback) = callback else {
+        return std::ptr::null_mut();
+    };
+    let user_data = UserData(user_data);
+    let timeout = DynTimeout::spawn(
+        &TimeoutConfig::new(Duration::from_millis(millis)),
+        true,
+        move |_| callback(user_data.get()),
+    );
+    Box::into_raw(Box::new(timeout))
+}
+
+/// Increase the delay before the timeout, it fails if the timeout is
+/// reached or if the deadline overflows.
+///
+/// # Safety
+/// `timeout` must be returned by `dyn_timeout_new` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dyn_timeout_add(timeout: *const DynTimeout, millis: u64) -> c_int {
+    match timeout.as_ref() {
+        Some(timeout) => status(timeout.add(Duration::from_millis(millis))),
+        None => -1,
+    }
+}
+
+/// Decrease the delay before the timeout, it fails if the timeout is
+/// reached. The timeouts use `SubPolicy::Saturate`, a delay longer than
+/// the remaining time reaches the timeout immediately.
+///
+/// # Safety
+/// `timeout` must be returned by `dyn_timeout_new` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dyn_timeout_sub(timeout: *const DynTimeout, millis: u64) -> c_int {
+    match timeout.as_ref() {
+        Some(timeout) => status(timeout.sub(Duration::from_millis(millis))),
+        None => -1,
+    }
+}
+
+/// Dismiss the callback and join the timeout thread, the timeout still has
+/// to be freed.
+///
+/// # Safety
+/// `timeout` must be returned by `dyn_timeout_new` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dyn_timeout_cancel(timeout: *mut DynTimeout) -> c_int {
+    match timeout.as_mut() {
+        Some(timeout) => status(timeout.cancel()),
+        None => -1,
+    }
+}
+
+/// Release the timeout, it waits for the callback like a drop in Rust.
+///
+/// # Safety
+/// `timeout` must be returned by `dyn_timeout_new` and not freed yet, or
+/// null.
+#[no_mangle]
+pub unsafe extern "C" fn dyn_timeout_free(timeout: *mut DynTimeout) {
+    if !timeout.is_null() {
+        drop(Box::from_raw(timeout));
+    }
+}
+
+fn status(result: anyhow::Result<()>) -> c_int {
+    match result {
+        Ok(_) => 0,
+        Err(_) => -1,
+    }
+}
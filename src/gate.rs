@@ -0,0 +1,112 @@
+//! Blocking wait with a deadline pushed back by other threads, built on the
+//! std thread implementation
+use crate::{
+    config::{DropBehavior, TimeoutConfig},
+    std_thread::{DynTimeout, SharedDynTimeout},
+};
+use anyhow::Result;
+use std::{
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::Duration,
+};
+
+/// How a `TimedGate` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateOutcome {
+    /// Opened with `TimedGate::open` before the deadline.
+    Opened,
+    /// The deadline was reached first.
+    TimedOut,
+}
+
+/// State of the gate, none until it's opened or timed out.
+type State = Arc<(Mutex<Option<GateOutcome>>, Condvar)>;
+
+/// Gate blocking the threads calling `wait` until another thread opens it,
+/// or until its deadline. The deadline can be pushed back while the threads
+/// are waiting, for a pure threaded application waiting for a reply that
+/// keeps announcing progress. The clones share the same gate.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::gate::{GateOutcome, TimedGate};
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let gate = TimedGate::new(TWENTY * 2);
+/// let worker = gate.clone();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(TWENTY);
+///     worker.extend(TWENTY * 2).unwrap(); // still working
+///     std::thread::sleep(TWENTY * 2);
+///     worker.open();
+/// });
+/// assert_eq!(gate.wait(), GateOutcome::Opened);
+/// ```
+#[derive(Clone)]
+pub struct TimedGate {
+    timeout: SharedDynTimeout,
+    state: State,
+}
+
+impl TimedGate {
+    /// Create a gate closed for at most the given duration.
+    pub fn new(dur: Duration) -> Self {
+        let state: State = Arc::default();
+        let thread_state = state.clone();
+        let config = TimeoutConfig::new(dur).drop_behavior(DropBehavior::Cancel);
+        let timeout = DynTimeout::spawn(&config, true, move |_| {
+            settle(&thread_state, GateOutcome::TimedOut);
+        });
+        Self {
+            timeout: timeout.into_shared(),
+            state,
+        }
+    }
+    /// Open the gate, the waiting threads are released.
+    ///
+    /// # Return
+    /// Return false if the gate already timed out.
+    pub fn open(&self) -> bool {
+        let _ = self.timeout.cancel();
+        settle(&self.state, GateOutcome::Opened) == GateOutcome::Opened
+    }
+    /// Push the deadline back.
+    ///
+    /// # Return
+    /// Return a result with an error if the gate is already opened or timed
+    /// out, see `DynTimeout::add`.
+    /// Otherwise it return an empty success.
+    pub fn extend(&self, dur: Duration) -> Result<()> {
+        self.timeout.add(dur)
+    }
+    /// Time left before the gate times out.
+    ///
+    /// # Return
+    /// Return a result with an error if it failed to read the deadline.
+    /// Otherwise it return the remaining time.
+    pub fn remaining(&self) -> Result<Duration> {
+        self.timeout.remaining()
+    }
+    /// Block until the gate is opened or timed out, immediately if it
+    /// already is.
+    pub fn wait(&self) -> GateOutcome {
+        let (outcome, condvar) = &*self.state;
+        let outcome = outcome.lock().unwrap_or_else(PoisonError::into_inner);
+        let outcome = condvar
+            .wait_while(outcome, |outcome| outcome.is_none())
+            .unwrap_or_else(PoisonError::into_inner);
+        outcome.unwrap_or(GateOutcome::TimedOut)
+    }
+}
+
+/// Set the outcome unless the gate already ended, and release the waiting
+/// threads. Return the outcome of the gate.
+fn settle(state: &State, outcome: GateOutcome) -> GateOutcome {
+    let (settled, condvar) = &**state;
+    let mut settled = settled.lock().unwrap_or_else(PoisonError::into_inner);
+    let outcome = *settled.get_or_insert(outcome);
+    condvar.notify_all();
+    outcome
+}
@@ -0,0 +1,79 @@
+//! Adjustment history of a dynamic timeout
+use std::{
+    panic::Location,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, SystemTime},
+};
+
+/// Kind of adjustment made on a timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdjustmentKind {
+    /// Delay added with `add` and the like.
+    Add(Duration),
+    /// Delay removed with `sub` and the like.
+    Sub(Duration),
+    /// Delay replaced with `set`.
+    Set(Duration),
+    /// Timeout started again by `add` after it was reached, see
+    /// `AddPolicy::Rearm`.
+    Rearm(Duration),
+    /// Timeout cancelled.
+    Cancel,
+}
+
+/// An adjustment made on a timeout, see `DynTimeout::history`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adjustment {
+    /// Wall clock time of the adjustment.
+    pub at: SystemTime,
+    pub kind: AdjustmentKind,
+    /// Where the adjustment was called from. Only known with the std
+    /// implementation, the caller of an async function can't be tracked.
+    pub location: Option<&'static Location<'static>>,
+}
+
+/// List of the adjustments, none if the history isn't recorded. The
+/// extensions are always counted, see `TimeoutSnapshot`.
+pub(crate) struct History {
+    list: Option<Mutex<Vec<Adjustment>>>,
+    extensions: AtomicUsize,
+}
+
+impl History {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            list: enabled.then(|| Mutex::new(vec![])),
+            extensions: AtomicUsize::new(0),
+        }
+    }
+    pub(crate) fn record(
+        &self,
+        kind: AdjustmentKind,
+        location: Option<&'static Location<'static>>,
+    ) {
+        if let AdjustmentKind::Add(_) | AdjustmentKind::Rearm(_) = kind {
+            self.extensions.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(list) = &self.list {
+            list.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(Adjustment {
+                    at: SystemTime::now(),
+                    kind,
+                    location,
+                });
+        }
+    }
+    pub(crate) fn list(&self) -> Vec<Adjustment> {
+        match &self.list {
+            Some(list) => list.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+            None => vec![],
+        }
+    }
+    pub(crate) fn extensions(&self) -> usize {
+        self.extensions.load(Ordering::Relaxed)
+    }
+}
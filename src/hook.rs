@@ -0,0 +1,46 @@
+//! Error handling of the fallible timeout callbacks
+use std::{fmt::Debug, sync::mpsc::Sender};
+
+/// Define what to do with the error returned by a fallible callback. The
+/// callback is executed in a separated thread (or task) so the error can't
+/// be returned to the caller of the timeout.
+///
+/// # Example
+/// ```
+/// use std::{sync::mpsc, time::Duration};
+/// use dyn_timeout::{hook::ErrorHook, std_thread::DynTimeout};
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let (sender, receiver) = mpsc::channel();
+/// let dyn_timeout = DynTimeout::new_fallible(
+///     TWENTY,
+///     || Err("something went wrong"),
+///     ErrorHook::Channel(sender),
+/// );
+/// assert_eq!(receiver.recv().unwrap(), "something went wrong");
+/// ```
+pub enum ErrorHook<E> {
+    /// Print the error on the standard error output.
+    Log,
+    /// Panic in the timeout thread with the error.
+    Panic,
+    /// Send the error through a channel. The error is lost if the receiver
+    /// has been dropped.
+    Channel(Sender<E>),
+    /// Call the given function with the error.
+    Handler(fn(E)),
+}
+
+impl<E: Debug> ErrorHook<E> {
+    pub(crate) fn handle(&self, err: E) {
+        match self {
+            ErrorHook::Log => eprintln!("dyn-timeout callback failed: {:?}", err),
+            ErrorHook::Panic => panic!("dyn-timeout callback failed: {:?}", err),
+            ErrorHook::Channel(sender) => {
+                let _ = sender.send(err);
+            }
+            ErrorHook::Handler(handler) => handler(err),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Detect when a channel or a stream goes quiet
+use crate::sleep::DynSleep;
+use std::{
+    error::Error,
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::{
+    wrappers::{BroadcastStream, ReceiverStream},
+    Stream,
+};
+
+/// Error yielded by `Inactivity` when nothing was received during the
+/// quiet period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StalenessError {
+    /// Quiet period elapsed without any message.
+    pub quiet: Duration,
+}
+
+impl fmt::Display for StalenessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Nothing received since {:?}", self.quiet)
+    }
+}
+
+impl Error for StalenessError {}
+
+/// Stream wrapper yielding the items of the inner stream, or a
+/// `StalenessError` each time nothing is received during the quiet period.
+/// The deadline is pushed back on every item and the stream ends with the
+/// inner stream.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use tokio_stream::StreamExt;
+/// use dyn_timeout::inactivity::Inactivity;
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let (sender, receiver) = tokio::sync::mpsc::channel::<u32>(8);
+///    let mut messages = Inactivity::from_mpsc(receiver, TWENTY);
+///    sender.send(42).await.unwrap();
+///    assert_eq!(messages.next().await.unwrap(), Ok(42));
+///    // the channel goes quiet
+///    assert!(messages.next().await.unwrap().is_err());
+/// });
+/// ```
+pub struct Inactivity<S> {
+    stream: S,
+    quiet: Duration,
+    sleep: DynSleep,
+}
+
+impl<S: Stream + Unpin> Inactivity<S> {
+    /// Watch the given stream, must be called in the context of a tokio
+    /// runtime.
+    pub fn new(stream: S, quiet: Duration) -> Self {
+        Self {
+            stream,
+            quiet,
+            sleep: DynSleep::new(quiet),
+        }
+    }
+}
+
+impl<T: Send + 'static> Inactivity<ReceiverStream<T>> {
+    /// Watch a tokio mpsc receiver.
+    pub fn from_mpsc(receiver: mpsc::Receiver<T>, quiet: Duration) -> Self {
+        Self::new(ReceiverStream::new(receiver), quiet)
+    }
+}
+
+impl<T: Clone + Send + 'static> Inactivity<BroadcastStream<T>> {
+    /// Watch a tokio broadcast receiver, the items are errors if the
+    /// receiver lagged behind.
+    pub fn from_broadcast(receiver: broadcast::Receiver<T>, quiet: Duration) -> Self {
+        Self::new(BroadcastStream::new(receiver), quiet)
+    }
+}
+
+impl<S: Stream + Unpin> Stream for Inactivity<S> {
+    type Item = Result<S::Item, StalenessError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.stream).poll_next(cx) {
+            Poll::Ready(Some(item)) => {
+                this.sleep.reset(this.quiet);
+                return Poll::Ready(Some(Ok(item)));
+            }
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => {}
+        }
+        match Pin::new(&mut this.sleep).poll(cx) {
+            Poll::Ready(()) => {
+                // wait another quiet period before the next error
+                this.sleep.reset(this.quiet);
+                Poll::Ready(Some(Err(StalenessError { quiet: this.quiet })))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Recurring ticks with a period changed while the interval runs
+use crate::deadline;
+use std::{
+    future::{poll_fn, Future},
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use tokio::time::{Instant, Sleep};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// What a `DynInterval` does with the ticks missed while the consumer was
+/// late, like tokio's `MissedTickBehavior`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum MissedTick {
+    /// Yield all the missed ticks as fast as possible, then keep the
+    /// original schedule.
+    #[default]
+    Burst,
+    /// Drop the missed ticks, the next tick is the next one of the original
+    /// schedule.
+    Skip,
+    /// Shift the schedule, the next tick is a period after the late one.
+    Delay,
+}
+
+struct State {
+    period: Duration,
+    waker: Option<Waker>,
+    /// The next tick is the last one, see `stop_after_current`.
+    stopping: bool,
+}
+
+/// Interval yielding a tick every period, the period can be changed between
+/// two ticks, directly or through an `IntervalHandle`. A new period applies
+/// from the last tick. The first tick is immediate.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::interval::{DynInterval, MissedTick};
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let mut interval = DynInterval::new(TWENTY).missed_tick(MissedTick::Skip);
+///    interval.tick().await;
+///    interval.set_period(TWENTY * 2);
+///    interval.tick().await;
+///    println!("forty milliseconds after the first tick");
+/// });
+/// ```
+pub struct DynInterval {
+    sleep: Pin<Box<Sleep>>,
+    state: Arc<Mutex<State>>,
+    /// Scheduled instant of the last tick, none before the first one.
+    last: Option<Instant>,
+    start: Instant,
+    missed: MissedTick,
+    /// The last tick was yielded after `stop_after_current`.
+    finished: bool,
+}
+
+/// Handle to change the period of a `DynInterval` from anywhere, the handle
+/// can be cloned.
+#[derive(Clone)]
+pub struct IntervalHandle {
+    state: Arc<Mutex<State>>,
+}
+
+impl DynInterval {
+    /// Create an interval with the given period, the missed ticks are
+    /// yielded in a burst. Must be called in the context of a tokio runtime.
+    pub fn new(period: Duration) -> Self {
+        let start = Instant::now();
+        Self {
+            sleep: Box::pin(tokio::time::sleep_until(start)),
+            state: Arc::new(Mutex::new(State {
+                period,
+                waker: None,
+                stopping: false,
+            })),
+            last: None,
+            start,
+            missed: MissedTick::default(),
+            finished: false,
+        }
+    }
+    /// Set what to do with the missed ticks.
+    pub fn missed_tick(mut self, missed: MissedTick) -> Self {
+        self.missed = missed;
+        self
+    }
+    /// Create a handle to change the period while the interval is polled
+    /// somewhere else.
+    pub fn handle(&self) -> IntervalHandle {
+        IntervalHandle {
+            state: self.state.clone(),
+        }
+    }
+    /// Change the period, see `IntervalHandle::set_period`.
+    pub fn set_period(&self, period: Duration) {
+        self.handle().set_period(period)
+    }
+    /// Current period of the interval.
+    pub fn period(&self) -> Duration {
+        self.handle().period()
+    }
+    /// Stop after the period in progress, see
+    /// `IntervalHandle::stop_after_current`.
+    pub fn stop_after_current(&self) {
+        self.handle().stop_after_current()
+    }
+    /// Whether the last tick was yielded after `stop_after_current`, the
+    /// next ones never complete.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+    /// Wait for the next tick.
+    ///
+    /// # Return
+    /// Return the instant the tick was scheduled at, earlier than now if the
+    /// tick was missed.
+    pub async fn tick(&mut self) -> Instant {
+        poll_fn(|cx| self.poll_tick(cx)).await
+    }
+    /// Poll the next tick, see `tick`.
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        if self.finished {
+            return Poll::Pending;
+        }
+        loop {
+            let period = {
+                let mut state = lock(&self.state);
+                state.waker = Some(cx.waker().clone());
+                state.period
+            };
+            let next = self
+                .last
+                .map_or(self.start, |last| deadline::from(last, period));
+            if self.sleep.deadline() != next {
+                self.sleep.as_mut().reset(next);
+            }
+            if self.sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            // the period may have been increased in the meantime
+            let now = Instant::now();
+            if next > now {
+                continue;
+            }
+            self.last = Some(match self.missed {
+                MissedTick::Burst => next,
+                MissedTick::Skip if period.is_zero() => now,
+                MissedTick::Skip => {
+                    let period = period.as_nanos();
+                    let missed = (now - next).as_nanos() / period * period;
+                    next + Duration::from_nanos(missed as u64)
+                }
+                MissedTick::Delay => now,
+            });
+            self.finished = lock(&self.state).stopping;
+            return Poll::Ready(next);
+        }
+    }
+}
+
+impl IntervalHandle {
+    /// Change the period, the next tick is scheduled a new period after the
+    /// last one. The interval is woken up to wait the new schedule.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::interval::DynInterval;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let mut interval = DynInterval::new(Duration::from_secs(60));
+    ///    interval.tick().await;
+    ///    let handle = interval.handle();
+    ///    tokio::spawn(async move { handle.set_period(TWENTY) });
+    ///    interval.tick().await;
+    /// });
+    /// ```
+    pub fn set_period(&self, period: Duration) {
+        let mut state = lock(&self.state);
+        state.period = period;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+    /// Current period of the interval.
+    pub fn period(&self) -> Duration {
+        lock(&self.state).period
+    }
+    /// Stop the interval softly: the period in progress completes and its
+    /// tick is still yielded, then no more tick is scheduled. The consumer
+    /// can finish the work of the last tick and check
+    /// `DynInterval::is_finished` to leave its loop.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::interval::DynInterval;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let mut interval = DynInterval::new(TWENTY);
+    ///    let handle = interval.handle();
+    ///    loop {
+    ///        interval.tick().await;
+    ///        println!("flush the buffers");
+    ///        if interval.is_finished() {
+    ///            break;
+    ///        }
+    ///        handle.stop_after_current();
+    ///    }
+    /// });
+    /// ```
+    pub fn stop_after_current(&self) {
+        lock(&self.state).stopping = true;
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
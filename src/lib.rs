@@ -1,15 +1,74 @@
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod breaker;
+pub mod command;
+pub mod config;
+pub mod context;
+pub mod countdown;
+mod deadline;
+pub mod driver;
+#[cfg(feature = "durable")]
+pub mod durable;
+pub mod error;
+pub mod event;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod gate;
+pub mod history;
+pub mod hook;
+#[cfg(feature = "tokio-stream")]
+pub mod inactivity;
+pub mod interval;
+mod pool;
+#[cfg(not(target_family = "wasm"))]
+pub mod process;
+#[cfg(feature = "indicatif")]
+pub mod progress;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod rate_limit;
+pub mod recent;
+#[cfg(all(feature = "signal", unix))]
+pub mod signal;
+pub mod sleep;
+pub mod snapshot;
 pub mod std_thread;
+#[cfg(all(feature = "systemd", unix))]
+pub mod systemd;
+mod thread_cache;
+#[cfg(feature = "futures-core")]
+pub mod ticks;
+pub mod timeline;
 pub mod tokio_impl;
+pub mod wake;
+pub mod watchdog;
+
+pub use recent::recent_events;
 
 #[cfg(test)]
 mod test {
     //extern crate test;
-    use std::sync::{Arc, Mutex};
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
     use std::time::{Duration, SystemTime};
     //use test::Bencher;
     const TWENTY: Duration = Duration::from_millis(20);
+    use crate::command::Adjust;
+    use crate::config::{AddPolicy, CallbackExecution, DropBehavior, SubPolicy, TimeoutConfig};
+    use crate::driver::Driver;
+    use crate::error::TimeoutError;
+    use crate::history::AdjustmentKind;
+    use crate::hook::ErrorHook;
+    use crate::recent;
+    use crate::sleep::DynSleep;
     use crate::std_thread;
+    use crate::timeline::Timeline;
     use crate::tokio_impl;
+    use crate::watchdog::ProcessWatchdog;
 
     #[test]
     fn simple_test() {
@@ -27,6 +86,1935 @@ mod test {
         // this should be cancelled
         dyn_timeout.cancel().unwrap();
     }
+    #[test]
+    fn fallible_test() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let _dyn_timeout =
+            std_thread::DynTimeout::new_fallible(TWENTY, || Err(42), ErrorHook::Channel(sender));
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(1)).unwrap(), 42);
+    }
+    #[test]
+    fn driver_test() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let driver = Driver::new(move |key: u32, _: ()| sender.send(key).unwrap());
+        driver.insert(1, TWENTY * 2, ());
+        driver.insert(2, TWENTY, ());
+        driver.insert(3, TWENTY, ());
+        driver.add(&2, TWENTY * 2).unwrap();
+        driver.cancel(&3).unwrap();
+        let received: Vec<u32> = receiver.iter().take(2).collect();
+        assert_eq!(received, vec![1, 2]);
+        assert!(driver.is_empty());
+    }
+    #[test]
+    fn driver_lanes_test() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let driver =
+            Driver::with_tick(TWENTY * 5, move |key: u32, _: ()| sender.send(key).unwrap());
+        driver.insert_coarse(1, TWENTY, ());
+        driver.insert(2, TWENTY * 2, ());
+        assert!(driver.remaining(&1).unwrap() > TWENTY * 2);
+        let received: Vec<u32> = receiver.iter().take(2).collect();
+        assert_eq!(received, vec![2, 1]);
+    }
+    #[test]
+    fn driver_keys_test() {
+        use crate::driver::TimeoutKey;
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let driver = Driver::new(move |key: TimeoutKey, _: ()| sender.send(key).unwrap());
+        let first = driver.arm(TWENTY, ());
+        let second = driver.arm(TWENTY, ());
+        let third = driver.arm_coarse(TWENTY, ());
+        assert!(first != second);
+        driver.extend(first, TWENTY * 2).unwrap();
+        driver.cancel(&third).unwrap();
+        let received: Vec<TimeoutKey> = receiver.iter().take(2).collect();
+        assert_eq!(received, vec![second, first]);
+        assert!(driver.extend(first, TWENTY).is_err());
+    }
+    #[test]
+    fn driver_expiration_test() {
+        use crate::event::Expiration;
+        use std::sync::mpsc;
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let driver = Driver::with_expiration(move |key: u8, _: (), expiration: Expiration| {
+            sender.lock().unwrap().send((key, expiration)).unwrap();
+        });
+        let start = std::time::Instant::now();
+        driver.insert(1, TWENTY, ());
+        driver.add(&1, TWENTY).unwrap();
+        let (key, expiration) = receiver.recv_timeout(TWENTY * 10).unwrap();
+        assert_eq!(key, 1);
+        assert_eq!(expiration.seq, 1);
+        // the deadline after the extension
+        assert!(expiration.scheduled >= start + TWENTY * 2);
+        assert!(expiration.fired_at >= expiration.scheduled);
+        let driver = Driver::polled();
+        driver.insert("session", Duration::ZERO, ());
+        std::thread::sleep(TWENTY);
+        let mut batch = vec![];
+        assert_eq!(driver.poll_expired_with(&mut batch), 1);
+        assert!(batch[0].2.lateness() >= TWENTY);
+    }
+    #[test]
+    fn manual_driver_test() {
+        let reached = Arc::new(Mutex::new(vec![]));
+        let handler_reached = reached.clone();
+        let driver = Driver::manual(move |key: u8, _: ()| {
+            handler_reached.lock().unwrap().push(key);
+        });
+        assert_eq!(driver.next_wake(), None);
+        let start = std::time::Instant::now();
+        driver.insert(1, TWENTY, ());
+        driver.insert(2, TWENTY * 2, ());
+        driver.add(&1, TWENTY * 2).unwrap();
+        let wake = driver.next_wake().unwrap();
+        assert!(wake >= start + TWENTY * 2);
+        // nothing happens without the event loop
+        std::thread::sleep(TWENTY * 4);
+        assert!(reached.lock().unwrap().is_empty());
+        // the clock of the embedder drives the expirations
+        assert_eq!(driver.on_wake(wake), 1);
+        assert_eq!(*reached.lock().unwrap(), vec![2]);
+        assert_eq!(driver.on_wake(std::time::Instant::now()), 1);
+        assert_eq!(*reached.lock().unwrap(), vec![2, 1]);
+        assert_eq!(driver.next_wake(), None);
+        assert_eq!(Driver::<u8, ()>::polled().on_wake(start), 0);
+    }
+    #[test]
+    fn driver_tag_test() {
+        let driver = Driver::polled();
+        driver.insert(1, TWENTY, "acme");
+        driver.insert(2, TWENTY, "initech");
+        driver.insert(3, TWENTY * 2, "acme");
+        driver.tag(&1, "tenant=acme").unwrap();
+        driver.tag(&3, "tenant=acme").unwrap();
+        driver.tag(&3, "tenant=acme").unwrap();
+        driver.tag(&2, "tenant=initech").unwrap();
+        assert!(driver.tag(&4, "tenant=acme").is_err());
+        // the tags are kept by the adjustments
+        driver.add(&3, TWENTY).unwrap();
+        assert_eq!(driver.tagged("tenant=acme"), vec![1, 3]);
+        assert_eq!(driver.add_tagged("tenant=acme", TWENTY * 10).unwrap(), 2);
+        assert!(driver.add_tagged("tenant=acme", Duration::MAX).is_err());
+        std::thread::sleep(TWENTY * 2);
+        let mut batch = vec![];
+        driver.poll_expired_batch(&mut batch);
+        assert_eq!(batch, vec![(2, "initech")]);
+        assert!(driver.untag(&1, "tenant=acme"));
+        assert!(!driver.untag(&1, "tenant=acme"));
+        assert_eq!(driver.cancel_tagged("tenant=acme"), vec![(3, "acme")]);
+        assert_eq!(driver.len(), 1);
+        // a new insert drops the tags
+        driver.tag(&1, "tenant=acme").unwrap();
+        driver.insert(1, TWENTY, "acme");
+        assert!(driver.tagged("tenant=acme").is_empty());
+    }
+    #[test]
+    fn recent_events_test() {
+        use crate::event::Outcome;
+        // other tests run in parallel, their events are filtered out
+        recent::record_recent_events(10_000);
+        let labelled = |label: &str| {
+            crate::recent_events()
+                .into_iter()
+                .filter(|event| event.label.as_deref() == Some(label))
+                .collect::<Vec<_>>()
+        };
+        let config = TimeoutConfig::new(TWENTY).label("recent-fired");
+        let mut fired = std_thread::DynTimeout::with_config(&config, || {});
+        fired.wait().unwrap();
+        let events = labelled("recent-fired");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].outcome, Outcome::Fired);
+        assert!(events[0].overshoot.is_some());
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let config = TimeoutConfig::new(TWENTY).label("recent-cancelled");
+            let cancelled = tokio_impl::DynTimeout::with_config(&config, || {});
+            assert_eq!(cancelled.close().await, Outcome::Cancelled);
+        });
+        let events = labelled("recent-cancelled");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].overshoot, None);
+    }
+    #[test]
+    fn saturating_test() {
+        let dyn_timeout = std_thread::DynTimeout::new(TWENTY, || {});
+        dyn_timeout.add_millis(20).unwrap();
+        dyn_timeout.add_secs(1).unwrap();
+        assert!(dyn_timeout.remaining().unwrap() > Duration::from_secs(1));
+        assert!(dyn_timeout.add(Duration::MAX).is_err());
+        dyn_timeout.saturating_add(Duration::MAX).unwrap();
+        assert_eq!(dyn_timeout.remaining().unwrap(), Duration::MAX);
+        // already infinite
+        dyn_timeout.saturating_add(TWENTY).unwrap();
+        dyn_timeout.set(TWENTY).unwrap();
+        assert!(dyn_timeout.remaining().unwrap() <= TWENTY);
+        let config = TimeoutConfig::new(TWENTY * 10).sub_policy(SubPolicy::Error);
+        let mut dyn_timeout = std_thread::DynTimeout::with_config(&config, || {});
+        assert!(dyn_timeout.sub(Duration::from_secs(1)).is_err());
+        dyn_timeout.saturating_sub(Duration::from_secs(1)).unwrap();
+        dyn_timeout.wait().unwrap();
+        assert!(dyn_timeout.saturating_sub(TWENTY).is_err());
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || {});
+            dyn_timeout.add_secs(1).await.unwrap();
+            dyn_timeout.saturating_add(Duration::MAX).await.unwrap();
+            assert_eq!(dyn_timeout.remaining().await, Duration::MAX);
+            dyn_timeout.saturating_sub(Duration::MAX).await.unwrap();
+            assert!(dyn_timeout.remaining().await < TWENTY);
+        });
+    }
+    #[test]
+    fn assign_test() {
+        use crate::config::AssignPolicy;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        let mut dyn_timeout = std_thread::DynTimeout::new(TWENTY, || {}).into_shared();
+        let mut remote = dyn_timeout.clone();
+        remote += TWENTY;
+        dyn_timeout -= TWENTY / 2;
+        assert!(dyn_timeout.remaining().unwrap() > TWENTY);
+        let config = TimeoutConfig::new(TWENTY);
+        let mut reached = std_thread::DynTimeout::with_config(&config, || {});
+        reached.wait().unwrap();
+        assert!(catch_unwind(AssertUnwindSafe(|| reached += TWENTY)).is_err());
+        let config = config.assign_policy(AssignPolicy::Ignore);
+        let mut ignored = std_thread::DynTimeout::with_config(&config, || {});
+        ignored.wait().unwrap();
+        ignored += TWENTY;
+        ignored -= TWENTY;
+    }
+    #[test]
+    fn take_over_test() {
+        static FIRED: AtomicUsize = AtomicUsize::new(0);
+        let config = TimeoutConfig::new(TWENTY * 2).label("bootstrap");
+        let setup = std_thread::DynTimeout::with_config(&config, || {
+            FIRED.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut reached = std_thread::DynTimeout::new(Duration::ZERO, || {});
+        reached.wait().unwrap();
+        let pending = reached.then(TWENTY, || {});
+        let handshake = std_thread::DynTimeout::new(TWENTY * 10, || {});
+        let follower = handshake.then(TWENTY, || {}).unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut taken = tokio_impl::DynTimeout::try_from(setup).unwrap();
+            assert_eq!(taken.snapshot().await.label.as_deref(), Some("bootstrap"));
+            assert!(taken.remaining().await <= TWENTY * 2);
+            taken.add(TWENTY).await.unwrap();
+            taken.wait().await.unwrap();
+            assert!(tokio_impl::DynTimeout::try_from(reached).is_err());
+            assert!(tokio_impl::DynTimeout::try_from(follower).is_err());
+        });
+        assert!(pending.is_err());
+        assert_eq!(FIRED.load(Ordering::SeqCst), 1);
+    }
+    #[test]
+    fn memory_ordering_test() {
+        use crate::config::MemoryOrdering;
+        static FIRED: AtomicUsize = AtomicUsize::new(0);
+        for ordering in [MemoryOrdering::AcquireRelease, MemoryOrdering::SeqCst] {
+            FIRED.store(0, Ordering::SeqCst);
+            let mut expected = 0;
+            // cancelled around the expiry, either the cancel or the callback
+            // wins
+            for _ in 0..50 {
+                let config = TimeoutConfig::new(Duration::from_micros(200))
+                    .memory_ordering(ordering)
+                    .callback_execution(CallbackExecution::TimerThread);
+                let mut dyn_timeout = std_thread::DynTimeout::with_config(&config, || {
+                    FIRED.fetch_add(1, Ordering::SeqCst);
+                });
+                std::thread::sleep(Duration::from_micros(200));
+                match dyn_timeout.cancel() {
+                    Ok(()) => {}
+                    Err(err) => {
+                        assert_eq!(err.downcast_ref(), Some(&TimeoutError::AlreadyFired));
+                        expected += 1;
+                    }
+                }
+            }
+            assert_eq!(FIRED.load(Ordering::SeqCst), expected, "{:?}", ordering);
+        }
+    }
+    #[test]
+    fn reconfigure_test() {
+        use crate::{config::Precision, driver::Lane};
+        static FIRED: AtomicBool = AtomicBool::new(false);
+        let config = TimeoutConfig::new(TWENTY).precision(Precision::SubMillisecond);
+        let mut dyn_timeout = std_thread::DynTimeout::with_config(&config, || {});
+        dyn_timeout.set_precision(Precision::Millisecond);
+        dyn_timeout.wait().unwrap();
+        let stats = dyn_timeout.stats();
+        assert_eq!(stats.precision, Precision::Millisecond);
+        assert!(stats.lateness.is_some());
+
+        let driver = Driver::polled();
+        let session =
+            std_thread::DynTimeout::new(TWENTY * 2, || FIRED.store(true, Ordering::SeqCst));
+        driver.take_over(1, session, "session").unwrap();
+        assert!(driver.remaining(&1).unwrap() <= TWENTY * 2);
+        driver.insert(2, TWENTY, "sync");
+        assert!(driver.set_lane(&3, Lane::Coarse).is_err());
+        driver.set_lane(&2, Lane::Tolerant(TWENTY * 2)).unwrap();
+        assert_eq!(driver.set_all_lanes(Lane::Precise), 2);
+        std::thread::sleep(TWENTY * 3);
+        let mut batch = vec![];
+        driver.poll_expired_batch(&mut batch);
+        assert_eq!(batch, vec![(2, "sync"), (1, "session")]);
+        assert!(!FIRED.load(Ordering::SeqCst));
+        let mut reached = std_thread::DynTimeout::new(Duration::ZERO, || {});
+        reached.wait().unwrap();
+        assert!(driver.take_over(4, reached, "reached").is_err());
+    }
+    #[test]
+    fn scope_defaults_test() {
+        let defaults = TimeoutConfig::new(TWENTY)
+            .max_total(TWENTY * 2)
+            .sub_policy(SubPolicy::Error)
+            .label("service");
+        let scope = std_thread::TimeoutScope::with_defaults(defaults);
+        assert_eq!(scope.config(TWENTY * 3).duration, TWENTY * 3);
+        let request = scope.spawn(TWENTY * 3, || {});
+        // dismissed, the max total of the defaults is already reached
+        request.add(TWENTY).unwrap();
+        assert!(request.remaining().unwrap() <= TWENTY * 3);
+        assert!(request.sub(TWENTY * 10).is_err());
+        assert_eq!(request.snapshot().label.as_deref(), Some("service"));
+        let idle = scope.spawn_with_config(&scope.config(TWENTY).label("idle"), || {});
+        assert_eq!(idle.snapshot().label.as_deref(), Some("idle"));
+        assert_eq!(
+            std_thread::TimeoutScope::new().config(TWENTY),
+            TimeoutConfig::new(TWENTY)
+        );
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let defaults = TimeoutConfig::new(TWENTY).label("service");
+            let scope = tokio_impl::TimeoutScope::with_defaults(defaults);
+            let request = scope.spawn(TWENTY, || {});
+            assert_eq!(request.snapshot().await.label.as_deref(), Some("service"));
+            scope.close().await;
+        });
+    }
+    #[test]
+    fn driver_upsert_test() {
+        let driver = Driver::polled();
+        assert!(!driver.insert_or_add(1, TWENTY, "first").unwrap());
+        driver.insert(2, TWENTY * 2, "second");
+        assert!(driver.insert_or_add(1, TWENTY * 2, "dropped").unwrap());
+        assert!(driver.remaining(&1).unwrap() > TWENTY * 2);
+        assert!(driver.insert_or_add(1, Duration::MAX, "dropped").is_err());
+        std::thread::sleep(TWENTY * 4);
+        let mut batch = vec![];
+        driver.poll_expired_batch(&mut batch);
+        assert_eq!(batch, vec![(2, "second"), (1, "first")]);
+    }
+    #[test]
+    fn driver_batch_test() {
+        let driver = Driver::polled();
+        driver.insert(1, TWENTY, ());
+        driver.insert(2, Duration::ZERO, ());
+        driver.insert(3, TWENTY * 10, ());
+        let mut batch = vec![];
+        assert_eq!(driver.poll_expired_batch(&mut batch), 1);
+        std::thread::sleep(TWENTY);
+        assert_eq!(driver.poll_expired_batch(&mut batch), 1);
+        assert_eq!(batch, vec![(2, ()), (1, ())]);
+        assert_eq!(driver.len(), 1);
+    }
+    #[test]
+    fn driver_restore_test() {
+        use crate::driver::{Snapshot, SnapshotEntry};
+        let driver = Driver::polled();
+        let entry = |key, remaining| SnapshotEntry {
+            key,
+            remaining,
+            lane: Default::default(),
+            priority: Default::default(),
+            tags: vec![],
+            payload: (),
+        };
+        // a remaining time which can't be represented from now
+        driver.restore(Snapshot {
+            taken_at: SystemTime::now(),
+            timers: vec![entry(1, Duration::MAX), entry(2, Duration::ZERO)],
+        });
+        assert_eq!(driver.len(), 2);
+        assert!(driver.remaining(&1).unwrap() > Duration::from_secs(3600));
+        let mut batch = vec![];
+        driver.poll_expired_batch(&mut batch);
+        assert_eq!(batch, vec![(2, ())]);
+    }
+    #[test]
+    fn driver_priority_test() {
+        use crate::driver::{Lane, Priority};
+        let reached = Arc::new(Mutex::new(vec![]));
+        let handled = reached.clone();
+        let driver = Driver::with_tick(TWENTY * 2, move |key: u32, _: ()| {
+            handled.lock().unwrap().push(key)
+        });
+        // all expired in the same tick
+        driver.insert_with(1, TWENTY, Lane::Coarse, Priority::Low, ());
+        driver.insert_with(2, Duration::ZERO, Lane::Coarse, Priority::Normal, ());
+        driver.insert_with(3, TWENTY, Lane::Coarse, Priority::High, ());
+        driver.insert_with(4, TWENTY, Lane::Coarse, Priority::Normal, ());
+        driver.add(&3, Duration::ZERO).unwrap();
+        std::thread::sleep(TWENTY * 5);
+        assert_eq!(*reached.lock().unwrap(), vec![3, 2, 4, 1]);
+    }
+    #[test]
+    fn driver_average_overshoot_test() {
+        use crate::driver::DriverStats;
+        let stats = DriverStats {
+            active: 0,
+            fired: 1 << 32,
+            overshoot: Duration::from_secs(1 << 32),
+        };
+        assert_eq!(stats.average_overshoot(), Duration::from_secs(1));
+        let stats = DriverStats {
+            fired: 3,
+            overshoot: Duration::MAX,
+            ..stats
+        };
+        assert!(stats.average_overshoot() > Duration::from_secs(u64::MAX / 4));
+    }
+    #[test]
+    fn driver_strict_ordering_test() {
+        use crate::driver::{Lane, Priority};
+        let reached = Arc::new(Mutex::new(vec![]));
+        let handled = reached.clone();
+        let driver = Driver::with_tick(TWENTY * 10, move |key: u32, _: ()| {
+            handled.lock().unwrap().push(key)
+        })
+        .strict_ordering();
+        // the coarse timeout is reached with the precise one, before it
+        driver.insert_with(1, TWENTY, Lane::Coarse, Priority::Low, ());
+        driver.insert_with(2, TWENTY * 2, Lane::Precise, Priority::High, ());
+        driver.insert_tolerant(3, TWENTY * 3, TWENTY * 10, ());
+        driver.insert(4, TWENTY * 4, ());
+        driver.insert_with(5, TWENTY * 4, Lane::Precise, Priority::High, ());
+        std::thread::sleep(TWENTY * 3);
+        assert_eq!(*reached.lock().unwrap(), vec![1, 2]);
+        std::thread::sleep(TWENTY * 3);
+        assert_eq!(*reached.lock().unwrap(), vec![1, 2, 3, 4, 5]);
+    }
+    #[test]
+    fn driver_tolerance_test() {
+        let driver = Driver::polled();
+        driver.insert_tolerant(1, TWENTY, TWENTY * 10, ());
+        driver.insert_tolerant(2, TWENTY * 5, TWENTY * 10, ());
+        driver.insert(3, TWENTY * 2, ());
+        driver.add(&1, TWENTY).unwrap();
+        std::thread::sleep(TWENTY * 3);
+        // the first one is reached with the precise timeout, within its
+        // tolerance, the second one didn't pass its deadline
+        let mut batch = vec![];
+        assert_eq!(driver.poll_expired_batch(&mut batch), 2);
+        assert_eq!(batch, vec![(3, ()), (1, ())]);
+        assert!(driver.remaining(&2).unwrap() > TWENTY * 10);
+
+        let reached = Arc::new(Mutex::new(vec![]));
+        let handled = reached.clone();
+        let driver = Driver::new(move |key: u32, _: ()| handled.lock().unwrap().push(key));
+        driver.insert_tolerant(1, TWENTY, TWENTY * 2, ());
+        std::thread::sleep(TWENTY * 2);
+        // no wake up before the end of the tolerance window
+        assert!(reached.lock().unwrap().is_empty());
+        std::thread::sleep(TWENTY * 2);
+        assert_eq!(*reached.lock().unwrap(), vec![1]);
+    }
+    #[test]
+    fn driver_stats_test() {
+        let driver = Driver::polled();
+        driver.insert(1, Duration::ZERO, ());
+        driver.insert(2, TWENTY * 10, ());
+        std::thread::sleep(TWENTY);
+        driver.poll_expired_batch(&mut vec![]);
+        let stats = driver.stats();
+        assert_eq!(stats.active, 1);
+        assert_eq!(stats.fired, 1);
+        assert!(stats.average_overshoot() >= TWENTY);
+    }
+    #[test]
+    fn zero_duration_test() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let driver = Driver::new(move |key: u32, _: ()| sender.send(key).unwrap());
+        driver.insert(1, TWENTY, ());
+        driver.insert(2, Duration::ZERO, ());
+        driver.insert(3, Duration::ZERO, ());
+        driver.sub(&1, TWENTY * 2).unwrap();
+        driver.insert(4, Duration::ZERO, ());
+        let received: Vec<u32> = receiver.iter().take(4).collect();
+        assert_eq!(received, vec![2, 3, 1, 4]);
+    }
+    #[cfg(feature = "durable")]
+    #[test]
+    fn durable_test() {
+        let path = std::env::temp_dir().join("dyn-timeout-durable-test.log");
+        let _ = std::fs::remove_file(&path);
+        let driver = crate::durable::DurableDriver::open(&path, |_: u32, _: ()| {
+            panic!("Should never append");
+        })
+        .unwrap();
+        driver.insert(1, TWENTY * 10, ()).unwrap();
+        driver.insert(2, Duration::from_secs(20), ()).unwrap();
+        driver.cancel(&2).unwrap();
+        drop(driver);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let driver = crate::durable::DurableDriver::open(&path, move |key: u32, _: ()| {
+            sender.send(key).unwrap()
+        })
+        .unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(1)).unwrap(), 1);
+        assert!(receiver.recv_timeout(TWENTY).is_err());
+        drop(driver);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[cfg(feature = "durable")]
+    #[test]
+    fn durable_rearm_test() {
+        let path = std::env::temp_dir().join("dyn-timeout-durable-rearm-test.log");
+        let _ = std::fs::remove_file(&path);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let (resume, resumed) = std::sync::mpsc::channel();
+        let driver = crate::durable::DurableDriver::open(&path, move |key: u32, _: ()| {
+            sender.send(key).unwrap();
+            // the key is armed again before the expiry is written in the log
+            resumed.recv().unwrap()
+        })
+        .unwrap();
+        driver.insert(1, TWENTY, ()).unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(1)).unwrap(), 1);
+        driver.insert(1, Duration::from_secs(20), ()).unwrap();
+        resume.send(()).unwrap();
+        drop(driver);
+        let driver = crate::durable::DurableDriver::open(&path, |_: u32, _: ()| {}).unwrap();
+        assert_eq!(driver.len(), 1);
+        drop(driver);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[cfg(feature = "durable")]
+    #[test]
+    fn durable_overflow_test() {
+        use std::io::Write;
+        let path = std::env::temp_dir().join("dyn-timeout-durable-overflow-test.log");
+        let _ = std::fs::remove_file(&path);
+        let driver = crate::durable::DurableDriver::open(&path, |_: u32, _: ()| {}).unwrap();
+        let err = driver.insert(1, Duration::MAX, ()).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TimeoutError::InvalidDuration));
+        driver.insert(2, Duration::from_secs(20), ()).unwrap();
+        drop(driver);
+        // an extension which can't be represented by the system time
+        let mut log = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        let add = format!(
+            r#"{{"Add":{{"key":2,"generation":1,"dur":{{"secs":{},"nanos":0}}}}}}"#,
+            u64::MAX
+        );
+        writeln!(log, "{}", add).unwrap();
+        drop(log);
+        let driver = crate::durable::DurableDriver::open(&path, |_: u32, _: ()| {}).unwrap();
+        assert!(driver.remaining(&2).unwrap() > Duration::from_secs(20));
+        drop(driver);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[cfg(feature = "durable")]
+    #[test]
+    fn durable_adjust_test() {
+        use std::io::Write;
+        let path = std::env::temp_dir().join("dyn-timeout-durable-adjust-test.log");
+        let _ = std::fs::remove_file(&path);
+        let driver = crate::durable::DurableDriver::open(&path, |_: u32, _: ()| {}).unwrap();
+        driver.insert(1, Duration::from_secs(20), ()).unwrap();
+        driver.cancel(&1).unwrap();
+        driver.insert(1, Duration::from_secs(20), ()).unwrap();
+        // the failed extensions aren't written in the log
+        assert!(driver.add(&2, Duration::from_secs(20)).is_err());
+        let err = driver.add(&1, Duration::MAX).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TimeoutError::InvalidDuration));
+        drop(driver);
+        // an extension of the cancelled timeout, logged after its cancel
+        let mut log = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        writeln!(
+            log,
+            r#"{{"Add":{{"key":1,"generation":1,"dur":{{"secs":3600,"nanos":0}}}}}}"#
+        )
+        .unwrap();
+        drop(log);
+        let driver = crate::durable::DurableDriver::open(&path, |_: u32, _: ()| {}).unwrap();
+        assert_eq!(driver.len(), 1);
+        assert!(driver.remaining(&1).unwrap() <= Duration::from_secs(20));
+        driver.add(&1, Duration::from_secs(20)).unwrap();
+        drop(driver);
+        let driver = crate::durable::DurableDriver::open(&path, |_: u32, _: ()| {}).unwrap();
+        assert!(driver.remaining(&1).unwrap() > Duration::from_secs(20));
+        drop(driver);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[cfg(feature = "durable")]
+    #[test]
+    fn durable_corrupted_test() {
+        use std::io::Write;
+        let path = std::env::temp_dir().join("dyn-timeout-durable-corrupted-test.log");
+        let _ = std::fs::remove_file(&path);
+        let driver = crate::durable::DurableDriver::open(&path, |_: u32, _: ()| {}).unwrap();
+        driver.insert(1, Duration::from_secs(20), ()).unwrap();
+        drop(driver);
+        // a truncated last line is ignored
+        let mut log = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        write!(log, r#"{{"Arm":{{"key":2"#).unwrap();
+        drop(log);
+        let driver = crate::durable::DurableDriver::open(&path, |_: u32, _: ()| {}).unwrap();
+        assert_eq!(driver.len(), 1);
+        drop(driver);
+        // a corrupted line in the middle isn't compacted away
+        let valid = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, format!("garbage\n{}", valid)).unwrap();
+        assert!(crate::durable::DurableDriver::open(&path, |_: u32, _: ()| {}).is_err());
+        assert!(std::fs::read_to_string(&path).unwrap().ends_with(&valid));
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn drop_cancel_test() {
+        static FIRED: AtomicBool = AtomicBool::new(false);
+        let config = TimeoutConfig::new(TWENTY).drop_behavior(DropBehavior::Cancel);
+        let dyn_timeout =
+            std_thread::DynTimeout::with_config(&config, || FIRED.store(true, Ordering::Relaxed));
+        drop(dyn_timeout);
+        assert!(!FIRED.load(Ordering::Relaxed));
+    }
+    #[test]
+    fn drop_detached_infinite_test() {
+        // released by the thread when it ends
+        let marker = Arc::new(());
+        let config = TimeoutConfig::new(Duration::MAX).drop_behavior(DropBehavior::Detach);
+        let dyn_timeout = std_thread::DynTimeout::with_config(&config, || {});
+        let captured = marker.clone();
+        dyn_timeout.wrap_callback(move |inner| {
+            move |handle: &std_thread::TimeoutHandle| {
+                let _ = &captured;
+                inner(handle)
+            }
+        });
+        drop(dyn_timeout);
+        let start = std::time::Instant::now();
+        while Arc::strong_count(&marker) > 1 {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            std::thread::sleep(TWENTY / 4);
+        }
+    }
+    #[tokio::test]
+    async fn tokio_drop_infinite_test() {
+        // released by the task when it ends
+        let marker = Arc::new(());
+        let captured = marker.clone();
+        let dyn_timeout =
+            tokio_impl::DynTimeout::new_async(&TimeoutConfig::new(Duration::MAX), move || {
+                let _ = &captured;
+                async {}
+            });
+        drop(dyn_timeout);
+        let start = std::time::Instant::now();
+        while Arc::strong_count(&marker) > 1 {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            tokio::time::sleep(TWENTY / 4).await;
+        }
+    }
+    #[test]
+    fn then_test() {
+        static FIRST: AtomicBool = AtomicBool::new(false);
+        static SECOND: AtomicBool = AtomicBool::new(false);
+        let first = std_thread::DynTimeout::new(TWENTY, || FIRST.store(true, Ordering::Relaxed));
+        let second = first
+            .then(TWENTY, || {
+                assert!(FIRST.load(Ordering::Relaxed));
+                SECOND.store(true, Ordering::Relaxed)
+            })
+            .unwrap();
+        drop(first);
+        assert!(!SECOND.load(Ordering::Relaxed));
+        drop(second);
+        assert!(SECOND.load(Ordering::Relaxed));
+    }
+    #[tokio::test]
+    async fn tokio_then_test() {
+        let mut first = tokio_impl::DynTimeout::new(TWENTY, || {});
+        let mut second = first
+            .then(TWENTY, || panic!("Should never append"))
+            .await
+            .unwrap();
+        first.cancel().await.unwrap();
+        second.wait().await.unwrap();
+    }
+    #[tokio::test]
+    async fn race_test() {
+        let mut timeouts = [
+            tokio_impl::DynTimeout::new(TWENTY * 10, || panic!("Should never append")),
+            tokio_impl::DynTimeout::new(TWENTY, || {}),
+        ];
+        assert_eq!(tokio_impl::race(&mut timeouts).await.unwrap(), 1);
+        timeouts[0].wait().await.unwrap();
+        // the timeouts already waited are skipped
+        let mut timeouts = [
+            tokio_impl::DynTimeout::new(TWENTY, || panic!("Should never append")),
+            tokio_impl::DynTimeout::new(TWENTY, || {}),
+        ];
+        timeouts[0].cancel().await.unwrap();
+        timeouts[0].wait().await.unwrap();
+        assert_eq!(tokio_impl::race(&mut timeouts).await.unwrap(), 1);
+        assert!(tokio_impl::race(&mut timeouts).await.is_err());
+    }
+    #[test]
+    fn timeline_test() {
+        static STEP: AtomicUsize = AtomicUsize::new(0);
+        let timeline = Timeline::new("first", TWENTY, || {
+            assert_eq!(STEP.fetch_add(1, Ordering::Relaxed), 0)
+        })
+        .phase("second", TWENTY, || {
+            assert_eq!(STEP.fetch_add(1, Ordering::Relaxed), 1)
+        })
+        .unwrap()
+        .phase("third", TWENTY, || {
+            assert_eq!(STEP.fetch_add(1, Ordering::Relaxed), 2)
+        })
+        .unwrap();
+        timeline.add("second", TWENTY).unwrap();
+        assert!(timeline.remaining("second").unwrap() > TWENTY);
+        assert!(timeline.add("fourth", TWENTY).is_err());
+        drop(timeline);
+        assert_eq!(STEP.load(Ordering::Relaxed), 3);
+    }
+    #[test]
+    fn keep_alive_test() {
+        static FIRED: AtomicBool = AtomicBool::new(false);
+        let dyn_timeout =
+            std_thread::DynTimeout::new(TWENTY, || FIRED.store(true, Ordering::Relaxed));
+        let guard = dyn_timeout.keep_alive().unwrap();
+        std::thread::sleep(TWENTY * 3);
+        assert!(!FIRED.load(Ordering::Relaxed));
+        drop(guard);
+        assert!(dyn_timeout.remaining().unwrap() > Duration::ZERO);
+        drop(dyn_timeout);
+        assert!(FIRED.load(Ordering::Relaxed));
+    }
+    #[test]
+    fn sub_test() {
+        static FIRED: AtomicBool = AtomicBool::new(false);
+        let config = TimeoutConfig::new(TWENTY * 10).sub_policy(SubPolicy::Error);
+        let dyn_timeout =
+            std_thread::DynTimeout::with_config(&config, || FIRED.store(true, Ordering::Relaxed));
+        let err = dyn_timeout.sub(TWENTY * 20).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TimeoutError::ExceedsRemaining));
+        let start = SystemTime::now();
+        dyn_timeout.sub(TWENTY * 9).unwrap();
+        drop(dyn_timeout);
+        assert!(FIRED.load(Ordering::Relaxed));
+        assert!(start.elapsed().unwrap() < TWENTY * 5);
+
+        let start = SystemTime::now();
+        let dyn_timeout = std_thread::DynTimeout::new(TWENTY * 10, || {});
+        dyn_timeout.sub(TWENTY * 20).unwrap();
+        drop(dyn_timeout);
+        assert!(start.elapsed().unwrap() < TWENTY * 5);
+
+        let config = TimeoutConfig::new(TWENTY * 10).sub_policy(SubPolicy::Clamp(TWENTY * 5));
+        let dyn_timeout = std_thread::DynTimeout::with_config(&config, || {});
+        dyn_timeout.sub(TWENTY * 20).unwrap();
+        let remaining = dyn_timeout.remaining().unwrap();
+        assert!(remaining <= TWENTY * 5 && remaining > TWENTY * 4);
+    }
+    #[test]
+    fn concurrent_adjust_test() {
+        use crate::deadline::Deadline;
+        const BIG: Duration = Duration::from_secs(60);
+        let mut deadline = Deadline::new(TWENTY * 10, true);
+        let at = deadline.at();
+        deadline.sub(BIG);
+        deadline.add(BIG);
+        assert_eq!(deadline.at(), at);
+        deadline.add(BIG);
+        deadline.sub(BIG);
+        assert_eq!(deadline.at(), at);
+
+        // not started, so never reached while adjusted from the threads
+        let first = std_thread::DynTimeout::new(TWENTY * 10, || {});
+        let next = first.then(TWENTY * 10, || {}).unwrap().into_shared();
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let next = next.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..50 {
+                        if i % 2 == 0 {
+                            next.sub(BIG).unwrap();
+                            next.add(BIG).unwrap();
+                        } else {
+                            next.add(BIG).unwrap();
+                            next.sub(BIG).unwrap();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(next.remaining().unwrap(), TWENTY * 10);
+    }
+    #[test]
+    fn deadline_order_test() {
+        let first = std_thread::DynTimeout::new(TWENTY * 2, || {});
+        let mut second = std_thread::DynTimeout::new(TWENTY * 3, || {});
+        let next = first.then(TWENTY, || {}).unwrap();
+        assert!(first.expires_before(&second));
+        assert!(second.expires_before(&next));
+        assert!(!next.expires_before(&first));
+        assert_eq!(
+            std_thread::earliest(&[&first, &second, &next]),
+            first.deadline()
+        );
+        assert_eq!(
+            std_thread::latest(&[&first, &second, &next]),
+            second.deadline()
+        );
+        second.cancel().unwrap();
+        assert!(!second.expires_before(&next));
+        assert_eq!(
+            std_thread::latest(&[&first, &second, &next]),
+            first.deadline()
+        );
+        assert_eq!(std_thread::earliest(&[&second, &next]), None);
+    }
+    #[test]
+    fn snapshot_test() {
+        use crate::snapshot::TimeoutState;
+        let config = TimeoutConfig::new(TWENTY).label("request");
+        let mut first = std_thread::DynTimeout::with_config(&config, || {});
+        let next = first.then(TWENTY, || {}).unwrap();
+        first.add(TWENTY).unwrap();
+        first.add(TWENTY).unwrap();
+        first.sub(TWENTY).unwrap();
+        let snapshot = first.snapshot();
+        assert_eq!(snapshot.label.as_deref(), Some("request"));
+        assert_eq!(snapshot.state, TimeoutState::Running);
+        assert_eq!(snapshot.extensions, 2);
+        assert!(snapshot.remaining <= TWENTY * 2);
+        assert!(snapshot.created_at <= SystemTime::now());
+        assert_eq!(next.snapshot().label, None);
+        assert_eq!(next.snapshot().state, TimeoutState::Pending);
+        let reached = std_thread::DynTimeout::new(Duration::ZERO, || {});
+        std::thread::sleep(TWENTY);
+        assert_eq!(reached.snapshot().state, TimeoutState::Reached);
+        assert_eq!(reached.snapshot().remaining, Duration::ZERO);
+        first.cancel().unwrap();
+        assert_eq!(first.snapshot().state, TimeoutState::Cancelled);
+    }
+    #[test]
+    fn observer_test() {
+        use crate::snapshot::TimeoutState;
+        let mut dyn_timeout = std_thread::DynTimeout::new(TWENTY, || {});
+        let observer = dyn_timeout.observer();
+        let mut deadline = observer.watch_deadline().unwrap();
+        deadline.borrow_and_update();
+        dyn_timeout.add(TWENTY).unwrap();
+        assert!(deadline.has_changed().unwrap());
+        assert_eq!(observer.deadline(), dyn_timeout.deadline());
+        assert!(observer.remaining().unwrap() > TWENTY);
+        assert_eq!(observer.state(), TimeoutState::Running);
+        dyn_timeout.cancel().unwrap();
+        drop(dyn_timeout);
+        // still readable once the timeout is dropped
+        assert_eq!(observer.state(), TimeoutState::Cancelled);
+        assert_eq!(observer.snapshot().state, TimeoutState::Cancelled);
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dyn_timeout = tokio_impl::DynTimeout::new(Duration::ZERO, || {});
+            let observer = dyn_timeout.observer();
+            tokio::time::sleep(TWENTY).await;
+            assert_eq!(observer.state().await, TimeoutState::Reached);
+            assert_eq!(observer.remaining().await, Duration::ZERO);
+        });
+    }
+    #[test]
+    fn scope_test() {
+        use crate::snapshot::TimeoutState;
+        static DONE: AtomicBool = AtomicBool::new(false);
+        let scope = std_thread::TimeoutScope::new();
+        let pending = scope.spawn(TWENTY * 10, || panic!("never append"));
+        scope.spawn(Duration::ZERO, || {
+            std::thread::sleep(TWENTY * 2);
+            DONE.store(true, Ordering::SeqCst);
+        });
+        let observer = pending.observer();
+        drop(pending);
+        assert_eq!(scope.len(), 2);
+        std::thread::sleep(TWENTY);
+        // the callback started is waited
+        drop(scope);
+        assert!(DONE.load(Ordering::SeqCst));
+        assert_eq!(observer.state(), TimeoutState::Cancelled);
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let scope = tokio_impl::TimeoutScope::new();
+            let pending = scope.spawn(TWENTY * 10, || panic!("never append"));
+            let observer = pending.observer();
+            scope.close().await;
+            assert_eq!(observer.state().await, TimeoutState::Cancelled);
+            assert!(pending.add(TWENTY).await.is_err());
+        });
+    }
+    #[cfg(feature = "futures-core")]
+    #[test]
+    fn ticks_test() {
+        use crate::ticks::Ticks;
+        use futures::{executor::block_on, StreamExt};
+        let mut ticks = Ticks::new(TWENTY);
+        let handle = ticks.handle();
+        let first = block_on(ticks.next()).unwrap();
+        // late consumer, the missed ticks come in a burst
+        std::thread::sleep(TWENTY * 3 + TWENTY / 2);
+        let burst = block_on(ticks.by_ref().take(3).collect::<Vec<_>>());
+        assert!(burst[2] - first < TWENTY * 4);
+        handle.set_period(TWENTY * 3).unwrap();
+        assert_eq!(handle.period(), TWENTY * 3);
+        let last = block_on(ticks.next()).unwrap();
+        let next = block_on(ticks.next()).unwrap();
+        assert!(next - last >= TWENTY * 3);
+    }
+    #[test]
+    fn wakeups_test() {
+        let mut dyn_timeout = std_thread::DynTimeout::new(TWENTY * 2, || {});
+        std::thread::sleep(TWENTY / 2);
+        // woken up immediately, then reached
+        dyn_timeout.sub(TWENTY).unwrap();
+        dyn_timeout.wait().unwrap();
+        // the exact count depends on the scheduler and the spurious wake ups
+        let wakeups = dyn_timeout.wakeups();
+        assert!(wakeups >= 1);
+        assert_eq!(dyn_timeout.snapshot().wakeups, wakeups);
+    }
+    #[test]
+    fn panic_policy_test() {
+        use crate::{config::PanicPolicy, error::Panicked, event::Outcome};
+        use tokio::runtime::Runtime;
+        static HANDLED: AtomicBool = AtomicBool::new(false);
+        let config = TimeoutConfig::new(TWENTY).panic_policy(PanicPolicy::Propagate);
+        let mut dyn_timeout = std_thread::DynTimeout::with_config(&config, || panic!("boom"));
+        let err = dyn_timeout.wait().unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&Panicked("boom".to_string())));
+        assert_eq!(dyn_timeout.panicked().as_deref(), None);
+        // returned once
+        dyn_timeout.wait().unwrap();
+        let config = TimeoutConfig::new(TWENTY)
+            .panic_policy(PanicPolicy::Handler(|message| {
+                assert_eq!(message, "boom");
+                HANDLED.store(true, Ordering::SeqCst);
+            }))
+            .escalate(|| panic!("boom"), TWENTY);
+        // dropped without panicking
+        drop(std_thread::DynTimeout::with_config(&config, || {}));
+        assert!(HANDLED.load(Ordering::SeqCst));
+        let config = TimeoutConfig::new(TWENTY).panic_policy(PanicPolicy::Propagate);
+        Runtime::new().unwrap().block_on(async {
+            let dyn_timeout = tokio_impl::DynTimeout::with_config(&config, || panic!("boom"));
+            tokio::time::sleep(TWENTY * 2).await;
+            assert_eq!(dyn_timeout.close().await, Outcome::Panicked);
+            let mut dyn_timeout = tokio_impl::DynTimeout::with_config(&config, || panic!("boom"));
+            assert!(dyn_timeout.wait().await.is_err());
+        });
+    }
+    #[test]
+    fn reentrant_test() {
+        use std::sync::OnceLock;
+        static STD_TIMEOUT: OnceLock<std_thread::SharedDynTimeout> = OnceLock::new();
+        static TOKIO_TIMEOUT: OnceLock<tokio_impl::SharedDynTimeout> = OnceLock::new();
+        static REJECTED: AtomicUsize = AtomicUsize::new(0);
+        let dyn_timeout = std_thread::DynTimeout::new(TWENTY, || {
+            let dyn_timeout = STD_TIMEOUT.get().unwrap();
+            let err = dyn_timeout.add(TWENTY).unwrap_err();
+            if err.downcast_ref() == Some(&TimeoutError::ReentrantAdjust) {
+                REJECTED.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let _ = STD_TIMEOUT.set(dyn_timeout.into_shared());
+        // adjusted from another thread
+        STD_TIMEOUT.get().unwrap().add(TWENTY).unwrap();
+        std::thread::sleep(TWENTY * 3);
+        assert_eq!(REJECTED.load(Ordering::SeqCst), 1);
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || {
+                let dyn_timeout = TOKIO_TIMEOUT.get().unwrap();
+                let err = futures::executor::block_on(dyn_timeout.wait()).unwrap_err();
+                if err.downcast_ref() == Some(&TimeoutError::ReentrantAdjust) {
+                    REJECTED.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+            let _ = TOKIO_TIMEOUT.set(dyn_timeout.into_shared());
+            TOKIO_TIMEOUT.get().unwrap().wait().await.unwrap();
+        });
+        assert_eq!(REJECTED.load(Ordering::SeqCst), 2);
+    }
+    #[test]
+    fn wake_strategy_test() {
+        use crate::wake::{CondvarWake, ParkWake, WakeStrategy};
+        static FIRED: AtomicUsize = AtomicUsize::new(0);
+        let mut strategies: Vec<fn() -> Arc<dyn WakeStrategy>> =
+            vec![|| Arc::new(CondvarWake::default()), || {
+                Arc::new(ParkWake::default())
+            }];
+        #[cfg(unix)]
+        strategies.push(|| Arc::new(crate::wake::PipeWake::new().unwrap()));
+        for (index, strategy) in strategies.iter().copied().enumerate() {
+            let config = TimeoutConfig::new(TWENTY * 10).wake_strategy(strategy);
+            let dyn_timeout = std_thread::DynTimeout::with_config(&config, || {
+                FIRED.fetch_add(1, Ordering::SeqCst);
+            });
+            std::thread::sleep(TWENTY / 2);
+            // the thread is woken up to wait the new deadline
+            dyn_timeout.sub(TWENTY * 9).unwrap();
+            std::thread::sleep(TWENTY * 3);
+            assert_eq!(FIRED.load(Ordering::SeqCst), index + 1);
+        }
+    }
+    #[test]
+    fn wake_channel_test() {
+        use crate::config::WakeChannel;
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let channels = [
+                WakeChannel::Mpsc(0),
+                WakeChannel::Mpsc(8),
+                WakeChannel::Notify,
+            ];
+            for channel in channels {
+                let config = TimeoutConfig::new(TWENTY * 10).wake_channel(channel);
+                let mut dyn_timeout = tokio_impl::DynTimeout::with_config(&config, || {});
+                tokio::time::sleep(TWENTY / 2).await;
+                // the task is woken up to wait the new deadline
+                dyn_timeout.sub(TWENTY * 9).await.unwrap();
+                let start = std::time::Instant::now();
+                dyn_timeout.wait().await.unwrap();
+                assert!(start.elapsed() < TWENTY * 5);
+                let mut dyn_timeout = tokio_impl::DynTimeout::with_config(&config, || {});
+                dyn_timeout.cancel().await.unwrap();
+                let start = std::time::Instant::now();
+                dyn_timeout.wait().await.unwrap();
+                assert!(start.elapsed() < TWENTY * 5);
+            }
+        });
+    }
+    #[test]
+    fn precision_test() {
+        use crate::config::Precision;
+        assert!(Precision::BestEffort.is_met(TWENTY));
+        assert!(!Precision::Millisecond.is_met(TWENTY));
+        assert!(Precision::SubMillisecond.is_met(Duration::from_micros(50)));
+        for precision in [Precision::Millisecond, Precision::SubMillisecond] {
+            let config = TimeoutConfig::new(TWENTY).precision(precision);
+            let mut dyn_timeout = std_thread::DynTimeout::with_config(&config, || {});
+            assert_eq!(dyn_timeout.stats().lateness, None);
+            dyn_timeout.wait().unwrap();
+            let stats = dyn_timeout.stats();
+            let lateness = stats.lateness.unwrap();
+            assert!(lateness < TWENTY);
+            assert_eq!(stats.contract_met, Some(precision.is_met(lateness)));
+        }
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let config = TimeoutConfig::new(TWENTY).precision(Precision::Millisecond);
+            let mut dyn_timeout = tokio_impl::DynTimeout::with_config(&config, || {});
+            dyn_timeout.wait().await.unwrap();
+            let stats = dyn_timeout.stats().await;
+            assert_eq!(stats.precision, Precision::Millisecond);
+            assert!(stats.lateness.unwrap() < TWENTY);
+        });
+    }
+    #[cfg(feature = "bench")]
+    #[test]
+    fn bench_histogram_test() {
+        use crate::bench::{self, Histogram};
+        let histogram = Histogram::from_samples(vec![
+            Duration::from_micros(300),
+            Duration::from_micros(5),
+            Duration::from_millis(200),
+        ]);
+        assert_eq!(histogram.min(), Some(Duration::from_micros(5)));
+        assert_eq!(histogram.percentile(50.0), Some(Duration::from_micros(300)));
+        assert_eq!(histogram.max(), Some(Duration::from_millis(200)));
+        let buckets = histogram.buckets();
+        assert_eq!(buckets[0], (Some(Duration::from_micros(10)), 1));
+        assert_eq!(buckets[3], (Some(Duration::from_micros(500)), 1));
+        assert_eq!(buckets.last(), Some(&(None, 1)));
+        assert_eq!(Histogram::default().mean(), None);
+        let histogram = bench::run(4, &TimeoutConfig::new(TWENTY));
+        assert_eq!(histogram.len(), 4);
+        assert!(histogram.max().unwrap() < TWENTY);
+    }
+    #[test]
+    fn infinite_test() {
+        static FIRED: AtomicUsize = AtomicUsize::new(0);
+        let mut dyn_timeout = std_thread::DynTimeout::new(Duration::MAX, || {
+            FIRED.fetch_add(1, Ordering::SeqCst);
+        });
+        let err = dyn_timeout.add(TWENTY).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TimeoutError::InvalidDuration));
+        assert_eq!(dyn_timeout.remaining().unwrap(), Duration::MAX);
+        assert_eq!(dyn_timeout.deadline(), None);
+        // counted from the infinite delay
+        dyn_timeout.sub(Duration::MAX - TWENTY).unwrap();
+        assert!(dyn_timeout.remaining().unwrap() <= TWENTY);
+        dyn_timeout.wait().unwrap();
+        assert_eq!(FIRED.load(Ordering::SeqCst), 1);
+        let config = TimeoutConfig::new(Duration::MAX).jitter(TWENTY);
+        let mut dyn_timeout = std_thread::DynTimeout::with_config(&config, || {
+            FIRED.fetch_add(1, Ordering::SeqCst);
+        });
+        std::thread::sleep(TWENTY);
+        assert_eq!(FIRED.load(Ordering::SeqCst), 1);
+        dyn_timeout.set(TWENTY).unwrap();
+        dyn_timeout.wait().unwrap();
+        assert_eq!(FIRED.load(Ordering::SeqCst), 2);
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut dyn_timeout = tokio_impl::DynTimeout::new(Duration::MAX, || {});
+            assert_eq!(dyn_timeout.remaining().await, Duration::MAX);
+            dyn_timeout.set(TWENTY).await.unwrap();
+            assert!(dyn_timeout.remaining().await <= TWENTY);
+            dyn_timeout.wait().await.unwrap();
+            assert!(dyn_timeout.set(TWENTY).await.is_err());
+        });
+    }
+    #[test]
+    fn completion_test() {
+        use crate::event::Outcome;
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || {}).into_shared();
+            let waiters: Vec<_> = (0..4)
+                .map(|_| {
+                    let dyn_timeout = dyn_timeout.clone();
+                    tokio::spawn(async move { dyn_timeout.wait().await })
+                })
+                .collect();
+            for waiter in waiters {
+                waiter.await.unwrap().unwrap();
+            }
+            assert_eq!(dyn_timeout.completion().outcome(), Some(Outcome::Fired));
+
+            let mut dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || {});
+            let completion = dyn_timeout.completion();
+            assert_eq!(completion.outcome(), None);
+            dyn_timeout.cancel().await.unwrap();
+            assert_eq!(completion.wait().await, Outcome::Cancelled);
+
+            let dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || panic!("expected"));
+            assert_eq!(dyn_timeout.completion().wait().await, Outcome::Panicked);
+        });
+    }
+    #[test]
+    fn export_import_test() {
+        static FIRED: AtomicUsize = AtomicUsize::new(0);
+        let config = TimeoutConfig::new(TWENTY * 4)
+            .label("session")
+            .sub_policy(SubPolicy::Error);
+        let mut draining = std_thread::DynTimeout::with_config(&config, || {});
+        let record = draining.export(7).unwrap();
+        draining.cancel().unwrap();
+        assert_eq!(record.payload_id, 7);
+        assert_eq!(record.label.as_deref(), Some("session"));
+        std::thread::sleep(TWENTY);
+        let mut imported = std_thread::DynTimeout::import(&record, || {
+            FIRED.fetch_add(1, Ordering::SeqCst);
+        });
+        // the time elapsed since the export is deduced
+        assert!(imported.remaining().unwrap() <= TWENTY * 3);
+        assert!(imported.sub(TWENTY * 10).is_err());
+        imported.wait().unwrap();
+        assert_eq!(FIRED.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            imported.export(7).unwrap_err().downcast_ref(),
+            Some(&TimeoutError::Reached)
+        );
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let infinite = tokio_impl::DynTimeout::new(Duration::MAX, || {});
+            let record = infinite.export(1).await.unwrap();
+            let imported = tokio_impl::DynTimeout::import(&record, || {});
+            assert_eq!(imported.remaining().await, Duration::MAX);
+        });
+    }
+    #[test]
+    fn max_callback_runtime_test() {
+        use crate::{config::CallbackExecution, event::Outcome};
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let config = TimeoutConfig::new(TWENTY).max_callback_runtime(TWENTY);
+            let slow = tokio_impl::DynTimeout::new_async(&config, || async {
+                tokio::time::sleep(Duration::from_secs(10)).await
+            });
+            assert_eq!(slow.completion().wait().await, Outcome::Overrun);
+            assert_eq!(slow.overruns(), 1);
+            let quick = tokio_impl::DynTimeout::new_async(&config, || async {});
+            assert_eq!(quick.completion().wait().await, Outcome::Fired);
+            assert_eq!(quick.overruns(), 0);
+            let spawned = tokio_impl::DynTimeout::new_async(
+                &config
+                    .clone()
+                    .callback_execution(CallbackExecution::Spawned),
+                || async { tokio::time::sleep(Duration::from_secs(10)).await },
+            );
+            tokio::time::sleep(TWENTY * 3).await;
+            assert_eq!(spawned.overruns(), 1);
+        });
+    }
+    #[test]
+    fn timeout_set_test() {
+        use crate::{event::Outcome, tokio_impl::DynTimeoutSet};
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut set = DynTimeoutSet::new();
+            set.push(tokio_impl::DynTimeout::new(TWENTY, || {}));
+            let index = set.push(tokio_impl::DynTimeout::new(TWENTY * 2, || {}));
+            set.get_mut(index).unwrap().cancel().await.unwrap();
+            assert_eq!(
+                set.wait_all().await.unwrap(),
+                vec![Outcome::Fired, Outcome::Cancelled]
+            );
+            assert_eq!(
+                set.join_all().await,
+                vec![Outcome::Fired, Outcome::Cancelled]
+            );
+        });
+    }
+    #[test]
+    fn countdown_test() {
+        use crate::countdown::Countdown;
+        let format = |dur| Countdown(dur).to_string();
+        assert_eq!(format(Duration::ZERO), "0s");
+        assert_eq!(format(Duration::from_micros(449_200)), "450ms");
+        assert_eq!(format(Duration::from_millis(999_999)), "16m 40s");
+        assert_eq!(format(Duration::from_secs(3600)), "1h");
+        assert_eq!(format(Duration::from_secs(90_061)), "1d 1h 1m 1s");
+        assert_eq!(format(Duration::from_secs(3605)), "1h 5s");
+        let mut dyn_timeout = std_thread::DynTimeout::new(Duration::from_secs(133), || {});
+        dyn_timeout.sub(Duration::from_millis(500)).unwrap();
+        assert_eq!(dyn_timeout.remaining_human().unwrap(), "2m 13s");
+        dyn_timeout.cancel().unwrap();
+    }
+    #[test]
+    fn floor_test() {
+        let mut dyn_timeout = std_thread::DynTimeout::new(TWENTY * 10, || {});
+        dyn_timeout.set_floor(TWENTY * 5);
+        dyn_timeout.sub(TWENTY * 2).unwrap();
+        let remaining = dyn_timeout.remaining().unwrap();
+        assert!(remaining <= TWENTY * 8 && remaining > TWENTY * 7);
+        dyn_timeout.sub(TWENTY * 2).unwrap();
+        dyn_timeout.shrink_by_fraction(0.9).unwrap();
+        dyn_timeout.sub(Duration::MAX).unwrap();
+        let remaining = dyn_timeout.remaining().unwrap();
+        assert!(remaining <= TWENTY * 5 && remaining > TWENTY * 4);
+        // still reached once the floor elapsed
+        let start = SystemTime::now();
+        drop(dyn_timeout);
+        assert!(start.elapsed().unwrap() < TWENTY * 7);
+    }
+    #[test]
+    fn rearm_test() {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let config = TimeoutConfig::new(TWENTY).add_policy(AddPolicy::Rearm);
+        let dyn_timeout = std_thread::DynTimeout::with_config(&config, || {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        });
+        std::thread::sleep(TWENTY * 3);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+        dyn_timeout.add(TWENTY).unwrap();
+        drop(dyn_timeout);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+
+        let dyn_timeout = std_thread::DynTimeout::new(TWENTY, || {});
+        std::thread::sleep(TWENTY * 3);
+        assert!(dyn_timeout.add(TWENTY).is_err());
+    }
+    #[test]
+    fn drop_during_callback_test() {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        static DROPPED: AtomicBool = AtomicBool::new(false);
+        let config = TimeoutConfig::new(TWENTY).drop_behavior(DropBehavior::Cancel);
+        let dyn_timeout = std_thread::DynTimeout::with_handle(&config, |handle| {
+            // periodic, dropped before the end of the callback
+            COUNT.fetch_add(1, Ordering::Relaxed);
+            handle.rearm(TWENTY).unwrap();
+            while !DROPPED.load(Ordering::Relaxed) {
+                std::thread::sleep(TWENTY / 4);
+            }
+        });
+        while COUNT.load(Ordering::Relaxed) == 0 {
+            std::thread::sleep(TWENTY / 4);
+        }
+        drop(dyn_timeout);
+        DROPPED.store(true, Ordering::Relaxed);
+        std::thread::sleep(TWENTY * 5);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+    }
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_drop_during_callback_test() {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        static DROPPED: AtomicBool = AtomicBool::new(false);
+        let config = TimeoutConfig::new(TWENTY).drop_behavior(DropBehavior::Cancel);
+        let dyn_timeout = tokio_impl::DynTimeout::with_handle(&config, |handle| {
+            // periodic, dropped before the end of the callback
+            COUNT.fetch_add(1, Ordering::Relaxed);
+            handle.rearm(TWENTY).unwrap();
+            while !DROPPED.load(Ordering::Relaxed) {
+                std::thread::sleep(TWENTY / 4);
+            }
+        });
+        // the callback blocks a worker, the test waits without the runtime
+        while COUNT.load(Ordering::Relaxed) == 0 {
+            std::thread::sleep(TWENTY / 4);
+        }
+        drop(dyn_timeout);
+        DROPPED.store(true, Ordering::Relaxed);
+        std::thread::sleep(TWENTY * 5);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+    }
+    #[test]
+    fn add_race_test() {
+        let dur = Duration::from_millis(2);
+        // around the expiry, the add either wins or returns a distinct error
+        for i in 0..100 {
+            let fired = Arc::new(Mutex::new(None));
+            let thread_fired = fired.clone();
+            let start = std::time::Instant::now();
+            let dyn_timeout =
+                std_thread::DynTimeout::spawn(&TimeoutConfig::new(dur), true, move |_| {
+                    *thread_fired.lock().unwrap() = Some(std::time::Instant::now())
+                });
+            std::thread::sleep(Duration::from_micros(1500 + i % 10 * 100));
+            match dyn_timeout.add(TWENTY) {
+                Ok(()) => {
+                    drop(dyn_timeout);
+                    assert!(fired.lock().unwrap().unwrap() >= start + dur + TWENTY);
+                }
+                Err(err) => assert_eq!(err.downcast_ref(), Some(&TimeoutError::Reached)),
+            }
+        }
+    }
+    #[test]
+    fn stop_after_current_test() {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let config = TimeoutConfig::new(TWENTY).add_policy(AddPolicy::Rearm);
+        let mut dyn_timeout = std_thread::DynTimeout::with_config(&config, || {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        });
+        std::thread::sleep(TWENTY * 3);
+        dyn_timeout.add(TWENTY * 2).unwrap();
+        // the second cycle still completes
+        dyn_timeout.stop_after_current();
+        std::thread::sleep(TWENTY * 4);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+        assert!(dyn_timeout.add(TWENTY).is_err());
+        assert!(dyn_timeout.join_handle().unwrap().is_finished());
+        dyn_timeout.cancel().unwrap_err();
+    }
+    #[test]
+    fn callback_pool_test() {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let config = TimeoutConfig::new(TWENTY).callback_execution(CallbackExecution::SharedPool);
+        let timeouts: Vec<_> = (0..10)
+            .map(|_| {
+                std_thread::DynTimeout::with_config(&config, || {
+                    std::thread::sleep(TWENTY);
+                    COUNT.fetch_add(1, Ordering::Relaxed);
+                })
+            })
+            .collect();
+        // the timeout threads don't wait for the callbacks
+        drop(timeouts);
+        assert!(COUNT.load(Ordering::Relaxed) < 10);
+        std::thread::sleep(TWENTY * 20);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 10);
+    }
+    #[test]
+    fn callback_spawned_test() {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let config = TimeoutConfig::new(TWENTY)
+            .add_policy(AddPolicy::Rearm)
+            .callback_execution(CallbackExecution::Spawned);
+        let dyn_timeout = std_thread::DynTimeout::with_config(&config, || {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+            panic!("isolated from the timer");
+        });
+        std::thread::sleep(TWENTY * 2);
+        // the timer survived the panic and can be re-armed
+        dyn_timeout.add(TWENTY).unwrap();
+        std::thread::sleep(TWENTY * 2);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+        static REFUSED: AtomicBool = AtomicBool::new(false);
+        let _handled = std_thread::DynTimeout::with_handle(&config, |handle| {
+            REFUSED.store(handle.rearm(TWENTY).is_err(), Ordering::Relaxed);
+        });
+        std::thread::sleep(TWENTY * 2);
+        assert!(REFUSED.load(Ordering::Relaxed));
+    }
+    #[test]
+    fn wrap_callback_test() {
+        static CALLS: Mutex<Vec<&str>> = Mutex::new(vec![]);
+        let dyn_timeout = std_thread::DynTimeout::new(TWENTY, || {
+            CALLS.lock().unwrap().push("callback");
+        });
+        dyn_timeout.wrap_callback(|inner| {
+            move |handle: &std_thread::TimeoutHandle| {
+                CALLS.lock().unwrap().push("inner before");
+                inner(handle);
+                CALLS.lock().unwrap().push("inner after");
+            }
+        });
+        dyn_timeout.wrap_callback(|inner| {
+            move |handle: &std_thread::TimeoutHandle| {
+                CALLS.lock().unwrap().push("outer");
+                inner(handle);
+            }
+        });
+        drop(dyn_timeout);
+        assert_eq!(
+            *CALLS.lock().unwrap(),
+            ["outer", "inner before", "callback", "inner after"]
+        );
+    }
+    #[test]
+    fn breaker_test() {
+        use crate::breaker::{BreakerState, BreakerTimer};
+        let breaker = BreakerTimer::new(TWENTY * 2);
+        assert!(breaker.extend(TWENTY).is_err());
+        breaker.trip();
+        // repeated failures
+        breaker.extend(TWENTY * 2).unwrap();
+        breaker.trip();
+        assert!(breaker.remaining().unwrap() > TWENTY * 3);
+        std::thread::sleep(TWENTY * 3);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.success());
+        std::thread::sleep(TWENTY * 2);
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(breaker.extend(TWENTY).is_err());
+
+        // the trial call fails
+        breaker.trip();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        breaker.shorten(TWENTY * 2).unwrap();
+        std::thread::sleep(TWENTY);
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(breaker.success());
+        assert_eq!(breaker.remaining(), None);
+
+        breaker.trip();
+        breaker.reset();
+        std::thread::sleep(TWENTY * 3);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+    #[test]
+    fn rate_limit_drop_test() {
+        use crate::{rate_limit::RateLimiter, snapshot::TimeoutState};
+        // refilled continuously, dropped during a refill
+        let limiter = RateLimiter::new(1, Duration::ZERO);
+        let observer = limiter.observer();
+        std::thread::sleep(TWENTY);
+        drop(limiter);
+        std::thread::sleep(TWENTY);
+        for _ in 0..10 {
+            assert_ne!(observer.state(), TimeoutState::Running);
+            std::thread::sleep(TWENTY / 4);
+        }
+    }
+    #[test]
+    fn rate_limit_test() {
+        use crate::rate_limit::RateLimiter;
+        let limiter = RateLimiter::new(2, TWENTY);
+        assert!(limiter.try_acquire() && limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+        std::thread::sleep(TWENTY * 5 + TWENTY / 2);
+        // refilled up to the capacity only
+        assert_eq!(limiter.available(), 2);
+
+        // loosened during the refill in progress
+        limiter.set_interval(TWENTY * 4).unwrap();
+        assert_eq!(limiter.interval(), TWENTY * 4);
+        while limiter.try_acquire() {}
+        std::thread::sleep(TWENTY * 5);
+        assert!(limiter.available() <= 1);
+        let start = std::time::Instant::now();
+        limiter.acquire();
+        limiter.acquire();
+        assert!(start.elapsed() >= TWENTY * 2);
+
+        // tightened, the refill in progress is immediate
+        limiter.set_interval(Duration::ZERO).unwrap();
+        limiter.acquire();
+    }
+    #[test]
+    fn reuse_thread_test() {
+        static THREADS: Mutex<Vec<std::thread::ThreadId>> = Mutex::new(vec![]);
+        let config = TimeoutConfig::new(TWENTY).reuse_thread(true);
+        for _ in 0..3 {
+            let dyn_timeout = std_thread::DynTimeout::with_config(&config, || {
+                THREADS.lock().unwrap().push(std::thread::current().id());
+            });
+            assert!(dyn_timeout.join_handle().is_none());
+        }
+        let mut cancelled = std_thread::DynTimeout::with_config(&config, || {});
+        cancelled.cancel().unwrap();
+        std_thread::DynTimeout::with_config(&config, || {
+            THREADS.lock().unwrap().push(std::thread::current().id());
+        });
+        let threads = THREADS.lock().unwrap();
+        assert_eq!(threads.len(), 4);
+        assert!(threads.iter().all(|thread| *thread == threads[0]));
+    }
+    #[test]
+    fn watch_deadline_test() {
+        let mut dyn_timeout = std_thread::DynTimeout::new(TWENTY * 10, || {});
+        let mut deadline = dyn_timeout.watch_deadline().unwrap();
+        let first = deadline.borrow_and_update().unwrap();
+        dyn_timeout.sub(TWENTY).unwrap();
+        assert!(deadline.has_changed().unwrap());
+        assert_eq!(*deadline.borrow_and_update(), Some(first - TWENTY));
+        // not started until the first one is reached
+        let next = dyn_timeout.then(TWENTY, || {}).unwrap();
+        let mut next_deadline = next.watch_deadline().unwrap();
+        assert_eq!(*next_deadline.borrow(), None);
+        dyn_timeout.cancel().unwrap();
+        assert_eq!(*deadline.borrow(), None);
+        assert!(!next_deadline.has_changed().unwrap());
+        assert_eq!(*next_deadline.borrow_and_update(), None);
+
+        let first = std_thread::DynTimeout::new(TWENTY, || {});
+        let next = first.then(TWENTY, || {}).unwrap();
+        let mut next_deadline = next.watch_deadline().unwrap();
+        std::thread::sleep(TWENTY * 2);
+        assert!(next_deadline.has_changed().unwrap());
+        assert!(next_deadline.borrow_and_update().is_some());
+        std::thread::sleep(TWENTY * 2);
+        assert_eq!(*next_deadline.borrow(), None);
+    }
+    #[test]
+    fn history_test() {
+        let config = TimeoutConfig::new(TWENTY * 10).record_history(true);
+        let mut dyn_timeout = std_thread::DynTimeout::with_config(&config, || {});
+        dyn_timeout.add(TWENTY).unwrap();
+        let line = line!() + 1;
+        dyn_timeout.sub(TWENTY * 2).unwrap();
+        dyn_timeout.cancel().unwrap();
+        let history = dyn_timeout.history();
+        let kinds: Vec<_> = history.iter().map(|adjustment| adjustment.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                AdjustmentKind::Add(TWENTY),
+                AdjustmentKind::Sub(TWENTY * 2),
+                AdjustmentKind::Cancel
+            ]
+        );
+        let location = history[1].location.unwrap();
+        assert_eq!((location.file(), location.line()), (file!(), line));
+        assert!(std_thread::DynTimeout::new(TWENTY, || {})
+            .history()
+            .is_empty());
+    }
+    #[test]
+    fn history_capped_test() {
+        let config = TimeoutConfig::new(TWENTY * 10)
+            .record_history(true)
+            .max_total(TWENTY * 5);
+        let mut dyn_timeout = std_thread::DynTimeout::with_config(&config, || {});
+        // dismissed by the cap, not an extension
+        dyn_timeout.add(TWENTY).unwrap();
+        assert!(dyn_timeout.history().is_empty());
+        assert_eq!(dyn_timeout.snapshot().extensions, 0);
+        dyn_timeout.cancel().unwrap();
+    }
+    #[test]
+    fn actor_test() {
+        static FIRED: AtomicBool = AtomicBool::new(false);
+        let commands = std_thread::DynTimeout::actor(&TimeoutConfig::new(TWENTY * 5), || {
+            FIRED.store(true, Ordering::Relaxed)
+        });
+        let remote = commands.clone();
+        std::thread::spawn(move || remote.send(Adjust::Add(TWENTY * 5)).unwrap())
+            .join()
+            .unwrap();
+        commands.send(Adjust::Cancel).unwrap();
+        std::thread::sleep(TWENTY * 15);
+        assert!(!FIRED.load(Ordering::Relaxed));
+    }
+    #[test]
+    fn join_handle_test() {
+        static FIRED: AtomicBool = AtomicBool::new(false);
+        let dyn_timeout =
+            std_thread::DynTimeout::new(TWENTY, || FIRED.store(true, Ordering::Relaxed));
+        assert!(dyn_timeout.join_handle().is_some());
+        dyn_timeout.into_join_handle().unwrap().join().unwrap();
+        assert!(FIRED.load(Ordering::Relaxed));
+    }
+    #[test]
+    fn grace_test() {
+        static WARNED: AtomicBool = AtomicBool::new(false);
+        static FIRED: AtomicBool = AtomicBool::new(false);
+        let config = TimeoutConfig::new(TWENTY).grace(TWENTY * 5, || {
+            WARNED.store(true, Ordering::Relaxed);
+        });
+        let dyn_timeout =
+            std_thread::DynTimeout::with_config(&config, || FIRED.store(true, Ordering::Relaxed));
+        std::thread::sleep(TWENTY * 3);
+        assert!(WARNED.load(Ordering::Relaxed));
+        assert!(!FIRED.load(Ordering::Relaxed));
+        // the grace period is still extendable
+        dyn_timeout.add(TWENTY * 5).unwrap();
+        assert!(dyn_timeout.remaining().unwrap() > TWENTY * 5);
+        drop(dyn_timeout);
+        assert!(FIRED.load(Ordering::Relaxed));
+    }
+    #[test]
+    fn escalate_test() {
+        static STEPS: Mutex<Vec<&str>> = Mutex::new(vec![]);
+        let config = TimeoutConfig::new(TWENTY)
+            .escalate(|| STEPS.lock().unwrap().push("notify"), TWENTY)
+            .escalate(|| STEPS.lock().unwrap().push("degrade"), TWENTY);
+        let dyn_timeout =
+            std_thread::DynTimeout::with_config(&config, || STEPS.lock().unwrap().push("kill"));
+        dyn_timeout.add(TWENTY * 5).unwrap();
+        std::thread::sleep(TWENTY * 3);
+        assert!(STEPS.lock().unwrap().is_empty());
+        drop(dyn_timeout);
+        assert_eq!(*STEPS.lock().unwrap(), vec!["notify", "degrade", "kill"]);
+    }
+    #[test]
+    fn gate_test() {
+        use crate::gate::{GateOutcome, TimedGate};
+        let gate = TimedGate::new(TWENTY * 2);
+        let start = SystemTime::now();
+        let remote = gate.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(TWENTY);
+            remote.extend(TWENTY * 3).unwrap();
+        });
+        assert_eq!(gate.wait(), GateOutcome::TimedOut);
+        assert!(start.elapsed().unwrap() >= TWENTY * 5);
+        assert!(!gate.open());
+        assert!(gate.extend(TWENTY).is_err());
+
+        let gate = TimedGate::new(TWENTY * 10);
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let gate = gate.clone();
+                std::thread::spawn(move || gate.wait())
+            })
+            .collect();
+        std::thread::sleep(TWENTY);
+        assert!(gate.open());
+        for waiter in waiters {
+            assert_eq!(waiter.join().unwrap(), GateOutcome::Opened);
+        }
+        assert_eq!(gate.wait(), GateOutcome::Opened);
+    }
+    #[test]
+    fn watchdog_test() {
+        static WEDGED: AtomicBool = AtomicBool::new(false);
+        let watchdog =
+            ProcessWatchdog::with_hook(TWENTY * 2, || WEDGED.store(true, Ordering::Relaxed));
+        for _ in 0..5 {
+            std::thread::sleep(TWENTY);
+            watchdog.feed().unwrap();
+        }
+        assert!(!WEDGED.load(Ordering::Relaxed));
+        std::thread::sleep(TWENTY * 5);
+        assert!(WEDGED.load(Ordering::Relaxed));
+        assert!(watchdog.feed().is_err());
+    }
+    #[test]
+    fn ord_hash_test() {
+        use std::collections::{BinaryHeap, HashSet};
+
+        let first = std_thread::DynTimeout::new(TWENTY, || {});
+        let second = std_thread::DynTimeout::new(TWENTY * 2, || {});
+        assert!(first < second);
+        second.sub(TWENTY * 2).unwrap();
+        assert!(second < first);
+        assert!(first != second);
+        // still ordered by their deadlines once expired
+        std::thread::sleep(TWENTY * 2);
+        assert!(second < first);
+        let mut infinite = std_thread::DynTimeout::new(Duration::MAX, || {});
+        assert!(first < infinite);
+        infinite.cancel().unwrap();
+        let mut heap = BinaryHeap::new();
+        heap.push(std::cmp::Reverse(first));
+        heap.push(std::cmp::Reverse(second));
+        assert_eq!(heap.pop().unwrap().0.remaining().unwrap(), Duration::ZERO);
+
+        let shared = std_thread::DynTimeout::new(TWENTY, || {}).into_shared();
+        // hashed by identity, the mutable deadline isn't part of the key
+        #[allow(clippy::mutable_key_type)]
+        let mut set = HashSet::new();
+        set.insert(shared.clone());
+        assert!(set.contains(&shared));
+    }
+    #[test]
+    fn handle_test() {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dyn_timeout =
+            std_thread::DynTimeout::with_handle(&TimeoutConfig::new(TWENTY), |handle| {
+                if COUNT.fetch_add(1, Ordering::Relaxed) < 2 {
+                    handle.rearm(TWENTY).unwrap();
+                }
+            });
+        std::thread::sleep(TWENTY * 2);
+        assert!(COUNT.load(Ordering::Relaxed) < 3);
+        std::thread::sleep(TWENTY * 3);
+        drop(dyn_timeout);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 3);
+    }
+    #[test]
+    fn expiration_test() {
+        static SEQ: AtomicUsize = AtomicUsize::new(0);
+        let dyn_timeout =
+            std_thread::DynTimeout::with_handle(&TimeoutConfig::new(TWENTY), |handle| {
+                let expiration = handle.expiration().unwrap();
+                assert!(expiration.fired_at >= expiration.scheduled);
+                assert!(expiration.lateness() < TWENTY * 5);
+                SEQ.store(expiration.seq as usize, Ordering::Relaxed);
+                if expiration.seq < 3 {
+                    handle.rearm(TWENTY).unwrap();
+                }
+            });
+        std::thread::sleep(TWENTY * 6);
+        drop(dyn_timeout);
+        assert_eq!(SEQ.load(Ordering::Relaxed), 3);
+    }
+    #[cfg(all(feature = "systemd", unix))]
+    #[test]
+    fn systemd_test() {
+        use crate::systemd::SystemdWatchdog;
+        use std::os::unix::net::UnixDatagram;
+
+        let path = std::env::temp_dir().join("dyn-timeout-systemd-test.sock");
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        std::env::set_var("NOTIFY_SOCKET", &path);
+        let watchdog = SystemdWatchdog::new(TWENTY);
+        let mut buffer = [0; 64];
+        for _ in 0..2 {
+            let len = socket.recv(&mut buffer).unwrap();
+            assert_eq!(&buffer[..len], b"WATCHDOG=1");
+        }
+        watchdog.set_watchdog_timeout(TWENTY * 10).unwrap();
+        drop(watchdog);
+        let len = socket.recv(&mut buffer).unwrap();
+        assert!(buffer[..len].starts_with(b"WATCHDOG"));
+
+        // pinging continuously, dropped during a ping
+        let watchdog = SystemdWatchdog::new(Duration::ZERO);
+        socket.recv(&mut buffer).unwrap();
+        drop(watchdog);
+        // the pings in flight are received, then they stop
+        socket.set_read_timeout(Some(TWENTY * 2)).unwrap();
+        let start = std::time::Instant::now();
+        while socket.recv(&mut buffer).is_ok() {
+            assert!(start.elapsed() < Duration::from_secs(1));
+        }
+    }
+    #[cfg(all(feature = "signal", unix))]
+    #[test]
+    fn signal_test() {
+        use crate::signal::{SignalAction, SignalExtension};
+
+        let dyn_timeout = std_thread::DynTimeout::new(TWENTY * 5, || {}).into_shared();
+        let extension = SignalExtension::new(
+            libc::SIGUSR2,
+            dyn_timeout.clone(),
+            SignalAction::Reset(TWENTY * 50),
+        )
+        .unwrap();
+        unsafe { libc::raise(libc::SIGUSR2) };
+        std::thread::sleep(TWENTY);
+        assert!(dyn_timeout.remaining().unwrap() > TWENTY * 40);
+        // more signals than the queue holds, dropped right after
+        for _ in 0..10_000 {
+            unsafe { libc::raise(libc::SIGUSR2) };
+        }
+        drop(extension);
+        assert!(dyn_timeout.remaining().unwrap() > TWENTY * 40);
+        dyn_timeout.cancel().unwrap();
+    }
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn ffi_test() {
+        use crate::ffi::*;
+        use std::os::raw::c_void;
+
+        extern "C" fn callback(user_data: *mut c_void) {
+            let count = unsafe { &*(user_data as *const AtomicUsize) };
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+        let count = AtomicUsize::new(0);
+        let user_data = &count as *const AtomicUsize as *mut c_void;
+        unsafe {
+            assert!(dyn_timeout_new(20, None, user_data).is_null());
+            let timeout = dyn_timeout_new(20, Some(callback), user_data);
+            assert_eq!(dyn_timeout_add(timeout, 20), 0);
+            assert_eq!(dyn_timeout_sub(timeout, 10), 0);
+            dyn_timeout_free(timeout);
+            let timeout = dyn_timeout_new(20, Some(callback), user_data);
+            assert_eq!(dyn_timeout_cancel(timeout), 0);
+            assert_eq!(dyn_timeout_add(timeout, 20), -1);
+            dyn_timeout_free(timeout);
+        }
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+    }
+    #[cfg(feature = "python")]
+    #[test]
+    fn python_test() {
+        use pyo3::{ffi::c_str, prelude::*};
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "dyn_timeout").unwrap();
+            module.add_class::<crate::python::PyDynTimeout>().unwrap();
+            let globals = pyo3::types::PyDict::new(py);
+            globals.set_item("dyn_timeout", module).unwrap();
+            py.run(
+                c_str!(
+                    "import time\n\
+                     fired = []\n\
+                     timeout = dyn_timeout.DynTimeout(0.02, lambda: fired.append(1))\n\
+                     timeout.add(0.02)\n\
+                     assert timeout.remaining() > 0.02\n\
+                     time.sleep(0.08)\n\
+                     assert fired == [1]\n\
+                     cancelled = dyn_timeout.DynTimeout(0.02, lambda: fired.append(2))\n\
+                     cancelled.cancel()\n\
+                     time.sleep(0.04)\n\
+                     assert fired == [1]"
+                ),
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+        });
+    }
+    #[cfg(feature = "humantime")]
+    #[test]
+    fn adjust_parse_test() {
+        assert_eq!(
+            "+5s".parse::<Adjust>().unwrap(),
+            Adjust::Add(Duration::from_secs(5))
+        );
+        assert_eq!(
+            " - 2s".parse::<Adjust>().unwrap(),
+            Adjust::Sub(Duration::from_secs(2))
+        );
+        assert_eq!("cancel\n".parse::<Adjust>().unwrap(), Adjust::Cancel);
+        assert!("5s".parse::<Adjust>().is_err());
+        assert!("+forever".parse::<Adjust>().is_err());
+    }
+    #[cfg(feature = "indicatif")]
+    #[test]
+    fn progress_test() {
+        use crate::progress::TimeoutProgress;
+        use indicatif::ProgressBar;
+
+        let dyn_timeout = std_thread::DynTimeout::new(TWENTY * 2, || {}).into_shared();
+        let progress =
+            TimeoutProgress::with_refresh(dyn_timeout.clone(), ProgressBar::hidden(), TWENTY / 4);
+        std::thread::sleep(TWENTY);
+        assert!(progress.bar().length().unwrap() <= 40);
+        dyn_timeout.add(TWENTY * 3).unwrap();
+        std::thread::sleep(TWENTY);
+        assert!(progress.bar().length().unwrap() >= 90);
+        std::thread::sleep(TWENTY * 5);
+        assert!(progress.bar().is_finished());
+    }
+    #[test]
+    fn child_test() {
+        static FIRED: AtomicBool = AtomicBool::new(false);
+        let parent = std_thread::DynTimeout::new(TWENTY * 2, || {});
+        let child = parent
+            .child(TWENTY * 10, || FIRED.store(true, Ordering::Relaxed))
+            .unwrap();
+        assert!(child.remaining().unwrap() <= TWENTY * 2);
+        // the child can't outlive its parent
+        child.add(TWENTY * 10).unwrap();
+        drop(parent);
+        drop(child);
+        assert!(!FIRED.load(Ordering::Relaxed));
+    }
+    #[test]
+    fn cancel_race_test() {
+        // cancel around the deadline, either the cancel or the callback wins
+        for i in 0..200 {
+            let fired = Arc::new(AtomicUsize::new(0));
+            let counter = fired.clone();
+            let dur = Duration::from_micros(i % 20 * 50);
+            let mut dyn_timeout =
+                std_thread::DynTimeout::spawn(&TimeoutConfig::new(dur), true, move |_| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                });
+            std::thread::sleep(Duration::from_micros(i % 7 * 100));
+            match dyn_timeout.cancel() {
+                Ok(()) => assert_eq!(fired.load(Ordering::SeqCst), 0),
+                Err(err) => {
+                    assert_eq!(err.downcast_ref(), Some(&TimeoutError::AlreadyFired));
+                    drop(dyn_timeout);
+                    assert_eq!(fired.load(Ordering::SeqCst), 1);
+                }
+            }
+        }
+    }
+    #[test]
+    fn budget_test() {
+        let request = std_thread::DynTimeout::new(TWENTY * 10, || {});
+        let mut budget = request.budget();
+        let first = budget.take(0.5, || {}).unwrap();
+        assert!(first.remaining().unwrap() <= TWENTY * 5);
+        // the first step runs long, the next ones share what's left
+        std::thread::sleep(TWENTY * 7);
+        let second = budget.take(0.25, || {}).unwrap();
+        assert!(second.remaining().unwrap() <= TWENTY * 3 / 2);
+        assert!(budget.take(0.5, || {}).is_err());
+        let last = budget.take(budget.unallocated(), || {}).unwrap();
+        assert!(last.remaining().unwrap() > TWENTY * 2);
+        assert!(budget.take(0.1, || {}).is_err());
+    }
+    #[test]
+    fn cancel_completed_test() {
+        let dyn_timeout = std_thread::DynTimeout::new(TWENTY * 10, || {}).into_shared();
+        dyn_timeout.cancel().unwrap();
+        std::thread::sleep(TWENTY);
+        dyn_timeout.cancel().unwrap();
+
+        // the running callback isn't joined
+        let mut dyn_timeout = std_thread::DynTimeout::new(Duration::ZERO, || {
+            std::thread::sleep(TWENTY * 10);
+        });
+        std::thread::sleep(TWENTY);
+        let start = SystemTime::now();
+        let err = dyn_timeout.cancel().unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TimeoutError::AlreadyFired));
+        assert!(start.elapsed().unwrap() < TWENTY * 5);
+    }
+    #[test]
+    fn overflow_test() {
+        let dyn_timeout = std_thread::DynTimeout::new(Duration::MAX, || {});
+        let err = dyn_timeout.add(Duration::MAX).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TimeoutError::InvalidDuration));
+        let err = dyn_timeout.extend_by_fraction(f64::MAX).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TimeoutError::InvalidDuration));
+        dyn_timeout.sub(Duration::MAX).unwrap();
+
+        let driver = Driver::polled();
+        driver.insert(1, Duration::MAX, ());
+        let err = driver.add(&1, Duration::MAX).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TimeoutError::InvalidDuration));
+        assert!(driver.remaining(&1).is_some());
+    }
     //#[bench]
     //fn simple_bench(b: &mut Bencher) {
     //    b.iter(|| {
@@ -43,6 +2031,375 @@ mod test {
         dyn_timeout.add(TWENTY).await.unwrap();
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokio_cancel_race_test() {
+        for i in 0..200 {
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+            let dur = Duration::from_micros(i % 20 * 50);
+            let mut dyn_timeout = tokio_impl::DynTimeout::with_sender(dur, sender);
+            tokio::time::sleep(Duration::from_micros(i % 7 * 100)).await;
+            let cancelled = dyn_timeout.cancel().await;
+            // the sender is dropped at the end of the task
+            let fired = receiver.recv().await.is_some();
+            match cancelled {
+                Ok(()) => assert!(!fired),
+                Err(err) => {
+                    assert_eq!(err.downcast_ref(), Some(&TimeoutError::AlreadyFired));
+                    assert!(fired);
+                }
+            }
+        }
+    }
+    #[tokio::test]
+    async fn tokio_rearm_test() {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let config = TimeoutConfig::new(TWENTY).add_policy(AddPolicy::Rearm);
+        let mut dyn_timeout = tokio_impl::DynTimeout::with_config(&config, || {
+            COUNT.fetch_add(1, Ordering::Relaxed);
+        });
+        dyn_timeout.wait().await.unwrap();
+        dyn_timeout.add(TWENTY).await.unwrap();
+        dyn_timeout.wait().await.unwrap();
+        assert_eq!(COUNT.load(Ordering::Relaxed), 2);
+    }
+
+    #[cfg(feature = "tokio-util")]
+    #[tokio::test]
+    async fn cancellation_token_test() {
+        use tokio_util::sync::CancellationToken;
+
+        let shutdown = CancellationToken::new();
+        let mut dyn_timeout = tokio_impl::DynTimeout::new(Duration::from_secs(20), || {
+            panic!("Should never append");
+        })
+        .linked_to(shutdown.clone());
+        shutdown.cancel();
+        dyn_timeout.wait().await.unwrap();
+        assert!(dyn_timeout.add(TWENTY).await.is_err());
+
+        let shutdown = CancellationToken::new();
+        let _dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || {})
+            .linked_to(shutdown.clone())
+            .cancel_token_on_fire();
+        tokio::time::timeout(TWENTY * 10, shutdown.cancelled())
+            .await
+            .unwrap();
+    }
+
+    #[cfg(feature = "futures-sink")]
+    #[tokio::test]
+    async fn sink_test() {
+        use futures::{stream, StreamExt};
+
+        let dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || {});
+        stream::iter(0..3)
+            .map(|_| Ok(TWENTY))
+            .forward(dyn_timeout.sink())
+            .await
+            .unwrap();
+        assert!(dyn_timeout.remaining().await > TWENTY * 3);
+    }
+
+    #[tokio::test]
+    async fn shared_test() {
+        let dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || {}).into_shared();
+        let remote = dyn_timeout.clone();
+        let waiter = dyn_timeout.clone();
+        let start = tokio::time::Instant::now();
+        remote.add(TWENTY).await.unwrap();
+        let waiting = tokio::spawn(async move { waiter.wait().await.unwrap() });
+        dyn_timeout.wait().await.unwrap();
+        waiting.await.unwrap();
+        assert!(start.elapsed() >= TWENTY * 2);
+        let err = remote.cancel().await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&TimeoutError::AlreadyFired));
+    }
+
+    #[tokio::test]
+    async fn with_deadline_test() {
+        use crate::error::Elapsed;
+        let dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || {}).into_shared();
+        let remote = dyn_timeout.clone();
+        tokio::spawn(async move { remote.add(TWENTY * 5).await.unwrap() });
+        let output = crate::with_deadline!(dyn_timeout, async {
+            tokio::time::sleep(TWENTY * 3).await;
+            1
+        });
+        assert_eq!(output, Ok(1));
+        assert_eq!(
+            crate::with_deadline!(dyn_timeout, tokio::time::sleep(TWENTY * 10)),
+            Err(Elapsed)
+        );
+
+        // no deadline once cancelled
+        let mut dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || {});
+        dyn_timeout.cancel().await.unwrap();
+        let output = crate::with_deadline!(dyn_timeout, async {
+            tokio::time::sleep(TWENTY * 2).await;
+            2
+        });
+        assert_eq!(output, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn deadline_context_test() {
+        use crate::{context, error::Elapsed};
+        assert!(context::current_deadline().is_none());
+        let free = context::spawn_with_deadline(async { 1 });
+        assert_eq!(free.await.unwrap(), Ok(1));
+
+        let request = tokio_impl::DynTimeout::new(TWENTY * 2, || {}).into_shared();
+        let (quick, slow) = context::deadline_scope(request.clone(), async {
+            let quick = context::spawn_with_deadline(async {
+                // inherited by the nested tasks
+                let nested = context::spawn_with_deadline(async {
+                    context::current_deadline()
+                        .unwrap()
+                        .add(TWENTY)
+                        .await
+                        .unwrap();
+                    tokio::time::sleep(TWENTY * 2).await;
+                    2
+                });
+                nested.await.unwrap()
+            });
+            let slow = context::spawn_with_deadline(tokio::time::sleep(TWENTY * 10));
+            (quick, slow)
+        })
+        .await
+        .unwrap();
+        // the parent is done, the deadline still applies to the tasks
+        assert_eq!(quick.await.unwrap(), Ok(Ok(2)));
+        assert_eq!(slow.await.unwrap(), Err(Elapsed));
+    }
+
+    #[tokio::test]
+    async fn interval_test() {
+        use crate::interval::{DynInterval, MissedTick};
+        use tokio::time::Instant;
+
+        // in tens of milliseconds since the first tick
+        for (missed, expected) in [
+            (MissedTick::Burst, [7, 7, 7, 8]),
+            (MissedTick::Skip, [7, 8, 10, 12]),
+            (MissedTick::Delay, [7, 9, 11, 13]),
+        ] {
+            let mut interval = DynInterval::new(TWENTY).missed_tick(missed);
+            let start = interval.tick().await;
+            // late of three ticks and a half
+            tokio::time::sleep(TWENTY * 7 / 2).await;
+            let mut ticks = vec![];
+            for _ in 0..4 {
+                interval.tick().await;
+                ticks.push((Instant::now() - start).as_millis() / 10);
+            }
+            assert_eq!(ticks, expected, "{:?}", missed);
+        }
+
+        let mut interval = DynInterval::new(TWENTY);
+        interval.tick().await;
+        interval.tick().await;
+        interval.stop_after_current();
+        assert!(!interval.is_finished());
+        interval.tick().await;
+        assert!(interval.is_finished());
+        let next = tokio::time::timeout(TWENTY * 3, interval.tick()).await;
+        assert!(next.is_err());
+
+        // a period which can't be represented is never reached
+        let mut interval = DynInterval::new(TWENTY);
+        interval.tick().await;
+        interval.set_period(Duration::MAX);
+        let next = tokio::time::timeout(TWENTY * 3, interval.tick()).await;
+        assert!(next.is_err());
+        interval.set_period(TWENTY);
+        interval.tick().await;
+    }
+
+    #[tokio::test]
+    async fn close_test() {
+        use crate::event::Outcome;
+        static FIRED: AtomicBool = AtomicBool::new(false);
+        let dyn_timeout = tokio_impl::DynTimeout::new(TWENTY, || {
+            std::thread::sleep(TWENTY * 2);
+            FIRED.store(true, Ordering::Relaxed);
+        });
+        tokio::time::sleep(TWENTY * 2).await;
+        // the callback is waited
+        assert_eq!(dyn_timeout.close().await, Outcome::Fired);
+        assert!(FIRED.load(Ordering::Relaxed));
+
+        let dyn_timeout = tokio_impl::DynTimeout::new(Duration::ZERO, || panic!("close test"));
+        tokio::time::sleep(TWENTY).await;
+        assert_eq!(dyn_timeout.close().await, Outcome::Panicked);
+    }
+
+    #[tokio::test]
+    async fn sender_dropped_test() {
+        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+        let dyn_timeout = tokio_impl::DynTimeout::with_sender(TWENTY, sender);
+        drop(receiver);
+        let task = dyn_timeout.into_join_handle().unwrap();
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn callback_spawned_tokio_test() {
+        let config = TimeoutConfig::new(TWENTY);
+        let inline = tokio_impl::DynTimeout::with_config(&config, || panic!("in the timer task"));
+        assert!(inline.into_join_handle().unwrap().await.is_err());
+        let config = config.callback_execution(CallbackExecution::Spawned);
+        let spawned = tokio_impl::DynTimeout::with_config(&config, || panic!("in its own task"));
+        assert!(spawned.into_join_handle().unwrap().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn task_id_test() {
+        let config = TimeoutConfig::new(TWENTY).label("session");
+        let mut first = tokio_impl::DynTimeout::with_config(&config, || {});
+        let second = tokio_impl::DynTimeout::new(TWENTY, || {});
+        let id = first.task_id().unwrap();
+        assert_eq!(first.join_handle().unwrap().id(), id);
+        assert_ne!(second.task_id().unwrap(), id);
+        first.cancel().await.unwrap();
+        assert_eq!(first.task_id(), None);
+    }
+
+    #[tokio::test]
+    async fn send_policy_test() {
+        use crate::config::SendPolicy;
+        static FALLBACK: AtomicBool = AtomicBool::new(false);
+        for policy in [
+            SendPolicy::Drop,
+            SendPolicy::WaitFor(TWENTY),
+            SendPolicy::Fallback(|| FALLBACK.store(true, Ordering::Relaxed)),
+        ] {
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+            sender.send(()).await.unwrap();
+            let dyn_timeout = tokio_impl::DynTimeout::with_sender_policy(TWENTY, sender, policy);
+            // the channel stays full, the task ends anyway
+            let task = dyn_timeout.into_join_handle().unwrap();
+            tokio::time::timeout(TWENTY * 5, task)
+                .await
+                .unwrap()
+                .unwrap();
+            receiver.recv().await.unwrap();
+            assert!(receiver.try_recv().is_err());
+        }
+        assert!(FALLBACK.load(Ordering::Relaxed));
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+        sender.send(()).await.unwrap();
+        let dyn_timeout =
+            tokio_impl::DynTimeout::with_sender_policy(TWENTY, sender, SendPolicy::Wait);
+        tokio::time::sleep(TWENTY * 3).await;
+        receiver.recv().await.unwrap();
+        // sent once there is room
+        receiver.recv().await.unwrap();
+        drop(dyn_timeout);
+    }
+
+    #[tokio::test]
+    async fn tokio_cancel_twice_test() {
+        let dyn_timeout = tokio_impl::DynTimeout::new(TWENTY * 10, || {}).into_shared();
+        dyn_timeout.cancel().await.unwrap();
+        tokio::time::sleep(TWENTY).await;
+        dyn_timeout.cancel().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn process_test() {
+        use crate::process::TimedCommand;
+        use tokio::process::Command;
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "sleep 5"]);
+        let start = tokio::time::Instant::now();
+        assert!(TimedCommand::new(command, TWENTY).output().await.is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "for i in 1 2 3 4; do echo $i; sleep 0.05; done"]);
+        let output = TimedCommand::new(command, TWENTY * 4)
+            .extend_on_output(TWENTY * 4)
+            .output()
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"1\n2\n3\n4\n");
+    }
+
+    #[tokio::test]
+    async fn sleep_test() {
+        let start = tokio::time::Instant::now();
+        let sleep = DynSleep::new(TWENTY);
+        let handle = sleep.handle();
+        tokio::spawn(async move {
+            tokio::time::sleep(TWENTY / 2).await;
+            handle.add(TWENTY);
+        });
+        sleep.await;
+        assert!(start.elapsed() >= TWENTY * 2);
+
+        let start = tokio::time::Instant::now();
+        let sleep = DynSleep::new(Duration::from_secs(20));
+        let handle = sleep.handle();
+        tokio::spawn(async move {
+            tokio::time::sleep(TWENTY).await;
+            handle.sub(Duration::from_secs(20));
+        });
+        sleep.await;
+        assert!(start.elapsed() < TWENTY * 10);
+
+        // the delays which can't be represented are far deadlines
+        let sleep = DynSleep::new(Duration::MAX);
+        sleep.add(Duration::MAX);
+        assert!(sleep.remaining() > Duration::from_secs(60));
+        sleep.reset(Duration::MAX);
+        sleep.reset(TWENTY);
+        sleep.await;
+    }
+
+    #[cfg(feature = "tokio-stream")]
+    #[tokio::test]
+    async fn inactivity_test() {
+        use crate::inactivity::{Inactivity, StalenessError};
+        use tokio_stream::StreamExt;
+
+        let (sender, receiver) = tokio::sync::broadcast::channel::<u32>(8);
+        let mut messages = Inactivity::from_broadcast(receiver, TWENTY * 2);
+        tokio::spawn(async move {
+            for i in 0..3 {
+                tokio::time::sleep(TWENTY).await;
+                sender.send(i).unwrap();
+            }
+            tokio::time::sleep(TWENTY * 10).await;
+        });
+        for i in 0..3 {
+            assert_eq!(messages.next().await.unwrap().unwrap().unwrap(), i);
+        }
+        assert_eq!(
+            messages.next().await.unwrap().unwrap_err(),
+            StalenessError { quiet: TWENTY * 2 }
+        );
+    }
+
+    #[tokio::test]
+    async fn guard_task_test() {
+        let task = tokio::spawn(tokio::time::sleep(Duration::from_secs(20)));
+        let dyn_timeout = tokio_impl::DynTimeout::guard_task(TWENTY, &task);
+        dyn_timeout.add(TWENTY).await.unwrap();
+        tokio::time::sleep(TWENTY).await;
+        assert!(!task.is_finished());
+        assert!(task.await.unwrap_err().is_cancelled());
+
+        let task = tokio::spawn(async { 42 });
+        let mut dyn_timeout = tokio_impl::DynTimeout::guard_task(TWENTY, &task);
+        assert_eq!(task.await.unwrap(), 42);
+        dyn_timeout.wait().await.unwrap();
+    }
+
     lazy_static::lazy_static! {
         static ref TIME: Arc::<Mutex::<SystemTime>> = Arc::new(Mutex::new(SystemTime::now()));
     }
@@ -0,0 +1,37 @@
+//! Small thread pool shared by the std timeouts to execute their callbacks
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+lazy_static::lazy_static! {
+    static ref POOL: Mutex<mpsc::Sender<Job>> = Mutex::new(start());
+}
+
+/// Start one worker by available core.
+fn start() -> mpsc::Sender<Job> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    let size = thread::available_parallelism().map_or(1, |size| size.get());
+    for _ in 0..size {
+        let receiver = receiver.clone();
+        thread::spawn(move || loop {
+            let job = match receiver.lock().unwrap().recv() {
+                Ok(job) => job,
+                Err(_) => return,
+            };
+            // a panicking callback must not reduce the pool size
+            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+        });
+    }
+    sender
+}
+
+/// Execute the job in one of the workers, the workers are started on the
+/// first call.
+pub(crate) fn execute(job: Job) {
+    let _ = POOL.lock().unwrap().send(job);
+}
@@ -0,0 +1,162 @@
+//! Run an external command under a dynamic timeout, built on the tokio
+//! implementation
+use crate::tokio_impl::{DynTimeout, SharedDynTimeout};
+use anyhow::{bail, Result};
+use std::{
+    process::{Output, Stdio},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::{Child, Command},
+    task::JoinHandle,
+};
+
+/// Command killed if it doesn't end before a dynamic deadline. The deadline
+/// can be extended while the command runs, automatically each time it
+/// writes on its output with `extend_on_output`, or through
+/// `TimedChild::timeout`.
+///
+/// # Example
+/// ```
+/// use tokio::{process::Command, runtime::Runtime};
+/// use dyn_timeout::process::TimedCommand;
+/// use std::time::Duration;
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let mut command = Command::new("echo");
+///    command.arg("hello");
+///    let output = TimedCommand::new(command, Duration::from_secs(5))
+///        .extend_on_output(Duration::from_secs(1))
+///        .output()
+///        .await
+///        .unwrap();
+///    assert_eq!(output.stdout, b"hello\n");
+/// });
+/// ```
+pub struct TimedCommand {
+    command: Command,
+    duration: Duration,
+    extend_on_output: Option<Duration>,
+}
+
+impl TimedCommand {
+    /// Prepare the command with the initial delay before it's killed.
+    pub fn new(command: Command, duration: Duration) -> Self {
+        Self {
+            command,
+            duration,
+            extend_on_output: None,
+        }
+    }
+    /// Extend the deadline by `dur` each time the command writes on its
+    /// standard or error output, so a command printing its progress isn't
+    /// killed.
+    pub fn extend_on_output(mut self, dur: Duration) -> Self {
+        self.extend_on_output = Some(dur);
+        self
+    }
+    /// Start the command and its timeout. The outputs of the command are
+    /// captured.
+    ///
+    /// # Return
+    /// Return a result with an error if the command can't be started.
+    /// Otherwise it return the running child.
+    pub fn spawn(mut self) -> Result<TimedChild> {
+        let mut child = self
+            .command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let timeout = DynTimeout::new(self.duration, || {}).into_shared();
+        let stdout = child
+            .stdout
+            .take()
+            .map(|out| read(out, timeout.clone(), self.extend_on_output));
+        let stderr = child
+            .stderr
+            .take()
+            .map(|out| read(out, timeout.clone(), self.extend_on_output));
+        Ok(TimedChild {
+            child,
+            timeout,
+            stdout,
+            stderr,
+        })
+    }
+    /// Run the command until it ends or the deadline is reached.
+    ///
+    /// # Return
+    /// Return a result with an error if the command can't be started, or if
+    /// it was killed by the timeout.
+    /// Otherwise it return the output of the command.
+    pub async fn output(self) -> Result<Output> {
+        self.spawn()?.wait_with_output().await
+    }
+}
+
+/// Command started by `TimedCommand::spawn`.
+pub struct TimedChild {
+    child: Child,
+    timeout: SharedDynTimeout,
+    stdout: Option<JoinHandle<Vec<u8>>>,
+    stderr: Option<JoinHandle<Vec<u8>>>,
+}
+
+impl TimedChild {
+    /// Timeout of the command, to extend it from elsewhere.
+    pub fn timeout(&self) -> &SharedDynTimeout {
+        &self.timeout
+    }
+    /// Wait for the end of the command, it's killed if the deadline is
+    /// reached first.
+    ///
+    /// # Return
+    /// Return a result with an error if the command was killed by the
+    /// timeout or if it can't be waited.
+    /// Otherwise it return the output of the command.
+    pub async fn wait_with_output(mut self) -> Result<Output> {
+        let status = tokio::select! {
+            status = self.child.wait() => status?,
+            _ = self.timeout.wait() => {
+                self.child.kill().await?;
+                bail!("Command killed by the timeout")
+            }
+        };
+        let _ = self.timeout.cancel().await;
+        Ok(Output {
+            status,
+            stdout: collect(self.stdout).await?,
+            stderr: collect(self.stderr).await?,
+        })
+    }
+}
+
+/// Read an output of the command until its end, extending the timeout at
+/// each read if requested.
+fn read<R>(mut out: R, timeout: SharedDynTimeout, extend: Option<Duration>) -> JoinHandle<Vec<u8>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut output = vec![];
+        let mut buffer = [0; 1024];
+        while let Ok(len @ 1..) = out.read(&mut buffer).await {
+            output.extend_from_slice(&buffer[..len]);
+            if let Some(dur) = extend {
+                // dismissed once the timeout is reached, the child is killed
+                let _ = timeout.add(dur).await;
+            }
+        }
+        output
+    })
+}
+
+async fn collect(output: Option<JoinHandle<Vec<u8>>>) -> Result<Vec<u8>> {
+    match output {
+        Some(output) => Ok(output.await?),
+        None => Ok(vec![]),
+    }
+}
@@ -0,0 +1,94 @@
+//! Show the remaining time of a timeout in an indicatif progress bar
+use crate::{countdown::Countdown, std_thread::SharedDynTimeout};
+use indicatif::ProgressBar;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Default delay between two updates of the bar.
+pub const DEFAULT_REFRESH: Duration = Duration::from_millis(100);
+
+/// Progress bar following a timeout: the length of the bar is the total
+/// time to wait, updated when the timeout is increased or decreased, and
+/// the message is the remaining time formatted by `Countdown`. The ETA of
+/// indicatif is then the moment the timeout is reached. The bar is finished
+/// when the timeout is reached, and left as is when the progress is dropped.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use indicatif::{ProgressBar, ProgressStyle};
+/// use dyn_timeout::{progress::TimeoutProgress, std_thread::DynTimeout};
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let dyn_timeout = DynTimeout::new(TWENTY * 5, || {
+///    println!("aborting");
+/// })
+/// .into_shared();
+/// let bar = ProgressBar::new(0)
+///     .with_style(ProgressStyle::with_template("{bar:40} will abort in {msg}").unwrap());
+/// let progress = TimeoutProgress::new(dyn_timeout.clone(), bar);
+/// dyn_timeout.add(TWENTY).unwrap();
+/// ```
+pub struct TimeoutProgress {
+    bar: ProgressBar,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TimeoutProgress {
+    /// Drive the bar from the remaining time of the timeout, refreshed every
+    /// `DEFAULT_REFRESH`.
+    pub fn new(timeout: SharedDynTimeout, bar: ProgressBar) -> Self {
+        Self::with_refresh(timeout, bar, DEFAULT_REFRESH)
+    }
+    /// Drive the bar from the remaining time of the timeout, refreshed at
+    /// the given interval.
+    pub fn with_refresh(timeout: SharedDynTimeout, bar: ProgressBar, refresh: Duration) -> Self {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread_stopped = stopped.clone();
+        let thread_bar = bar.clone();
+        let start = Instant::now();
+        let thread = thread::spawn(move || {
+            while !thread_stopped.load(Ordering::Relaxed) {
+                let remaining = match timeout.remaining() {
+                    Ok(remaining) => remaining,
+                    Err(_) => return,
+                };
+                let elapsed = start.elapsed();
+                thread_bar.set_length((elapsed + remaining).as_millis() as u64);
+                thread_bar.set_position(elapsed.as_millis() as u64);
+                thread_bar.set_message(Countdown(remaining).to_string());
+                if remaining.is_zero() {
+                    thread_bar.finish();
+                    return;
+                }
+                thread::sleep(refresh.min(remaining));
+            }
+        });
+        Self {
+            bar,
+            stopped,
+            thread: Some(thread),
+        }
+    }
+    /// The bar driven by the timeout.
+    pub fn bar(&self) -> &ProgressBar {
+        &self.bar
+    }
+}
+
+impl Drop for TimeoutProgress {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
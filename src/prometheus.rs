@@ -0,0 +1,69 @@
+//! Export the statistics of a shared driver in the Prometheus text format
+use crate::driver::DriverStats;
+use std::fmt::Write;
+
+/// Encode the statistics of a driver in the Prometheus text exposition
+/// format, each metric name is prefixed with `prefix`. The output can be
+/// appended to the body of an existing `/metrics` endpoint. The timeouts
+/// reached per second are given by the `rate` of the `fired_total` counter.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::{driver::Driver, prometheus};
+///
+/// let driver = Driver::new(|_: u64, _: ()| {});
+/// driver.insert(1, Duration::from_secs(60), ());
+/// let body = prometheus::encode("sessions", &driver.stats());
+/// assert!(body.contains("sessions_active_timers 1\n"));
+/// ```
+pub fn encode(prefix: &str, stats: &DriverStats) -> String {
+    let mut body = String::new();
+    metric(
+        &mut body,
+        prefix,
+        "active_timers",
+        "gauge",
+        "Number of pending timeouts.",
+        stats.active,
+    );
+    metric(
+        &mut body,
+        prefix,
+        "fired_total",
+        "counter",
+        "Number of timeouts reached.",
+        stats.fired,
+    );
+    metric(
+        &mut body,
+        prefix,
+        "overshoot_seconds_total",
+        "counter",
+        "Sum of the delays between the deadlines and the handling of the timeouts.",
+        stats.overshoot.as_secs_f64(),
+    );
+    metric(
+        &mut body,
+        prefix,
+        "average_overshoot_seconds",
+        "gauge",
+        "Average delay between the deadline and the handling of a timeout.",
+        stats.average_overshoot().as_secs_f64(),
+    );
+    body
+}
+
+fn metric<V: std::fmt::Display>(
+    body: &mut String,
+    prefix: &str,
+    name: &str,
+    kind: &str,
+    help: &str,
+    value: V,
+) {
+    // writing in a string never fails
+    let _ = writeln!(body, "# HELP {}_{} {}", prefix, name, help);
+    let _ = writeln!(body, "# TYPE {}_{} {}", prefix, name, kind);
+    let _ = writeln!(body, "{}_{} {}", prefix, name, value);
+}
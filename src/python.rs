@@ -0,0 +1,75 @@
+//! Python bindings of the std thread implementation
+//!
+//! The `dyn_timeout` module exposes a `DynTimeout` class, the durations are
+//! given in seconds and the callback is any Python callable.
+//!
+//! ```python
+//! from dyn_timeout import DynTimeout
+//!
+//! timeout = DynTimeout(0.02, lambda: print("after forty milliseconds"))
+//! timeout.add(0.02)
+//! ```
+use crate::{
+    config::{DropBehavior, TimeoutConfig},
+    std_thread::DynTimeout,
+};
+use pyo3::{exceptions::PyRuntimeError, prelude::*};
+use std::time::Duration;
+
+/// Dynamic timeout executing a Python callable in a separated thread. The
+/// timeout isn't joined when the object is garbage collected, like a
+/// `threading.Timer` it's executed unless cancelled.
+#[pyclass(name = "DynTimeout")]
+pub struct PyDynTimeout {
+    timeout: DynTimeout,
+}
+
+#[pymethods]
+impl PyDynTimeout {
+    #[new]
+    fn new(seconds: f64, callback: PyObject) -> PyResult<Self> {
+        let config = TimeoutConfig::new(duration(seconds)?).drop_behavior(DropBehavior::Detach);
+        let timeout = DynTimeout::spawn(&config, true, move |_| {
+            Python::with_gil(|py| {
+                if let Err(err) = callback.call0(py) {
+                    err.print(py);
+                }
+            })
+        });
+        Ok(Self { timeout })
+    }
+    /// Increase the delay before the timeout.
+    fn add(&self, seconds: f64) -> PyResult<()> {
+        self.timeout.add(duration(seconds)?).map_err(runtime_error)
+    }
+    /// Decrease the delay before the timeout.
+    fn sub(&self, seconds: f64) -> PyResult<()> {
+        self.timeout.sub(duration(seconds)?).map_err(runtime_error)
+    }
+    /// Dismiss the callback and wait for the end of the thread.
+    fn cancel(&mut self, py: Python<'_>) -> PyResult<()> {
+        // the callback may be waiting for the GIL
+        py.allow_threads(|| self.timeout.cancel())
+            .map_err(runtime_error)
+    }
+    /// Time left before the timeout, in seconds.
+    fn remaining(&self) -> PyResult<f64> {
+        let remaining = self.timeout.remaining().map_err(runtime_error)?;
+        Ok(remaining.as_secs_f64())
+    }
+}
+
+fn duration(seconds: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds).map_err(|err| PyRuntimeError::new_err(err.to_string()))
+}
+
+fn runtime_error(err: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(err.to_string())
+}
+
+/// Python module, to build as an extension module with `maturin` and the
+/// `pyo3/extension-module` feature.
+#[pymodule]
+fn dyn_timeout(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyDynTimeout>()
+}
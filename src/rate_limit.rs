@@ -0,0 +1,135 @@
+//! Token bucket refilled by a dynamic timeout, built on the std thread
+//! implementation
+use crate::{
+    config::{DropBehavior, TimeoutConfig},
+    error::TimeoutError,
+    std_thread::DynTimeout,
+};
+use anyhow::Result;
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+struct Bucket {
+    tokens: u32,
+    capacity: u32,
+    interval: Duration,
+}
+
+/// State of the limiter, shared with the timeout thread.
+type State = Arc<(Mutex<Bucket>, Condvar)>;
+
+/// Token bucket refilled by one token each interval, up to its capacity.
+/// The refill interval is a dynamic timeout, so a service can tighten or
+/// loosen its rate limit at runtime with `set_interval` instead of creating
+/// a new limiter: the refill in progress is moved, the tokens are kept.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::rate_limit::RateLimiter;
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let limiter = RateLimiter::new(2, TWENTY);
+/// assert!(limiter.try_acquire());
+/// assert!(limiter.try_acquire());
+/// assert!(!limiter.try_acquire());
+/// // under load, one request every forty milliseconds
+/// limiter.set_interval(TWENTY * 2).unwrap();
+/// limiter.acquire();
+/// ```
+pub struct RateLimiter {
+    timeout: DynTimeout,
+    state: State,
+}
+
+impl RateLimiter {
+    /// Create a full bucket of `capacity` tokens, refilled by one token
+    /// each `interval`.
+    pub fn new(capacity: u32, interval: Duration) -> Self {
+        let state: State = Arc::new((
+            Mutex::new(Bucket {
+                tokens: capacity,
+                capacity,
+                interval,
+            }),
+            Condvar::new(),
+        ));
+        let thread_state = state.clone();
+        let config = TimeoutConfig::new(interval).drop_behavior(DropBehavior::Cancel);
+        let timeout = DynTimeout::spawn(&config, true, move |handle| {
+            let (bucket, condvar) = &*thread_state;
+            let mut bucket = lock(bucket);
+            bucket.tokens = bucket.capacity.min(bucket.tokens.saturating_add(1));
+            condvar.notify_one();
+            // fails only once the limiter is dropped
+            let _ = handle.rearm(bucket.interval);
+        });
+        Self { timeout, state }
+    }
+    /// Take a token if one is available.
+    ///
+    /// # Return
+    /// Return false if the bucket is empty.
+    pub fn try_acquire(&self) -> bool {
+        let mut bucket = lock(&self.state.0);
+        if bucket.tokens == 0 {
+            return false;
+        }
+        bucket.tokens -= 1;
+        true
+    }
+    /// Take a token, block until the next refill if the bucket is empty.
+    pub fn acquire(&self) {
+        let (bucket, condvar) = &*self.state;
+        let mut bucket = condvar
+            .wait_while(lock(bucket), |bucket| bucket.tokens == 0)
+            .unwrap_or_else(PoisonError::into_inner);
+        bucket.tokens -= 1;
+    }
+    /// Observer of the refill timeout, to check the refills stop with the
+    /// limiter.
+    #[cfg(test)]
+    pub(crate) fn observer(&self) -> crate::std_thread::Observer {
+        self.timeout.observer()
+    }
+    /// Number of tokens available.
+    pub fn available(&self) -> u32 {
+        lock(&self.state.0).tokens
+    }
+    /// Time between two refills.
+    pub fn interval(&self) -> Duration {
+        lock(&self.state.0).interval
+    }
+    /// Change the time between two refills. The refill in progress is moved
+    /// by the difference, it's immediate if the new interval already
+    /// elapsed.
+    ///
+    /// # Return
+    /// Return a result with an error if the refill in progress can't be
+    /// moved, see `DynTimeout::add`.
+    /// Otherwise it return an empty success.
+    pub fn set_interval(&self, interval: Duration) -> Result<()> {
+        // locked during the adjustment, so a refill rearms the timeout
+        // either before it or with the new interval
+        let mut bucket = lock(&self.state.0);
+        let previous = bucket.interval;
+        bucket.interval = interval;
+        let moved = if interval > previous {
+            self.timeout.add(interval - previous)
+        } else {
+            self.timeout.sub(previous - interval)
+        };
+        match moved {
+            // the refill is executing, it rearms with the new interval
+            Err(err) if matches!(err.downcast_ref(), Some(TimeoutError::Reached)) => Ok(()),
+            moved => moved,
+        }
+    }
+}
+
+fn lock(bucket: &Mutex<Bucket>) -> MutexGuard<'_, Bucket> {
+    bucket.lock().unwrap_or_else(PoisonError::into_inner)
+}
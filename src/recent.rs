@@ -0,0 +1,84 @@
+//! Process-wide log of the last timeouts fired or cancelled, to answer
+//! "what expired around 14:32?" without a tracing infrastructure
+use crate::event::Outcome;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, SystemTime},
+};
+
+lazy_static::lazy_static! {
+    /// Last events, the oldest first.
+    static ref EVENTS: Mutex<VecDeque<RecentEvent>> = Mutex::new(VecDeque::new());
+}
+/// Number of events kept, zero when the log is disabled.
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+/// End of a timeout cycle, see `recent_events`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentEvent {
+    /// Wall clock time of the event.
+    pub at: SystemTime,
+    /// Label of the timeout, see `TimeoutConfig::label`.
+    pub label: Option<String>,
+    pub outcome: Outcome,
+    /// Delay between the deadline and the moment it was reached, none if
+    /// the timeout was cancelled.
+    pub overshoot: Option<Duration>,
+}
+
+/// Keep the last `capacity` fire and cancel events of all the std and tokio
+/// timeouts of the process. The log is disabled by default, a capacity of
+/// zero disables it again. Reducing the capacity drops the oldest events.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::{config::TimeoutConfig, recent, std_thread::DynTimeout};
+///
+/// recent::record_recent_events(100);
+/// let config = TimeoutConfig::new(Duration::from_millis(20)).label("session");
+/// let mut dyn_timeout = DynTimeout::with_config(&config, || {});
+/// dyn_timeout.wait().unwrap();
+/// for event in dyn_timeout::recent_events() {
+///     println!("{:?} {:?} {:?}", event.at, event.label, event.outcome);
+/// }
+/// ```
+pub fn record_recent_events(capacity: usize) {
+    let mut events = EVENTS.lock().unwrap_or_else(PoisonError::into_inner);
+    CAPACITY.store(capacity, Ordering::Relaxed);
+    let excess = events.len().saturating_sub(capacity);
+    events.drain(..excess);
+}
+
+/// Last fire and cancel events, the oldest first. Empty unless enabled with
+/// `record_recent_events`.
+pub fn recent_events() -> Vec<RecentEvent> {
+    let events = EVENTS.lock().unwrap_or_else(PoisonError::into_inner);
+    events.iter().cloned().collect()
+}
+
+/// Log the end of a timeout cycle if the log is enabled.
+pub(crate) fn record(label: Option<&str>, outcome: Outcome, overshoot: Option<Duration>) {
+    if CAPACITY.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let mut events = EVENTS.lock().unwrap_or_else(PoisonError::into_inner);
+    // read again under the lock, the capacity may have been reduced
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+    if events.len() >= capacity {
+        events.pop_front();
+    }
+    events.push_back(RecentEvent {
+        at: SystemTime::now(),
+        label: label.map(str::to_owned),
+        outcome,
+        overshoot,
+    });
+}
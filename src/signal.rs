@@ -0,0 +1,124 @@
+//! Adjust a timeout when the process receives a Unix signal
+use crate::std_thread::SharedDynTimeout;
+use anyhow::Result;
+use libc::c_int;
+use signal_hook_registry::SigId;
+use std::{
+    io::ErrorKind,
+    os::unix::net::UnixDatagram,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+const SIGNALED: u8 = 0;
+const STOP: u8 = 1;
+
+/// What to do with the timeout when the signal arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalAction {
+    /// Increase the delay, see `DynTimeout::add`.
+    Extend(Duration),
+    /// Set the remaining time to the given duration.
+    Reset(Duration),
+}
+
+/// Adjust a timeout each time the process receives a signal, so an operator
+/// can push back a maintenance or shutdown deadline with `kill -USR1 <pid>`.
+/// The timeout is adjusted in a separated thread, not in the signal handler.
+/// Dropping the value restores the previous handling of the signal.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::{
+///     signal::{SignalAction, SignalExtension},
+///     std_thread::DynTimeout,
+/// };
+///
+/// let shutdown = DynTimeout::new(Duration::from_secs(60), || {
+///    println!("shutting down");
+/// })
+/// .into_shared();
+/// let extension = SignalExtension::new(
+///     libc::SIGUSR1,
+///     shutdown.clone(),
+///     SignalAction::Extend(Duration::from_secs(600)),
+/// )
+/// .unwrap();
+/// // ...
+/// drop(extension);
+/// shutdown.cancel().unwrap();
+/// ```
+pub struct SignalExtension {
+    id: SigId,
+    sender: UnixDatagram,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SignalExtension {
+    /// Apply `action` to the timeout each time `signal` arrives.
+    ///
+    /// # Return
+    /// Return a result with an error if the signal can't be handled, like
+    /// `SIGKILL`.
+    /// Otherwise it return the extension, active until it's dropped.
+    pub fn new(signal: c_int, timeout: SharedDynTimeout, action: SignalAction) -> Result<Self> {
+        let (sender, receiver) = UnixDatagram::pair()?;
+        let handler_sender = sender.try_clone()?;
+        // The handler never blocks, a signal is dropped while the queue is
+        // full of the previous ones.
+        handler_sender.set_nonblocking(true)?;
+        // Only a datagram is sent in the handler, which is async-signal-safe.
+        let id = unsafe {
+            signal_hook_registry::register(signal, move || {
+                let _ = handler_sender.send(&[SIGNALED]);
+            })?
+        };
+        let thread = thread::spawn(move || {
+            let mut message = [STOP];
+            while let Ok(1) = receiver.recv(&mut message) {
+                if message[0] != SIGNALED {
+                    return;
+                }
+                // the errors are dismissed, the timeout may be reached
+                let _ = apply(&timeout, action);
+            }
+        });
+        Ok(Self {
+            id,
+            sender,
+            thread: Some(thread),
+        })
+    }
+}
+
+fn apply(timeout: &SharedDynTimeout, action: SignalAction) -> Result<()> {
+    match action {
+        SignalAction::Extend(dur) => timeout.add(dur),
+        SignalAction::Reset(dur) => {
+            let remaining = timeout.remaining()?;
+            if dur >= remaining {
+                timeout.add(dur - remaining)
+            } else {
+                timeout.sub(remaining - dur)
+            }
+        }
+    }
+}
+
+impl Drop for SignalExtension {
+    fn drop(&mut self) {
+        signal_hook_registry::unregister(self.id);
+        // The sender shares the nonblocking socket of the handler, the queue
+        // is drained by the thread until there is room for the stop.
+        while let Err(err) = self.sender.send(&[STOP]) {
+            if err.kind() != ErrorKind::WouldBlock {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
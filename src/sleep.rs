@@ -0,0 +1,197 @@
+//! Low level sleep future with a dynamic deadline, no thread or task spawned
+use crate::deadline;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use tokio::time::{Instant, Sleep};
+
+struct State {
+    deadline: Instant,
+    waker: Option<Waker>,
+}
+
+/// Future resolved at a deadline which can be increased or decreased
+/// between polls, directly or through a `SleepHandle`. Unlike a
+/// `DynTimeout`, there is no callback, no channel and nothing spawned: it's
+/// a building block to embed a dynamic deadline in another future.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::sleep::DynSleep;
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let sleep = DynSleep::new(TWENTY);
+///    let handle = sleep.handle();
+///    tokio::spawn(async move { handle.add(TWENTY) });
+///    sleep.await;
+///    println!("after about forty milliseconds");
+/// });
+/// ```
+pub struct DynSleep {
+    sleep: Pin<Box<Sleep>>,
+    state: Arc<Mutex<State>>,
+}
+
+/// Handle to adjust the deadline of a `DynSleep` from anywhere, the handle
+/// can be cloned.
+#[derive(Clone)]
+pub struct SleepHandle {
+    state: Arc<Mutex<State>>,
+}
+
+impl DynSleep {
+    /// Create a sleep resolved after the given duration. Must be called in
+    /// the context of a tokio runtime.
+    pub fn new(dur: Duration) -> Self {
+        let deadline = deadline::from(Instant::now(), dur);
+        Self {
+            sleep: Box::pin(tokio::time::sleep_until(deadline)),
+            state: Arc::new(Mutex::new(State {
+                deadline,
+                waker: None,
+            })),
+        }
+    }
+    /// Create a handle to adjust the deadline while the sleep is polled
+    /// somewhere else.
+    pub fn handle(&self) -> SleepHandle {
+        SleepHandle {
+            state: self.state.clone(),
+        }
+    }
+    /// Increase the delay before the deadline.
+    pub fn add(&self, dur: Duration) {
+        self.handle().add(dur)
+    }
+    /// Decrease the delay before the deadline, at most to now.
+    pub fn sub(&self, dur: Duration) {
+        self.handle().sub(dur)
+    }
+    /// Move the deadline to `dur` from now.
+    pub fn reset(&self, dur: Duration) {
+        self.handle().reset(dur)
+    }
+    /// Time left before the deadline.
+    pub fn remaining(&self) -> Duration {
+        self.handle().remaining()
+    }
+}
+
+impl SleepHandle {
+    /// Increase the delay before the deadline.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::sleep::DynSleep;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let sleep = DynSleep::new(TWENTY);
+    ///    sleep.handle().add(TWENTY);
+    ///    assert!(sleep.remaining() > TWENTY);
+    /// });
+    /// ```
+    pub fn add(&self, dur: Duration) {
+        // the sleep is reset on the next poll, when the old deadline is
+        // reached at worst
+        let mut state = lock(&self.state);
+        state.deadline = deadline::from(state.deadline, dur);
+    }
+    /// Decrease the delay before the deadline, at most to now. The future is
+    /// woken up to wait the new deadline.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::sleep::DynSleep;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let sleep = DynSleep::new(Duration::from_secs(60));
+    ///    sleep.handle().sub(Duration::from_secs(60));
+    ///    sleep.await;
+    /// });
+    /// ```
+    pub fn sub(&self, dur: Duration) {
+        let mut state = lock(&self.state);
+        let now = Instant::now();
+        state.deadline = state
+            .deadline
+            .checked_sub(dur)
+            .map_or(now, |deadline| deadline.max(now));
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+    /// Move the deadline to `dur` from now, the future is woken up to wait
+    /// the new deadline.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::sleep::DynSleep;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let sleep = DynSleep::new(Duration::from_secs(60));
+    ///    sleep.handle().reset(TWENTY);
+    ///    sleep.await;
+    /// });
+    /// ```
+    pub fn reset(&self, dur: Duration) {
+        let mut state = lock(&self.state);
+        state.deadline = deadline::from(Instant::now(), dur);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+    /// Time left before the deadline.
+    pub fn remaining(&self) -> Duration {
+        lock(&self.state)
+            .deadline
+            .saturating_duration_since(Instant::now())
+    }
+}
+
+impl Future for DynSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            let deadline = {
+                let mut state = lock(&self.state);
+                state.waker = Some(cx.waker().clone());
+                state.deadline
+            };
+            if self.sleep.deadline() != deadline {
+                self.sleep.as_mut().reset(deadline);
+            }
+            if self.sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            // the deadline may have been increased in the meantime
+            if lock(&self.state).deadline <= Instant::now() {
+                return Poll::Ready(());
+            }
+        }
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
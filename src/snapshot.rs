@@ -0,0 +1,117 @@
+//! Point in time view of a timeout, for debug and admin endpoints
+use crate::config::{AddPolicy, DropBehavior, Precision, SubPolicy, TimeoutConfig};
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// State of a timeout when the snapshot was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TimeoutState {
+    /// Not started yet, see `DynTimeout::then`.
+    Pending,
+    /// Waiting for its deadline.
+    Running,
+    /// Reached, the callback is executed or already executed.
+    Reached,
+    /// Cancelled before it was reached.
+    Cancelled,
+}
+
+/// Live state of a timeout, see `DynTimeout::snapshot`. With the `serde`
+/// feature a list of snapshots can be dumped as JSON by a debug endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimeoutSnapshot {
+    /// Label of the configuration, see `TimeoutConfig::label`.
+    pub label: Option<String>,
+    pub state: TimeoutState,
+    /// Time left before the timeout, zero once reached or cancelled.
+    pub remaining: Duration,
+    /// Number of time the timeout was increased or re-armed.
+    pub extensions: usize,
+    /// Number of times the thread (or task) woke up, see
+    /// `DynTimeout::wakeups`.
+    pub wakeups: u64,
+    /// Wall clock time of the creation.
+    pub created_at: SystemTime,
+}
+
+/// Accuracy of a timeout, see `DynTimeout::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimeoutStats {
+    /// Contract of the configuration, see `TimeoutConfig::precision`.
+    pub precision: Precision,
+    /// Number of times the thread (or task) woke up.
+    pub wakeups: u64,
+    /// Delay between the deadline and the moment it was reached, none until
+    /// the first expiration.
+    pub lateness: Option<Duration>,
+    /// The last expiration met the precision contract, none until the first
+    /// expiration.
+    pub contract_met: Option<bool>,
+}
+
+impl TimeoutStats {
+    pub(crate) fn new(precision: Precision, wakeups: u64, lateness: Option<Duration>) -> Self {
+        Self {
+            precision,
+            wakeups,
+            lateness,
+            contract_met: lateness.map(|lateness| precision.is_met(lateness)),
+        }
+    }
+}
+
+/// Pending timeout exported by `DynTimeout::export`, to be re-armed by
+/// `DynTimeout::import` in another process, like a replacement instance
+/// during a rolling deploy. The callback can't be serialized, the payload id
+/// tells the importing process which one to give.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimeoutRecord {
+    /// Chosen by the exporting process, to find the callback on import.
+    pub payload_id: u64,
+    /// Time left at the export, `Duration::MAX` for an infinite timeout.
+    pub remaining: Duration,
+    /// Wall clock time of the export, the time elapsed until the import is
+    /// deduced from the remaining time.
+    pub exported_at: SystemTime,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub label: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_total: Option<Duration>,
+    /// See `DynTimeout::set_floor`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub floor: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub drop_behavior: DropBehavior,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sub_policy: SubPolicy,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub add_policy: AddPolicy,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub precision: Precision,
+}
+
+impl TimeoutRecord {
+    /// Configuration re-arming the timeout for the time left now, the
+    /// timeouts expired since the export are reached immediately.
+    pub fn config(&self) -> TimeoutConfig {
+        let remaining = match self.remaining {
+            Duration::MAX => Duration::MAX,
+            remaining => remaining.saturating_sub(self.exported_at.elapsed().unwrap_or_default()),
+        };
+        let mut config = TimeoutConfig::new(remaining)
+            .drop_behavior(self.drop_behavior)
+            .sub_policy(self.sub_policy)
+            .add_policy(self.add_policy)
+            .precision(self.precision);
+        config.max_total = self.max_total;
+        config.label = self.label.clone();
+        config
+    }
+}
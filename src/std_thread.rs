@@ -1,15 +1,118 @@
-///! Implementation of the dynamic timeout with the std thread library
+//! Implementation of the dynamic timeout with the std thread library
+use crate::{
+    command::Adjust,
+    config::{
+        AddPolicy, AssignPolicy, CallbackExecution, DropBehavior, PanicPolicy, Precision,
+        SubPolicy, TimeoutConfig,
+    },
+    countdown::Countdown,
+    deadline::{scale, share, Claim, Deadline, KeepAliveState, Stop, Wait},
+    error::TimeoutError,
+    event::{Expiration, Outcome},
+    history::{Adjustment, AdjustmentKind, History},
+    hook::ErrorHook,
+    pool, recent,
+    snapshot::{TimeoutRecord, TimeoutSnapshot, TimeoutState, TimeoutStats},
+    thread_cache::{self, Worker},
+    wake::{self, CondvarWake, Waker},
+};
 use anyhow::{bail, Result};
 use std::{
+    cmp::Ordering as CmpOrdering,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    ops::{AddAssign, Deref, SubAssign},
+    panic::Location,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        mpsc, Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Condvar, Mutex, MutexGuard, PoisonError,
     },
-    thread::{self, JoinHandle},
-    time::Duration,
+    thread::{self, JoinHandle, ThreadId},
+    time::{Duration, Instant, SystemTime},
 };
+use tokio::sync::watch;
+#[cfg(feature = "chrono")]
+use {
+    crate::deadline::until,
+    chrono::{DateTime, Utc},
+};
+
+/// State shared by a timeout, its thread and its guards, in a single
+/// allocation.
+struct Shared {
+    deadline: Mutex<Deadline>,
+    /// Claimed by either the callback or a cancellation.
+    claim: Claim,
+    /// No more re-arm, the thread ends after the cycle in progress. Set on
+    /// drop or by `stop_after_current`.
+    stopped: Stop,
+    /// Timeouts started when this one is reached, none once the thread ended.
+    followers: Mutex<Option<Vec<Linked>>>,
+    /// Timeouts cancelled when this one ends, none once the thread ended.
+    children: Mutex<Option<Vec<Linked>>>,
+    keep_alive: KeepAliveState,
+    /// Number of times the thread (or task) woke up, see `wakeups`.
+    wakeups: AtomicU64,
+    /// Read when the timeout is reached, so it can be wrapped meanwhile.
+    callback: Mutex<Callback>,
+    panic_policy: PanicPolicy,
+    /// Message of the last panic caught in a callback.
+    panicked: Mutex<Option<String>>,
+    /// Thread executing the callback inline, see `check_reentrant`.
+    callback_thread: Mutex<Option<ThreadId>>,
+    history: History,
+    /// Set once the thread ended, see `wait_ended`.
+    ended: Mutex<bool>,
+    ended_condvar: Condvar,
+}
+impl Shared {
+    /// Execute a callback in the timeout thread.
+    fn inline(&self, callback: impl FnOnce()) {
+        *self.callback_thread.lock().unwrap() = Some(thread::current().id());
+        callback();
+        *self.callback_thread.lock().unwrap() = None;
+    }
+    /// Reject a call into the timeout from its own callback, which would
+    /// wait for the end of the callback (or adjust a deadline the thread
+    /// isn't waiting for anymore). The callback re-arms the timeout with
+    /// its `TimeoutHandle` instead.
+    fn check_reentrant(&self) -> Result<()> {
+        let callback_thread = *self
+            .callback_thread
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if callback_thread == Some(thread::current().id()) {
+            bail!(TimeoutError::ReentrantAdjust)
+        }
+        Ok(())
+    }
+    /// Block until the end of the thread, without its handle.
+    fn wait_ended(&self) {
+        let ended = self.ended.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ended = self
+            .ended_condvar
+            .wait_while(ended, |ended| !*ended)
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+/// Mark the thread as ended when it's dropped, even if the callback
+/// panicked.
+struct Ended(Arc<Shared>);
+
+impl Drop for Ended {
+    fn drop(&mut self) {
+        *self.0.ended.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.0.ended_condvar.notify_all();
+    }
+}
 
-type DurationVec = Arc<Mutex<Vec<Duration>>>;
+/// Another timeout and the channel to wake up its thread.
+type Linked = (Arc<Shared>, Waker);
+/// Callback of a timeout, see `DynTimeout::wrap_callback`.
+pub type Callback = Arc<dyn Fn(&TimeoutHandle) + Send + Sync>;
+/// Callback of a timeout taken over by another backend, see `hand_over`.
+pub(crate) type HandedOver = Arc<dyn Fn() + Send + Sync>;
 
 /// Dynamic timeout, standard implementation with std::thread. Automaticcaly
 /// join on drop.
@@ -26,10 +129,17 @@ type DurationVec = Arc<Mutex<Vec<Duration>>>;
 /// dyn_timeout.add(TWENTY).unwrap();
 /// ```
 pub struct DynTimeout {
-    thread: Option<JoinHandle<()>>,
-    cancelled: Arc<AtomicBool>,
-    sender: mpsc::Sender<()>,
-    durations: DurationVec,
+    thread: Option<Worker>,
+    shared: Arc<Shared>,
+    sender: Waker,
+    max_waiting_time: Option<Duration>,
+    floor: Option<Duration>,
+    drop_behavior: DropBehavior,
+    sub_policy: SubPolicy,
+    add_policy: AddPolicy,
+    assign_policy: AssignPolicy,
+    label: Option<String>,
+    created_at: SystemTime,
 }
 
 impl DynTimeout {
@@ -51,30 +161,417 @@ impl DynTimeout {
     /// dyn_timeout.add(TWENTY).unwrap();
     /// ```
     pub fn new(dur: Duration, callback: fn() -> ()) -> Self {
-        let durations: DurationVec = Arc::new(Mutex::new(vec![Duration::ZERO, dur]));
-        let thread_vec = durations.clone();
-        let cancelled = Arc::new(AtomicBool::new(false));
-        let thread_cancelled = cancelled.clone();
-        let (sender, receiver) = mpsc::channel::<()>();
-        Self {
-            thread: Some(thread::spawn(move || {
-                while let Some(dur) = thread_vec.lock().unwrap().pop() {
-                    let _ = receiver.recv_timeout(dur);
+        Self::spawn(&TimeoutConfig::new(dur), true, move |_| callback())
+    }
+    /// Create a new dynamic timeout with the policies of the given
+    /// configuration.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::TimeoutConfig, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).max_total(TWENTY);
+    /// let dyn_timeout = DynTimeout::with_config(&config, || {
+    ///    println!("after twenty milliseconds");
+    /// });
+    /// // dismissed, the max waiting time is already reached
+    /// dyn_timeout.add(TWENTY).unwrap();
+    /// ```
+    pub fn with_config(config: &TimeoutConfig, callback: fn() -> ()) -> Self {
+        Self::spawn(config, true, move |_| callback())
+    }
+    /// Create a new dynamic timeout whose callback receives a handle to the
+    /// timeout, so it can start it again and run periodically with a delay
+    /// chosen at each execution. The callback is executed without any lock
+    /// held, re-arming the timeout with the handle never deadlocks. The
+    /// methods of the timeout itself return `TimeoutError::ReentrantAdjust`
+    /// when called from the callback.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::{DropBehavior, TimeoutConfig}, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).drop_behavior(DropBehavior::Cancel);
+    /// let heartbeat = DynTimeout::with_handle(&config, |handle| {
+    ///    println!("heartbeat");
+    ///    handle.rearm(TWENTY).unwrap();
+    /// });
+    /// ```
+    pub fn with_handle(config: &TimeoutConfig, callback: fn(&TimeoutHandle)) -> Self {
+        Self::spawn(config, true, callback)
+    }
+    /// Create a new dynamic timeout with a fallible callback. If the callback
+    /// returns an error, it is given to the `hook` in the separated thread.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{hook::ErrorHook, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new_fallible(
+    ///     TWENTY,
+    ///     || Err("after forty milliseconds"),
+    ///     ErrorHook::Log,
+    /// );
+    /// dyn_timeout.add(TWENTY).unwrap();
+    /// ```
+    pub fn new_fallible<E>(
+        dur: Duration,
+        callback: fn() -> Result<(), E>,
+        hook: ErrorHook<E>,
+    ) -> Self
+    where
+        E: Debug + Send + 'static,
+    {
+        Self::spawn(&TimeoutConfig::new(dur), true, move |_| {
+            if let Err(err) = callback() {
+                hook.handle(err)
+            }
+        })
+    }
+    /// Create a new dynamic timeout controlled only through the returned
+    /// sender, which can be cloned and given to other threads. The timeout is
+    /// owned by a separated thread which applies the commands, it's dropped
+    /// when all the senders are dropped or after an `Adjust::Cancel`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{command::Adjust, config::TimeoutConfig, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let commands = DynTimeout::actor(&TimeoutConfig::new(TWENTY), || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// commands.send(Adjust::Add(TWENTY)).unwrap();
+    /// ```
+    pub fn actor(config: &TimeoutConfig, callback: fn() -> ()) -> mpsc::Sender<Adjust> {
+        let mut dyn_timeout = Self::with_config(config, callback);
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for command in receiver {
+                // the errors are dismissed, there is nobody to report them
+                match command {
+                    Adjust::Add(dur) => {
+                        let _ = dyn_timeout.add(dur);
+                    }
+                    Adjust::Sub(dur) => {
+                        let _ = dyn_timeout.sub(dur);
+                    }
+                    Adjust::Cancel => {
+                        let _ = dyn_timeout.cancel();
+                        break;
+                    }
+                }
+            }
+        });
+        sender
+    }
+    /// Create a new dynamic timeout from a human readable duration such as
+    /// `"1m30s"` or `"250ms"`.
+    ///
+    /// # Return
+    /// Return a result with an error if the duration can't be parsed.
+    ///
+    /// # Example
+    /// ```
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// let dyn_timeout = DynTimeout::new_human("20ms", || {
+    ///    println!("after forty milliseconds");
+    /// }).unwrap();
+    /// dyn_timeout.add_human("20ms").unwrap();
+    /// ```
+    #[cfg(feature = "humantime")]
+    pub fn new_human(dur: &str, callback: fn() -> ()) -> Result<Self> {
+        Ok(Self::new(humantime::parse_duration(dur)?, callback))
+    }
+    /// Create a new dynamic timeout reached at the given date. The date is
+    /// converted into a delay from now, if it's already passed the callback
+    /// is executed immediately.
+    ///
+    /// # Example
+    /// ```
+    /// use chrono::{Duration, Utc};
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// let dyn_timeout = DynTimeout::at(Utc::now() + Duration::milliseconds(20), || {
+    ///    println!("after twenty milliseconds");
+    /// });
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn at(deadline: DateTime<Utc>, callback: fn() -> ()) -> Self {
+        Self::new(until(deadline), callback)
+    }
+    pub(crate) fn spawn<F>(config: &TimeoutConfig, started: bool, callback: F) -> Self
+    where
+        F: Fn(&TimeoutHandle) + Send + Sync + 'static,
+    {
+        let shared = Arc::new(Shared {
+            deadline: Mutex::new(
+                Deadline::new(config.initial_duration(), started).with_precision(config.precision),
+            ),
+            claim: Claim::new(config.memory_ordering),
+            stopped: Stop::new(config.memory_ordering),
+            followers: Mutex::new(Some(vec![])),
+            children: Mutex::new(Some(vec![])),
+            keep_alive: KeepAliveState::default(),
+            wakeups: AtomicU64::new(0),
+            callback: Mutex::new(Arc::new(callback)),
+            panic_policy: config.panic_policy,
+            panicked: Mutex::new(None),
+            callback_thread: Mutex::new(None),
+            history: History::new(config.record_history),
+            ended: Mutex::new(false),
+            ended_condvar: Condvar::new(),
+        });
+        let thread_shared = shared.clone();
+        let handle = TimeoutHandle {
+            shared: shared.clone(),
+            detached: false,
+        };
+        let rearm = config.add_policy == AddPolicy::Rearm;
+        let execution = config.callback_execution;
+        let stages = config.stages.clone();
+        let strategy = match config.wake_strategy {
+            Some(strategy) => strategy(),
+            None => Arc::new(CondvarWake::default()),
+        };
+        let (sender, receiver) = wake::channel(strategy);
+        let label = config.label.clone();
+        let run = move || {
+            let _ended = Ended(thread_shared.clone());
+            loop {
+                let mut next_stages = stages.iter();
+                let reached = loop {
+                    while !thread_shared.claim.is_cancelled() {
+                        let wait = thread_shared
+                            .deadline
+                            .lock()
+                            .unwrap()
+                            .next_wait(&thread_shared.keep_alive);
+                        match wait {
+                            Wait::For(dur) => {
+                                // nobody can wake up a detached timeout
+                                if receiver.wait_timeout(dur).is_err() {
+                                    thread::sleep(dur);
+                                }
+                                thread_shared.wakeups.fetch_add(1, Ordering::Relaxed);
+                            }
+                            // wait for the start, the last keep-alive guard or a
+                            // cancellation, nobody can ever set a deadline on a
+                            // detached timeout without one
+                            Wait::Signal => {
+                                if receiver.wait().is_err() {
+                                    return;
+                                }
+                                thread_shared.wakeups.fetch_add(1, Ordering::Relaxed);
+                            }
+                            // the deadline is too close to sleep
+                            Wait::Spin(precision) => precision.spin(),
+                            Wait::Reached => break,
+                        }
+                    }
+                    if thread_shared.claim.is_cancelled() {
+                        break false;
+                    }
+                    match next_stages.next() {
+                        // execute the intermediate callback and wait the next
+                        // stage
+                        Some(&(stage_callback, dur)) => {
+                            let shared = thread_shared.clone();
+                            let job =
+                                move || shared.panic_policy.call(&shared.panicked, stage_callback);
+                            match execution {
+                                CallbackExecution::TimerThread => thread_shared.inline(job),
+                                CallbackExecution::SharedPool => pool::execute(Box::new(job)),
+                                CallbackExecution::Spawned => {
+                                    thread::spawn(job);
+                                }
+                            }
+                            thread_shared.deadline.lock().unwrap().rearm(dur);
+                        }
+                        // the cancellation may have claimed the timeout in the
+                        // meantime
+                        None => break thread_shared.claim.fire(),
+                    }
+                };
+                let overshoot = match reached {
+                    true => thread_shared.deadline.lock().unwrap().expiration(),
+                    false => None,
+                };
+                if reached {
+                    let callback = thread_shared.callback.lock().unwrap().clone();
+                    if execution == CallbackExecution::TimerThread {
+                        let policy = thread_shared.panic_policy;
+                        thread_shared
+                            .inline(|| policy.call(&thread_shared.panicked, || callback(&handle)));
+                    } else {
+                        let handle = TimeoutHandle {
+                            shared: handle.shared.clone(),
+                            detached: true,
+                        };
+                        let job = move || {
+                            let shared = &handle.shared;
+                            shared
+                                .panic_policy
+                                .call(&shared.panicked, || callback(&handle))
+                        };
+                        if execution == CallbackExecution::SharedPool {
+                            pool::execute(Box::new(job));
+                        } else {
+                            thread::spawn(job);
+                        }
+                    }
+                }
+                let outcome = match reached {
+                    true if thread_shared.panicked.lock().unwrap().is_some() => Outcome::Panicked,
+                    true => Outcome::Fired,
+                    false => Outcome::Cancelled,
+                };
+                let overshoot = overshoot.map(|expiration| expiration.lateness());
+                recent::record(label.as_deref(), outcome, overshoot);
+                let children = thread_shared.children.lock().unwrap().take();
+                for (child, sender) in children.unwrap_or_default() {
+                    child.claim.cancel();
+                    child.deadline.lock().unwrap().clear();
+                    let _ = sender.wake();
                 }
-                if !thread_cancelled.load(Ordering::Relaxed) {
-                    callback();
+                let followers = thread_shared.followers.lock().unwrap().take();
+                for (next, sender) in followers.unwrap_or_default() {
+                    if reached {
+                        next.deadline.lock().unwrap().start();
+                    } else {
+                        next.claim.cancel();
+                    }
+                    let _ = sender.wake();
                 }
-            })),
-            cancelled,
+                if !reached {
+                    break;
+                }
+                if !rearm {
+                    // the callback may have started the timeout again with
+                    // its handle
+                    if thread_shared.deadline.lock().unwrap().is_reached() {
+                        break;
+                    }
+                }
+                // wait for a re-arm, a cancellation or the drop
+                while thread_shared.deadline.lock().unwrap().is_reached() {
+                    if thread_shared.claim.is_cancelled()
+                        || thread_shared.stopped.is_stopped()
+                        || receiver.wait().is_err()
+                    {
+                        return;
+                    }
+                }
+                thread_shared.claim.rearm();
+            }
+        };
+        let thread = if config.reuse_thread {
+            thread_cache::execute(Box::new(run))
+        } else {
+            Worker::Spawned(thread::spawn(run))
+        };
+        Self {
+            thread: Some(thread),
+            shared,
             sender,
-            durations,
+            max_waiting_time: config.max_total,
+            floor: None,
+            drop_behavior: config.drop_behavior,
+            sub_policy: config.sub_policy,
+            add_policy: config.add_policy,
+            assign_policy: config.assign_policy,
+            label: config.label.clone(),
+            created_at: SystemTime::now(),
         }
     }
-    /// Increase the delay before the timeout.
+    /// Set a muximum time we can wait, dismiss the `add` call if overflow.
+    pub fn set_max_waiting_time(&mut self, duration: Duration) {
+        self.max_waiting_time = Some(duration)
+    }
+    /// Set a minimum remaining time: the `sub` calls (and
+    /// `shrink_by_fraction`) can't bring the timeout closer than `floor`
+    /// from now, a larger decrease is reduced to stop at the floor. It
+    /// protects an operation of a minimum processing time whatever the other
+    /// components remove. The timeout is reached normally once the floor
+    /// itself has elapsed.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut dyn_timeout = DynTimeout::new(TWENTY * 5, || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// dyn_timeout.set_floor(TWENTY * 2);
+    /// dyn_timeout.sub(TWENTY * 4).unwrap();
+    /// assert!(dyn_timeout.remaining().unwrap() > TWENTY);
+    /// ```
+    pub fn set_floor(&mut self, floor: Duration) {
+        self.floor = Some(floor)
+    }
+    /// Wrap the callback with a decorator receiving the current one, to add
+    /// a behavior around it (timing, logging, retry...) without replacing
+    /// it. The decorators can be stacked, the last one is the outermost. A
+    /// callback already executing is unchanged, the wrapped one applies
+    /// from the next expiry.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use dyn_timeout::std_thread::{DynTimeout, TimeoutHandle};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after twenty milliseconds");
+    /// });
+    /// dyn_timeout.wrap_callback(|inner| move |handle: &TimeoutHandle| {
+    ///    let start = Instant::now();
+    ///    inner(handle);
+    ///    println!("callback executed in {:?}", start.elapsed());
+    /// });
+    /// ```
+    pub fn wrap_callback<W, F>(&self, wrapper: W)
+    where
+        W: FnOnce(Callback) -> F,
+        F: Fn(&TimeoutHandle) + Send + Sync + 'static,
+    {
+        let mut callback = self
+            .shared
+            .callback
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *callback = Arc::new(wrapper(callback.clone()));
+    }
+    /// Increase the delay before the timeout. If the timeout already appened
+    /// and the configuration uses `AddPolicy::Rearm`, the timeout is started
+    /// again with the given duration.
+    ///
+    /// The thread decides to execute the callback with the deadline locked,
+    /// so a successful `add` guarantees the callback isn't executed before
+    /// the new deadline, even if the thread was about to wake up.
     ///
     /// # Return
-    /// Return a result with an error if the timeout already appened or it failed
-    /// to increase the delay for any other reason.
+    /// Return a result with `TimeoutError::Reached` if the timeout already
+    /// appened (unless it's re-armed), the `add` lost the race against the
+    /// expiry. `TimeoutError::InvalidDuration` if the new deadline overflows,
+    /// `TimeoutError::ReentrantAdjust` if it's called from the callback, or
+    /// an error if it failed to increase the delay for any other reason.
     /// Otherwise it return an empty success.
     ///
     /// # Example
@@ -88,22 +585,186 @@ impl DynTimeout {
     /// });
     /// dyn_timeout.add(TWENTY).unwrap();
     /// ```
+    #[track_caller]
     pub fn add(&self, dur: Duration) -> Result<()> {
-        match self.durations.lock() {
-            Ok(mut durations) => {
-                if durations.is_empty() {
-                    bail!("Timeout already reached")
+        self.shared.check_reentrant()?;
+        match self.shared.deadline.lock() {
+            Ok(mut deadline) => {
+                if deadline.is_reached() {
+                    if self.add_policy == AddPolicy::Rearm
+                        && !self.shared.claim.is_cancelled()
+                        && !self.shared.stopped.is_stopped()
+                        && deadline.rearm(dur)
+                    {
+                        self.shared
+                            .history
+                            .record(AdjustmentKind::Rearm(dur), Some(Location::caller()));
+                        self.sender.wake()?;
+                        return Ok(());
+                    }
+                    bail!(TimeoutError::Reached)
                 }
-                durations.push(dur);
-                Ok(())
+                self.extend(&mut deadline, dur)
             }
             Err(err) => bail!(err.to_string()),
         }
     }
-    /// Try to decrease the delay before the timeout. (bad precision, work in progress)
+    #[track_caller]
+    fn extend(&self, deadline: &mut Deadline, dur: Duration) -> Result<()> {
+        // dismissed once the max waiting time is reached
+        if matches!(self.max_waiting_time, Some(m) if deadline.remaining() >= m) {
+            return Ok(());
+        }
+        if !deadline.add(dur) {
+            bail!(TimeoutError::InvalidDuration)
+        }
+        self.shared
+            .history
+            .record(AdjustmentKind::Add(dur), Some(Location::caller()));
+        Ok(())
+    }
+    /// Increase the delay before the timeout by `ms` milliseconds, see
+    /// `add`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// let dyn_timeout = DynTimeout::new(Duration::from_millis(20), || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// dyn_timeout.add_millis(20).unwrap();
+    /// ```
+    #[track_caller]
+    pub fn add_millis(&self, ms: u64) -> Result<()> {
+        self.add(Duration::from_millis(ms))
+    }
+    /// Increase the delay before the timeout by `secs` seconds, see `add`.
+    #[track_caller]
+    pub fn add_secs(&self, secs: u64) -> Result<()> {
+        self.add(Duration::from_secs(secs))
+    }
+    /// Increase the delay before the timeout like `add`, but a deadline
+    /// which can't be represented is pushed to `Duration::MAX` instead of
+    /// returning `TimeoutError::InvalidDuration`: the timeout is then never
+    /// reached until a delay is `set`, like a timeout armed with
+    /// `Duration::MAX`.
+    ///
+    /// # Return
+    /// Return a result with an error for the same reasons as `add`, except
+    /// an overflow.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// let dyn_timeout = DynTimeout::new(Duration::from_millis(20), || {
+    ///    println!("twenty milliseconds after the delay is set");
+    /// });
+    /// dyn_timeout.saturating_add(Duration::MAX).unwrap();
+    /// assert_eq!(dyn_timeout.remaining().unwrap(), Duration::MAX);
+    /// dyn_timeout.set(Duration::from_millis(20)).unwrap();
+    /// ```
+    #[track_caller]
+    pub fn saturating_add(&self, dur: Duration) -> Result<()> {
+        match self.add(dur) {
+            Err(err) if err.downcast_ref() == Some(&TimeoutError::InvalidDuration) => {
+                let mut deadline = match self.shared.deadline.lock() {
+                    Ok(deadline) => deadline,
+                    Err(err) => bail!(err.to_string()),
+                };
+                // reached in the meantime
+                if deadline.is_reached() {
+                    bail!(TimeoutError::Reached)
+                }
+                deadline.saturate();
+                self.shared
+                    .history
+                    .record(AdjustmentKind::Add(dur), Some(Location::caller()));
+                Ok(())
+            }
+            result => result,
+        }
+    }
+    /// Increase the delay before the timeout with a human readable duration
+    /// such as `"1m30s"` or `"250ms"`.
+    ///
+    /// # Return
+    /// Return a result with an error if the duration can't be parsed, or for
+    /// the same reasons as `add`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// dyn_timeout.add_human("20ms").unwrap();
+    /// ```
+    #[cfg(feature = "humantime")]
+    #[track_caller]
+    pub fn add_human(&self, dur: &str) -> Result<()> {
+        self.add(humantime::parse_duration(dur)?)
+    }
+    /// Increase the delay so the timeout isn't reached before the given date.
+    /// Do nothing if the timeout is already expected after this date.
     ///
     /// # Return
     /// Return a result with an error if the timeout already appened or it failed
+    /// to increase the delay for any other reason.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// dyn_timeout
+    ///     .extend_until(chrono::Utc::now() + chrono::Duration::milliseconds(40))
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "chrono")]
+    #[track_caller]
+    pub fn extend_until(&self, date: DateTime<Utc>) -> Result<()> {
+        match self.shared.deadline.lock() {
+            Ok(mut deadline) => {
+                if deadline.is_reached() {
+                    bail!(TimeoutError::Reached)
+                }
+                let until = until(date);
+                let remaining = deadline.remaining();
+                if until > remaining {
+                    self.extend(&mut deadline, until - remaining)?;
+                }
+                Ok(())
+            }
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Decrease the delay before the timeout. If more than the remaining time
+    /// is removed, the timeout is reached immediately, an error is returned
+    /// with `SubPolicy::Error` or the remaining time is clamped with
+    /// `SubPolicy::Clamp`.
+    ///
+    /// Concurrent `add` and `sub` calls are applied one at a time, and until
+    /// the timeout is reached the deadline moves by the sum of their
+    /// durations whatever their order: the time removed beyond the remaining
+    /// time isn't forgotten, an `add` applied before the thread notices the
+    /// timeout is reached pays it back first.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened, if the
+    /// duration exceeds the remaining time with `SubPolicy::Error` or it failed
     /// to decrease the delay for any other reason.
     /// Otherwise it return an empty success.
     ///
@@ -122,32 +783,64 @@ impl DynTimeout {
     /// dyn_timeout.add(TWENTY).unwrap();
     /// dyn_timeout.sub(TEN).unwrap();
     /// ```
+    #[track_caller]
     pub fn sub(&self, dur: Duration) -> Result<()> {
-        let mut durations = match self.durations.lock() {
-            Ok(durations) => {
-                if durations.is_empty() {
-                    bail!("Timeout already reached")
+        self.shared.check_reentrant()?;
+        let mut deadline = match self.shared.deadline.lock() {
+            Ok(deadline) => {
+                if deadline.is_reached() {
+                    bail!(TimeoutError::Reached)
                 } else {
-                    durations
+                    deadline
                 }
             }
             Err(err) => bail!(err.to_string()),
         };
-        let mut pop_dur = Duration::default();
-        while pop_dur < dur && durations.len() > 1 {
-            pop_dur += durations.pop().unwrap();
-        }
-        if pop_dur > dur {
-            durations.push(pop_dur - dur);
+        self.shorten(&mut deadline, dur, false)
+    }
+    /// Decrease the delay before the timeout by at most the remaining time:
+    /// the timeout is reached immediately if `dur` exceeds it, without error
+    /// even with `SubPolicy::Error`, and the excess isn't deducted from a
+    /// later `add` as with `sub`. The `SubPolicy::Clamp` minimum and the
+    /// floor still apply.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::{SubPolicy, TimeoutConfig}, std_thread::DynTimeout};
+    ///
+    /// let config = TimeoutConfig::new(Duration::from_millis(20)).sub_policy(SubPolicy::Error);
+    /// let mut dyn_timeout = DynTimeout::with_config(&config, || {
+    ///    println!("right now");
+    /// });
+    /// assert!(dyn_timeout.sub(Duration::from_secs(1)).is_err());
+    /// dyn_timeout.saturating_sub(Duration::from_secs(1)).unwrap();
+    /// dyn_timeout.wait().unwrap();
+    /// ```
+    #[track_caller]
+    pub fn saturating_sub(&self, dur: Duration) -> Result<()> {
+        self.shared.check_reentrant()?;
+        let mut deadline = match self.shared.deadline.lock() {
+            Ok(deadline) => deadline,
+            Err(err) => bail!(err.to_string()),
+        };
+        if deadline.is_reached() {
+            bail!(TimeoutError::Reached)
         }
-        Ok(())
+        self.shorten(&mut deadline, dur, true)
     }
-    /// Dismiss the timeout callback and cancel all delays added.
-    /// Stop immediatelly all waiting process and join the created thread.
+    /// Replace the delay before the timeout, the timeout is reached `dur`
+    /// from now whatever the time left. A timeout armed with `Duration::MAX`
+    /// is never reached until a delay is set, so it can be created eagerly
+    /// and activated later. On such a timeout, `sub` counts from
+    /// `Duration::MAX` and `add` overflows.
     ///
     /// # Return
-    /// Return a result with an error if the timeout if the program failed to
-    /// clear the delays.
+    /// Return a result with an error if the timeout already appened.
     /// Otherwise it return an empty success.
     ///
     /// # Example
@@ -156,47 +849,1376 @@ impl DynTimeout {
     /// use dyn_timeout::std_thread::DynTimeout;
     ///
     /// const TWENTY: Duration = Duration::from_millis(20);
-    /// const TEN: Duration = Duration::from_millis(10);
     ///
-    /// let mut dyn_timeout = DynTimeout::new(TWENTY, || {
-    ///    println!("never append");
+    /// let dyn_timeout = DynTimeout::new(Duration::MAX, || {
+    ///    println!("twenty milliseconds after the activation");
     /// });
-    /// dyn_timeout.add(TEN).unwrap();
-    /// // cancel the last ten milliseconds and dismiss the callback
-    /// dyn_timeout.cancel().unwrap();
+    /// assert_eq!(dyn_timeout.remaining().unwrap(), Duration::MAX);
+    /// dyn_timeout.set(TWENTY).unwrap();
+    /// assert!(dyn_timeout.remaining().unwrap() <= TWENTY);
     /// ```
-    pub fn cancel(&mut self) -> Result<()> {
-        match self.durations.lock() {
-            Ok(mut durations) => {
-                self.cancelled.store(true, Ordering::Release);
-                durations.clear();
-                self.sender.send(())?;
+    #[track_caller]
+    pub fn set(&self, dur: Duration) -> Result<()> {
+        self.shared.check_reentrant()?;
+        match self.shared.deadline.lock() {
+            Ok(mut deadline) => {
+                if deadline.is_reached() {
+                    bail!(TimeoutError::Reached)
+                }
+                self.shared
+                    .history
+                    .record(AdjustmentKind::Set(dur), Some(Location::caller()));
+                if deadline.set(dur) {
+                    // wake up the thread to wait the new deadline
+                    let _ = self.sender.wake();
+                }
+                Ok(())
             }
             Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Remove `dur` from the deadline, at most the remaining time if
+    /// `saturating`.
+    #[track_caller]
+    fn shorten(&self, deadline: &mut Deadline, dur: Duration, saturating: bool) -> Result<()> {
+        let remaining = deadline.remaining();
+        let dur = match saturating {
+            true => dur.min(remaining),
+            false => dur,
         };
-        self.join()?;
-        self.thread = None;
+        let mut removed = self.sub_policy.apply(dur, remaining)?;
+        if let Some(floor) = self.floor {
+            // nothing removed if the remaining time is already under it
+            removed = removed.min(remaining.saturating_sub(floor));
+        }
+        self.shared
+            .history
+            .record(AdjustmentKind::Sub(dur), Some(Location::caller()));
+        if deadline.sub(removed) {
+            // wake up the thread to wait the new deadline
+            let _ = self.sender.wake();
+        }
         Ok(())
     }
-    fn join(&mut self) -> Result<()> {
-        if self.thread.is_none() {
-            return Ok(());
+    /// Increase the remaining time by a fraction of itself, `0.5` gives 50%
+    /// more time than what remains.
+    ///
+    /// # Return
+    /// Return a result with an error if the fraction is negative, if the
+    /// timeout already appened or it failed to increase the delay for any
+    /// other reason.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after about thirty milliseconds");
+    /// });
+    /// dyn_timeout.extend_by_fraction(0.5).unwrap();
+    /// ```
+    #[track_caller]
+    pub fn extend_by_fraction(&self, fraction: f64) -> Result<()> {
+        if !fraction.is_finite() || fraction < 0.0 {
+            bail!("Invalid fraction {}", fraction)
         }
-        match self.thread.take() {
-            Some(thread) => match thread.join() {
-                Ok(_) => {
-                    self.thread = None;
-                    Ok(())
+        match self.shared.deadline.lock() {
+            Ok(mut deadline) => {
+                if deadline.is_reached() {
+                    bail!(TimeoutError::Reached)
                 }
-                Err(_) => bail!("Cannot join dyn-timeout"),
-            },
-            None => bail!("Cannot take thread"),
+                let dur = scale(deadline.remaining(), fraction)?;
+                self.extend(&mut deadline, dur)
+            }
+            Err(err) => bail!(err.to_string()),
         }
     }
-}
-
-impl Drop for DynTimeout {
-    fn drop(&mut self) {
-        self.join().unwrap()
+    /// Decrease the remaining time by a fraction of itself, `0.25` removes a
+    /// quarter of what remains.
+    ///
+    /// # Return
+    /// Return a result with an error if the fraction isn't between 0 and 1,
+    /// if the timeout already appened or it failed to decrease the delay for
+    /// any other reason.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after some milliseconds");
+    /// });
+    /// dyn_timeout.add(TWENTY).unwrap();
+    /// dyn_timeout.shrink_by_fraction(0.25).unwrap();
+    /// ```
+    #[track_caller]
+    pub fn shrink_by_fraction(&self, fraction: f64) -> Result<()> {
+        if !(0.0..=1.0).contains(&fraction) {
+            bail!("Invalid fraction {}", fraction)
+        }
+        match self.shared.deadline.lock() {
+            Ok(mut deadline) => {
+                if deadline.is_reached() {
+                    bail!(TimeoutError::Reached)
+                }
+                let dur = scale(deadline.remaining(), fraction)?;
+                self.shorten(&mut deadline, dur, false)
+            }
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Decrease the delay before the timeout with a human readable
+    /// duration such as `"1m30s"` or `"250ms"`.
+    ///
+    /// # Return
+    /// Return a result with an error if the duration can't be parsed, or for
+    /// the same reasons as `sub`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after some milliseconds");
+    /// });
+    /// dyn_timeout.add_human("20ms").unwrap();
+    /// dyn_timeout.sub_human("10ms").unwrap();
+    /// ```
+    #[cfg(feature = "humantime")]
+    #[track_caller]
+    pub fn sub_human(&self, dur: &str) -> Result<()> {
+        self.sub(humantime::parse_duration(dur)?)
+    }
+    /// Wait again the initial duration from now.
+    pub(crate) fn restart(&self) -> Result<()> {
+        match self.shared.deadline.lock() {
+            Ok(mut deadline) => {
+                if deadline.is_reached() {
+                    bail!(TimeoutError::Reached)
+                }
+                if deadline.restart() {
+                    self.sender.wake()?;
+                }
+                Ok(())
+            }
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Time left before the timeout, zero if the timeout is already reached
+    /// or cancelled.
+    ///
+    /// # Return
+    /// Return a result with an error if it failed to read the delays.
+    /// Otherwise it return the remaining time.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// dyn_timeout.add(TWENTY).unwrap();
+    /// assert!(dyn_timeout.remaining().unwrap() <= TWENTY * 2);
+    /// ```
+    pub fn remaining(&self) -> Result<Duration> {
+        match self.shared.deadline.lock() {
+            Ok(deadline) => Ok(deadline.remaining()),
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Time left before the timeout, formatted like `2m 13s`, see
+    /// `Countdown`.
+    ///
+    /// # Return
+    /// Return a result with an error if it failed to read the delays.
+    /// Otherwise it return the formatted remaining time.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// let mut dyn_timeout = DynTimeout::new(Duration::from_secs(90), || {
+    ///    println!("never append");
+    /// });
+    /// assert_eq!(dyn_timeout.remaining_human().unwrap(), "1m 30s");
+    /// dyn_timeout.cancel().unwrap();
+    /// ```
+    pub fn remaining_human(&self) -> Result<String> {
+        Ok(Countdown(self.remaining()?).to_string())
+    }
+    /// Watch the absolute deadline, for an event loop arming its own timer
+    /// (a timerfd, a `poll` timeout...) on it instead of polling
+    /// `remaining`. A new value is published each time the deadline moves,
+    /// none once the timeout is reached or cancelled, or while it's not
+    /// started yet.
+    ///
+    /// # Return
+    /// Return a result with an error if it failed to read the delays.
+    /// Otherwise it return the receiver, its current value is the deadline.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// let mut deadline = dyn_timeout.watch_deadline().unwrap();
+    /// let first = deadline.borrow_and_update().unwrap();
+    /// dyn_timeout.add(TWENTY).unwrap();
+    /// assert!(deadline.has_changed().unwrap());
+    /// assert_eq!(*deadline.borrow(), Some(first + TWENTY));
+    /// ```
+    pub fn watch_deadline(&self) -> Result<watch::Receiver<Option<Instant>>> {
+        match self.shared.deadline.lock() {
+            Ok(mut deadline) => Ok(deadline.watch()),
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Instant the timeout will be reached at, none if it's not started
+    /// yet, already reached or cancelled.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after twenty milliseconds");
+    /// });
+    /// assert!(dyn_timeout.deadline().unwrap() <= Instant::now() + TWENTY);
+    /// ```
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.shared.deadline.lock() {
+            Ok(deadline) => deadline.at(),
+            Err(err) => err.into_inner().at(),
+        }
+    }
+    /// Whether this timeout will be reached before the `other` one. A
+    /// running timeout is reached before a timeout not started, reached or
+    /// cancelled, see `deadline`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let request = DynTimeout::new(TWENTY, || println!("request timeout"));
+    /// let session = DynTimeout::new(TWENTY * 2, || println!("session timeout"));
+    /// assert!(request.expires_before(&session));
+    /// request.add(TWENTY * 2).unwrap();
+    /// assert!(session.expires_before(&request));
+    /// ```
+    pub fn expires_before(&self, other: &DynTimeout) -> bool {
+        match (self.deadline(), other.deadline()) {
+            (Some(at), Some(other)) => at < other,
+            (at, _) => at.is_some(),
+        }
+    }
+    /// Adjustments made on the timeout, with their date and the location of
+    /// the call. Empty unless the configuration records the history.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::TimeoutConfig, history::AdjustmentKind, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).record_history(true);
+    /// let dyn_timeout = DynTimeout::with_config(&config, || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// dyn_timeout.add(TWENTY).unwrap();
+    /// let history = dyn_timeout.history();
+    /// assert_eq!(history[0].kind, AdjustmentKind::Add(TWENTY));
+    /// println!("added from {}", history[0].location.unwrap());
+    /// ```
+    pub fn history(&self) -> Vec<Adjustment> {
+        self.shared.history.list()
+    }
+    /// Number of times the timeout thread woke up since its start. The
+    /// thread sleeps until the deadline known when it fell asleep: an `add`
+    /// costs a wake up at the previous deadline, and a `sub` wakes it up
+    /// immediately. Compared to the number of expirations, it measures the
+    /// overhead of an extension pattern.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// std::thread::sleep(TWENTY / 2);
+    /// dyn_timeout.add(TWENTY).unwrap();
+    /// dyn_timeout.wait().unwrap();
+    /// // asleep again after twenty milliseconds, then reached, the exact
+    /// // count depends on the scheduler and the spurious wake ups
+    /// assert!(dyn_timeout.wakeups() >= 1);
+    /// ```
+    pub fn wakeups(&self) -> u64 {
+        self.shared.wakeups.load(Ordering::Relaxed)
+    }
+    /// Live state of the timeout, with its label, the time left and the
+    /// number of extensions, for a debug endpoint dumping the timers.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::TimeoutConfig, snapshot::TimeoutState, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).label("session");
+    /// let dyn_timeout = DynTimeout::with_config(&config, || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// dyn_timeout.add(TWENTY).unwrap();
+    /// let snapshot = dyn_timeout.snapshot();
+    /// assert_eq!(snapshot.label.as_deref(), Some("session"));
+    /// assert_eq!(snapshot.state, TimeoutState::Running);
+    /// assert_eq!(snapshot.extensions, 1);
+    /// ```
+    pub fn snapshot(&self) -> TimeoutSnapshot {
+        self.observer().snapshot()
+    }
+    /// Accuracy of the timeout: how late its last expiration was, and
+    /// whether it met the contract of `TimeoutConfig::precision`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::TimeoutConfig, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut dyn_timeout = DynTimeout::new(TWENTY, || {});
+    /// assert_eq!(dyn_timeout.stats().contract_met, None);
+    /// dyn_timeout.wait().unwrap();
+    /// // always met without a contract
+    /// assert_eq!(dyn_timeout.stats().contract_met, Some(true));
+    /// ```
+    pub fn stats(&self) -> TimeoutStats {
+        let deadline = self.shared.deadline.lock().unwrap();
+        let expiration = deadline.expiration();
+        TimeoutStats::new(
+            deadline.precision(),
+            self.shared.wakeups.load(Ordering::Relaxed),
+            expiration.map(|expiration| expiration.lateness()),
+        )
+    }
+    /// Change the accuracy required by the live timeout, its remaining time
+    /// is kept. A service can switch to `Precision::BestEffort` when idle,
+    /// so the thread stops yielding or spinning before the deadlines, and
+    /// back to a stricter precision when busy.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::{Precision, TimeoutConfig}, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).precision(Precision::SubMillisecond);
+    /// let mut dyn_timeout = DynTimeout::with_config(&config, || {});
+    /// // the service became idle
+    /// dyn_timeout.set_precision(Precision::BestEffort);
+    /// dyn_timeout.wait().unwrap();
+    /// assert_eq!(dyn_timeout.stats().precision, Precision::BestEffort);
+    /// ```
+    pub fn set_precision(&self, precision: Precision) {
+        let mut deadline = self
+            .shared
+            .deadline
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if deadline.set_precision(precision) {
+            // wake up the thread to wait with the new margin
+            let _ = self.sender.wake();
+        }
+    }
+    /// Export the pending timeout, its time left and its policies, so
+    /// another process can re-arm it with `import`. The timeout itself keeps
+    /// running, the exporting process cancels it once handed over.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return the record.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    /// const SESSION: u64 = 1;
+    ///
+    /// let mut draining = DynTimeout::new(TWENTY * 10, || {});
+    /// let record = draining.export(SESSION).unwrap();
+    /// draining.cancel().unwrap();
+    ///
+    /// // in the replacement instance
+    /// let imported = DynTimeout::import(&record, match record.payload_id {
+    ///    SESSION => || println!("session expired"),
+    ///    _ => || {},
+    /// });
+    /// assert!(imported.remaining().unwrap() <= TWENTY * 10);
+    /// ```
+    pub fn export(&self, payload_id: u64) -> Result<TimeoutRecord> {
+        let (remaining, precision) = match self.shared.deadline.lock() {
+            Ok(deadline) if deadline.is_reached() => bail!(TimeoutError::Reached),
+            Ok(deadline) => (deadline.remaining(), deadline.precision()),
+            Err(err) => bail!(err.to_string()),
+        };
+        Ok(TimeoutRecord {
+            payload_id,
+            remaining,
+            exported_at: SystemTime::now(),
+            label: self.label.clone(),
+            max_total: self.max_waiting_time,
+            floor: self.floor,
+            drop_behavior: self.drop_behavior,
+            sub_policy: self.sub_policy,
+            add_policy: self.add_policy,
+            precision,
+        })
+    }
+    /// Re-arm a timeout exported by another process with `export`, the time
+    /// elapsed since the export is deduced.
+    pub fn import(record: &TimeoutRecord, callback: fn() -> ()) -> Self {
+        let mut timeout = Self::with_config(&record.config(), callback);
+        timeout.floor = record.floor;
+        timeout
+    }
+    /// Stop the timeout without executing its callback, so another backend
+    /// takes it over, see `tokio_impl::DynTimeout::try_from`.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened or if
+    /// it's not started yet, its predecessor would start it.
+    /// Otherwise it return the record of the timeout and its callback bound
+    /// to a detached handle.
+    #[track_caller]
+    pub(crate) fn hand_over(mut self) -> Result<(TimeoutRecord, HandedOver)> {
+        self.shared.check_reentrant()?;
+        let record = self.export(0)?;
+        match self.shared.deadline.lock() {
+            Ok(deadline) if deadline.state(false) == TimeoutState::Pending => {
+                bail!("Cannot take over a timeout not started")
+            }
+            Ok(_) => {}
+            Err(err) => bail!(err.to_string()),
+        }
+        // fails if the callback was claimed since the export
+        self.dismiss(Location::caller())?;
+        self.join()?;
+        let callback = self.shared.callback.lock().unwrap().clone();
+        let handle = TimeoutHandle {
+            shared: self.shared.clone(),
+            detached: true,
+        };
+        Ok((record, Arc::new(move || callback(&handle))))
+    }
+    /// Read-only handle to the timeout, for a monitoring component: it
+    /// reads the state, the remaining time and the deadline, and subscribes
+    /// to the deadline changes, but it can't adjust nor cancel the timeout.
+    /// The observer doesn't keep the timeout alive.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{snapshot::TimeoutState, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after forty milliseconds");
+    /// });
+    /// let observer = dyn_timeout.observer();
+    /// std::thread::spawn(move || {
+    ///    let mut deadline = observer.watch_deadline().unwrap();
+    ///    while observer.state() == TimeoutState::Running {
+    ///        println!("{:?} left", observer.remaining().unwrap());
+    ///        let _ = deadline.has_changed();
+    ///        std::thread::sleep(TWENTY / 2);
+    ///    }
+    /// });
+    /// dyn_timeout.add(TWENTY).unwrap();
+    /// ```
+    pub fn observer(&self) -> Observer {
+        Observer {
+            shared: self.shared.clone(),
+            label: self.label.clone(),
+            created_at: self.created_at,
+        }
+    }
+    /// Create a new dynamic timeout started when this one is reached, after
+    /// the execution of its callback. The new timeout can be increased or
+    /// decreased before it's started, and it's dismissed if this one is
+    /// cancelled.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return the new timeout.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let handshake = DynTimeout::new(TWENTY, || {
+    ///    println!("handshake deadline reached");
+    /// });
+    /// let idle = handshake.then(TWENTY, || {
+    ///    println!("idle deadline reached");
+    /// }).unwrap();
+    /// idle.add(TWENTY).unwrap();
+    /// ```
+    pub fn then(&self, dur: Duration, callback: fn() -> ()) -> Result<DynTimeout> {
+        match self.shared.followers.lock() {
+            Ok(mut followers) => match followers.as_mut() {
+                Some(followers) => {
+                    let next = Self::spawn(&TimeoutConfig::new(dur), false, move |_| callback());
+                    followers.push((next.shared.clone(), next.sender.clone()));
+                    Ok(next)
+                }
+                None => bail!(TimeoutError::Reached),
+            },
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Create a new dynamic timeout for a nested operation, its duration is
+    /// clamped to the remaining time of this one and it's cancelled when this
+    /// one ends, so the deadline is propagated to the nested operations.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return the new timeout.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let request = DynTimeout::new(TWENTY, || {
+    ///    println!("request deadline reached");
+    /// });
+    /// let query = request.child(TWENTY * 10, || {
+    ///    println!("query deadline reached");
+    /// }).unwrap();
+    /// assert!(query.remaining().unwrap() <= TWENTY);
+    /// ```
+    pub fn child(&self, dur: Duration, callback: fn() -> ()) -> Result<DynTimeout> {
+        let remaining = self.remaining()?;
+        match self.shared.children.lock() {
+            Ok(mut children) => match children.as_mut() {
+                Some(children) => {
+                    let child =
+                        Self::spawn(&TimeoutConfig::new(dur.min(remaining)), true, move |_| {
+                            callback()
+                        });
+                    children.push((child.shared.clone(), child.sender.clone()));
+                    Ok(child)
+                }
+                None => bail!(TimeoutError::Reached),
+            },
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Split the remaining time between the successive steps of an
+    /// operation, each step gets a child timeout with a fraction of the
+    /// budget, see `Budget::take`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let request = DynTimeout::new(TWENTY * 10, || {
+    ///    println!("request deadline reached");
+    /// });
+    /// let mut budget = request.budget();
+    /// let connect = budget.take(0.4, || println!("connect too long")).unwrap();
+    /// assert!(connect.remaining().unwrap() <= TWENTY * 4);
+    /// // the query gets what the connection left
+    /// let query = budget.take(0.6, || println!("query too long")).unwrap();
+    /// assert!(query.remaining().unwrap() > TWENTY * 4);
+    /// ```
+    pub fn budget(&self) -> Budget<'_> {
+        Budget {
+            timeout: self,
+            allocated: 0.0,
+        }
+    }
+    /// Suspend the timeout while the returned guard is alive. The callback
+    /// can't be executed while there is at least one guard, and the timeout
+    /// restarts with its initial duration when the last guard is dropped.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return the guard.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("twenty milliseconds after the work");
+    /// });
+    /// {
+    ///     let _guard = dyn_timeout.keep_alive().unwrap();
+    ///     std::thread::sleep(TWENTY * 2); // do some work
+    /// }
+    /// ```
+    pub fn keep_alive(&self) -> Result<KeepAlive> {
+        match self.shared.deadline.lock() {
+            Ok(deadline) => {
+                if deadline.is_reached() {
+                    bail!(TimeoutError::Reached)
+                }
+                self.shared.keep_alive.acquire();
+                Ok(KeepAlive {
+                    shared: self.shared.clone(),
+                    sender: self.sender.clone(),
+                })
+            }
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Dismiss the timeout callback and cancel all delays added.
+    /// Stop immediatelly all waiting process and join the created thread.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout if the program failed to
+    /// clear the delays, or `TimeoutError::AlreadyFired` if the callback was
+    /// already started, the thread isn't joined in that case. The callback is
+    /// never started after a success, and cancelling twice succeeds.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    /// const TEN: Duration = Duration::from_millis(10);
+    ///
+    /// let mut dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("never append");
+    /// });
+    /// dyn_timeout.add(TEN).unwrap();
+    /// // cancel the last ten milliseconds and dismiss the callback
+    /// dyn_timeout.cancel().unwrap();
+    /// ```
+    #[track_caller]
+    pub fn cancel(&mut self) -> Result<()> {
+        self.shared.check_reentrant()?;
+        self.dismiss(Location::caller())?;
+        self.join()?;
+        self.thread = None;
+        Ok(())
+    }
+    fn dismiss(&self, location: &'static Location<'static>) -> Result<()> {
+        self.shared
+            .history
+            .record(AdjustmentKind::Cancel, Some(location));
+        // a callback already running can't re-arm the timeout anymore
+        self.shared.stopped.stop();
+        match self.shared.deadline.lock() {
+            Ok(mut deadline) => {
+                let dismissed = self.shared.claim.cancel();
+                deadline.clear();
+                // the thread may be already ended, fired or cancelled before
+                let _ = self.sender.wake();
+                if !dismissed {
+                    bail!(TimeoutError::AlreadyFired)
+                }
+            }
+            Err(err) => bail!(err.to_string()),
+        };
+        Ok(())
+    }
+    /// Stop a re-armable timeout after the cycle in progress: unlike
+    /// `cancel`, the running countdown is still reached and its callback
+    /// completes, but the timeout isn't re-armed anymore, neither by `add`
+    /// with `AddPolicy::Rearm` nor by `TimeoutHandle::rearm`, and the
+    /// thread ends.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::{AddPolicy, TimeoutConfig}, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).add_policy(AddPolicy::Rearm);
+    /// let heartbeat = DynTimeout::with_config(&config, || {
+    ///    println!("heartbeat missed, executed one last time");
+    /// });
+    /// heartbeat.stop_after_current();
+    /// std::thread::sleep(TWENTY * 2);
+    /// assert!(heartbeat.add(TWENTY).is_err());
+    /// ```
+    pub fn stop_after_current(&self) {
+        self.shared.stopped.stop();
+        // wake up the thread waiting for a re-arm
+        let _ = self.sender.wake();
+    }
+    /// Share the timeout between several owners, see `SharedDynTimeout`.
+    pub fn into_shared(self) -> SharedDynTimeout {
+        SharedDynTimeout(Arc::new(self))
+    }
+    /// Handle of the timeout thread, none once the timeout is cancelled or
+    /// if the thread is reused, see `TimeoutConfig::reuse_thread`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after twenty milliseconds");
+    /// });
+    /// let thread = dyn_timeout.join_handle().unwrap().thread();
+    /// println!("waiting in {:?}", thread.id());
+    /// ```
+    pub fn join_handle(&self) -> Option<&JoinHandle<()>> {
+        self.thread.as_ref().and_then(Worker::join_handle)
+    }
+    /// Take the handle of the timeout thread, to join it or attach it to
+    /// another supervision. The timeout is detached and can't be adjusted
+    /// anymore, the thread ends after the execution of the callback.
+    ///
+    /// # Return
+    /// Return the handle, or none if the timeout was cancelled or if the
+    /// thread is reused.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///    println!("after twenty milliseconds");
+    /// });
+    /// dyn_timeout.into_join_handle().unwrap().join().unwrap();
+    /// ```
+    pub fn into_join_handle(mut self) -> Option<JoinHandle<()>> {
+        self.drop_behavior = DropBehavior::Detach;
+        match self.thread.take() {
+            Some(Worker::Spawned(thread)) => Some(thread),
+            _ => None,
+        }
+    }
+    /// Block until the end of the timeout thread, like the join on drop. A
+    /// re-armable timeout ends once it isn't re-armed anymore.
+    ///
+    /// # Return
+    /// Return a result with an error if the thread panicked, or a
+    /// `Panicked` error if the callback panicked with
+    /// `PanicPolicy::Propagate`.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{config::{PanicPolicy, TimeoutConfig}, error::Panicked, std_thread::DynTimeout};
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let config = TimeoutConfig::new(TWENTY).panic_policy(PanicPolicy::Propagate);
+    /// let mut dyn_timeout = DynTimeout::with_config(&config, || {
+    ///    panic!("cleanup failed");
+    /// });
+    /// let err = dyn_timeout.wait().unwrap_err();
+    /// assert_eq!(err.downcast_ref(), Some(&Panicked("cleanup failed".to_string())));
+    /// ```
+    pub fn wait(&mut self) -> Result<()> {
+        self.shared.check_reentrant()?;
+        self.join()?;
+        self.shared.panic_policy.propagate(&self.shared.panicked)
+    }
+    /// Message of the last panic of the callback, caught with
+    /// `PanicPolicy::Record` or `PanicPolicy::Propagate`.
+    pub fn panicked(&self) -> Option<String> {
+        self.shared
+            .panicked
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+    fn join(&mut self) -> Result<()> {
+        match self.thread.take() {
+            Some(thread) => thread.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Earliest deadline of the given timeouts, the next one to be reached.
+///
+/// # Return
+/// Return none if none of the timeouts is running, see `DynTimeout::deadline`.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::std_thread::{earliest, latest, DynTimeout};
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let idle = DynTimeout::new(TWENTY, || println!("idle timeout"));
+/// let session = DynTimeout::new(TWENTY * 2, || println!("session timeout"));
+/// assert_eq!(earliest(&[&idle, &session]), idle.deadline());
+/// assert_eq!(latest(&[&idle, &session]), session.deadline());
+/// ```
+pub fn earliest(timeouts: &[&DynTimeout]) -> Option<Instant> {
+    timeouts
+        .iter()
+        .filter_map(|timeout| timeout.deadline())
+        .min()
+}
+
+/// Latest deadline of the given timeouts, once it's reached all of them are.
+///
+/// # Return
+/// Return none if none of the timeouts is running, see `DynTimeout::deadline`.
+pub fn latest(timeouts: &[&DynTimeout]) -> Option<Instant> {
+    timeouts
+        .iter()
+        .filter_map(|timeout| timeout.deadline())
+        .max()
+}
+
+/// Remaining time of a timeout split between successive steps, returned by
+/// `DynTimeout::budget`.
+pub struct Budget<'a> {
+    timeout: &'a DynTimeout,
+    allocated: f64,
+}
+
+impl Budget<'_> {
+    /// Create a child timeout for the next step with `fraction` of the
+    /// budget, `0.4` for 40%. The fraction is applied to the time remaining
+    /// when the step starts, in proportion of the fractions not allocated
+    /// yet: a late step shrinks the next ones and an early one leaves them
+    /// more time.
+    ///
+    /// # Return
+    /// Return a result with an error if the fraction exceeds what's left of
+    /// the budget or if the timeout already appened.
+    /// Otherwise it return the timeout of the step.
+    pub fn take(&mut self, fraction: f64, callback: fn() -> ()) -> Result<DynTimeout> {
+        let dur = match share(self.timeout.remaining()?, fraction, self.allocated) {
+            Some(dur) => dur,
+            None => bail!("Invalid fraction {}", fraction),
+        };
+        let step = self.timeout.child(dur, callback)?;
+        self.allocated += fraction;
+        Ok(step)
+    }
+    /// Fraction of the budget not allocated yet.
+    pub fn unallocated(&self) -> f64 {
+        (1.0 - self.allocated).max(0.0)
+    }
+}
+
+/// Guard returned by `DynTimeout::keep_alive`, the timeout is suspended
+/// while the guard is alive.
+pub struct KeepAlive {
+    shared: Arc<Shared>,
+    sender: Waker,
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        if self.shared.keep_alive.release() {
+            if let Ok(mut deadline) = self.shared.deadline.lock() {
+                if deadline.restart() {
+                    let _ = self.sender.wake();
+                }
+            }
+        }
+    }
+}
+
+/// Read-only handle to a timeout, created with `DynTimeout::observer`. The
+/// clones are cheap.
+#[derive(Clone)]
+pub struct Observer {
+    shared: Arc<Shared>,
+    label: Option<String>,
+    created_at: SystemTime,
+}
+
+impl Observer {
+    /// State of the timeout.
+    pub fn state(&self) -> TimeoutState {
+        let deadline = self
+            .shared
+            .deadline
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        deadline.state(self.shared.claim.is_cancelled())
+    }
+    /// Time left before the timeout, see `DynTimeout::remaining`.
+    pub fn remaining(&self) -> Result<Duration> {
+        match self.shared.deadline.lock() {
+            Ok(deadline) => Ok(deadline.remaining()),
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Instant the timeout will be reached at, see `DynTimeout::deadline`.
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.shared.deadline.lock() {
+            Ok(deadline) => deadline.at(),
+            Err(err) => err.into_inner().at(),
+        }
+    }
+    /// Watch the absolute deadline, see `DynTimeout::watch_deadline`.
+    pub fn watch_deadline(&self) -> Result<watch::Receiver<Option<Instant>>> {
+        match self.shared.deadline.lock() {
+            Ok(mut deadline) => Ok(deadline.watch()),
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Adjustments made on the timeout, see `DynTimeout::history`.
+    pub fn history(&self) -> Vec<Adjustment> {
+        self.shared.history.list()
+    }
+    /// Live state of the timeout, see `DynTimeout::snapshot`.
+    pub fn snapshot(&self) -> TimeoutSnapshot {
+        let deadline = self
+            .shared
+            .deadline
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        TimeoutSnapshot {
+            label: self.label.clone(),
+            state: deadline.state(self.shared.claim.is_cancelled()),
+            remaining: deadline.remaining(),
+            extensions: self.shared.history.extensions(),
+            wakeups: self.shared.wakeups.load(Ordering::Relaxed),
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Handle given to the callback of a timeout created with
+/// `DynTimeout::with_handle`.
+pub struct TimeoutHandle {
+    shared: Arc<Shared>,
+    /// The callback isn't executed in the timeout thread, see
+    /// `CallbackExecution`.
+    detached: bool,
+}
+
+impl TimeoutHandle {
+    /// Start the timeout again with the given duration, the callback is
+    /// executed again when it's reached. Unlike `AddPolicy::Rearm`, it's
+    /// decided by the callback itself at each execution. A timeout dropped
+    /// with `DropBehavior::Join` isn't started again, so the drop doesn't
+    /// wait forever, neither is a timeout stopped with `stop_after_current`.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout was cancelled, stopped
+    /// or dropped, if it's already running again, or if the callback isn't
+    /// executed in the timeout thread.
+    /// Otherwise it return an empty success.
+    pub fn rearm(&self, dur: Duration) -> Result<()> {
+        if self.detached {
+            bail!("Cannot rearm outside of the timeout thread")
+        }
+        match self.shared.deadline.lock() {
+            Ok(mut deadline) => {
+                // checked with the deadline locked, a concurrent `cancel`
+                // stops the timeout before clearing the deadline
+                if self.shared.claim.is_cancelled() || self.shared.stopped.is_stopped() {
+                    bail!("Timeout cancelled")
+                }
+                if !deadline.rearm(dur) {
+                    bail!("Timeout not reached")
+                }
+                Ok(())
+            }
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Time left before the timeout, zero while the callback is executed.
+    pub fn remaining(&self) -> Result<Duration> {
+        match self.shared.deadline.lock() {
+            Ok(deadline) => Ok(deadline.remaining()),
+            Err(err) => bail!(err.to_string()),
+        }
+    }
+    /// Last expiration of the timeout, the one handled while the callback
+    /// is executed.
+    pub fn expiration(&self) -> Option<Expiration> {
+        match self.shared.deadline.lock() {
+            Ok(deadline) => deadline.expiration(),
+            Err(err) => err.into_inner().expiration(),
+        }
+    }
+}
+
+/// Dynamic timeout with several owners, created with
+/// `DynTimeout::into_shared`. The clones are cheap and all adjust the same
+/// timeout, the methods of `DynTimeout` taking a reference are available on
+/// each of them. The drop behavior applies when the last clone is dropped.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::std_thread::DynTimeout;
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let dyn_timeout = DynTimeout::new(TWENTY, || {
+///    println!("after forty milliseconds");
+/// })
+/// .into_shared();
+/// let remote = dyn_timeout.clone();
+/// std::thread::spawn(move || remote.add(TWENTY).unwrap())
+///     .join()
+///     .unwrap();
+/// assert!(dyn_timeout.remaining().unwrap() > TWENTY);
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SharedDynTimeout(Arc<DynTimeout>);
+
+impl SharedDynTimeout {
+    /// Dismiss the timeout callback for all the owners. Unlike
+    /// `DynTimeout::cancel`, the thread isn't joined.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already ended.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::std_thread::DynTimeout;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let dyn_timeout = DynTimeout::new(TWENTY * 10, || {
+    ///    println!("never append");
+    /// })
+    /// .into_shared();
+    /// dyn_timeout.clone().cancel().unwrap();
+    /// ```
+    #[track_caller]
+    pub fn cancel(&self) -> Result<()> {
+        self.0.dismiss(Location::caller())
+    }
+}
+
+/// Scope owning the timeouts created in it: exiting the scope, by dropping it
+/// or with `close`, cancels all of them and waits for the end of their
+/// threads, so no timer thread outlives a test or a request handler. The
+/// timeouts are shared with the scope, a callback already started is
+/// waited.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::std_thread::TimeoutScope;
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let scope = TimeoutScope::new();
+/// let request = scope.spawn(TWENTY * 10, || println!("request timeout"));
+/// let idle = scope.spawn(TWENTY * 20, || println!("idle timeout"));
+/// request.add(TWENTY).unwrap();
+/// idle.sub(TWENTY).unwrap();
+/// // both cancelled, their threads are joined
+/// scope.close();
+/// ```
+#[derive(Default)]
+pub struct TimeoutScope {
+    timeouts: Mutex<Vec<SharedDynTimeout>>,
+    /// Policies inherited by the timeouts created with `spawn`.
+    defaults: Option<TimeoutConfig>,
+}
+
+impl TimeoutScope {
+    /// Create an empty scope.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Create an empty scope whose timeouts created with `spawn` inherit
+    /// the policies of `defaults` (drop behavior, jitter, max total,
+    /// callback execution...), so they are configured in one place rather
+    /// than at every construction site. The duration of `defaults` is
+    /// replaced by the one given to `spawn`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::{
+    ///    config::{CallbackExecution, DropBehavior, TimeoutConfig},
+    ///    std_thread::TimeoutScope,
+    /// };
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// // configured once for the whole service
+    /// let defaults = TimeoutConfig::new(TWENTY)
+    ///     .drop_behavior(DropBehavior::Cancel)
+    ///     .jitter(TWENTY / 4)
+    ///     .max_total(TWENTY * 50)
+    ///     .callback_execution(CallbackExecution::SharedPool);
+    /// let scope = TimeoutScope::with_defaults(defaults);
+    /// let request = scope.spawn(TWENTY * 10, || println!("request timeout"));
+    /// let idle = scope.spawn_with_config(&scope.config(TWENTY * 20).label("idle"), || {
+    ///    println!("idle timeout")
+    /// });
+    /// request.add(TWENTY).unwrap();
+    /// assert_eq!(idle.snapshot().label.as_deref(), Some("idle"));
+    /// ```
+    pub fn with_defaults(defaults: TimeoutConfig) -> Self {
+        Self {
+            timeouts: Default::default(),
+            defaults: Some(defaults),
+        }
+    }
+    /// Configuration of a timeout of `dur` with the default policies of the
+    /// scope, to override some of them for a single timeout with
+    /// `spawn_with_config`.
+    pub fn config(&self, dur: Duration) -> TimeoutConfig {
+        match &self.defaults {
+            Some(defaults) => TimeoutConfig {
+                duration: dur,
+                ..defaults.clone()
+            },
+            None => TimeoutConfig::new(dur),
+        }
+    }
+    /// Create a timeout in the scope with the default policies of the
+    /// scope, see `DynTimeout::new` and `with_defaults`.
+    pub fn spawn(&self, dur: Duration, callback: fn() -> ()) -> SharedDynTimeout {
+        self.insert(DynTimeout::with_config(&self.config(dur), callback))
+    }
+    /// Create a timeout with the given configuration in the scope, see
+    /// `DynTimeout::with_config`.
+    pub fn spawn_with_config(
+        &self,
+        config: &TimeoutConfig,
+        callback: fn() -> (),
+    ) -> SharedDynTimeout {
+        self.insert(DynTimeout::with_config(config, callback))
+    }
+    /// Move a timeout created elsewhere into the scope.
+    pub fn insert(&self, timeout: DynTimeout) -> SharedDynTimeout {
+        let timeout = timeout.into_shared();
+        self.lock().push(timeout.clone());
+        timeout
+    }
+    /// Number of timeouts in the scope.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+    /// Whether the scope has no timeout.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+    /// Exit the scope, like the drop.
+    pub fn close(self) {}
+    fn lock(&self) -> MutexGuard<'_, Vec<SharedDynTimeout>> {
+        self.timeouts.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for TimeoutScope {
+    fn drop(&mut self) {
+        let timeouts = std::mem::take(&mut *self.lock());
+        // cancelled all together, then joined
+        for timeout in &timeouts {
+            let _ = timeout.0.dismiss(Location::caller());
+        }
+        for timeout in &timeouts {
+            // a callback closing its own scope can't wait for itself
+            if timeout.shared.check_reentrant().is_ok() {
+                timeout.shared.wait_ended();
+            }
+        }
+    }
+}
+
+impl Deref for SharedDynTimeout {
+    type Target = DynTimeout;
+
+    fn deref(&self) -> &DynTimeout {
+        &self.0
+    }
+}
+
+/// Increase the delay before the timeout, `idle_timeout += GRACE` is
+/// `idle_timeout.add(GRACE)` with the error handled by the
+/// `AssignPolicy` of the configuration.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::{config::{AssignPolicy, TimeoutConfig}, std_thread::DynTimeout};
+///
+/// const HEARTBEAT_GRACE: Duration = Duration::from_millis(20);
+///
+/// let config = TimeoutConfig::new(HEARTBEAT_GRACE).assign_policy(AssignPolicy::Ignore);
+/// let mut idle_timeout = DynTimeout::with_config(&config, || {
+///    println!("peer idle");
+/// });
+/// for _heartbeat in 0..3 {
+///    idle_timeout += HEARTBEAT_GRACE;
+/// }
+/// idle_timeout -= HEARTBEAT_GRACE;
+/// ```
+impl AddAssign<Duration> for DynTimeout {
+    #[track_caller]
+    fn add_assign(&mut self, dur: Duration) {
+        self.assign_policy.handle(self.add(dur));
+    }
+}
+
+/// Decrease the delay before the timeout, see `sub` and `AddAssign`.
+impl SubAssign<Duration> for DynTimeout {
+    #[track_caller]
+    fn sub_assign(&mut self, dur: Duration) {
+        self.assign_policy.handle(self.sub(dur));
+    }
+}
+
+/// Same as for `DynTimeout`, on any of the owners.
+impl AddAssign<Duration> for SharedDynTimeout {
+    #[track_caller]
+    fn add_assign(&mut self, dur: Duration) {
+        self.assign_policy.handle(self.add(dur));
+    }
+}
+
+impl SubAssign<Duration> for SharedDynTimeout {
+    #[track_caller]
+    fn sub_assign(&mut self, dur: Duration) {
+        self.assign_policy.handle(self.sub(dur));
+    }
+}
+
+/// Timeouts are equal only to themselves, and hashed by identity, so they can
+/// be used as keys of a `HashMap`.
+impl PartialEq for DynTimeout {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl Eq for DynTimeout {}
+
+impl Hash for DynTimeout {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.shared).hash(state)
+    }
+}
+
+/// Timeouts are ordered by the instant of their deadline, the first to be
+/// reached first, an expired timeout by the deadline it reached. The ones
+/// without a deadline (not started, infinite or cancelled) are after them.
+/// A `BinaryHeap` doesn't see the adjustments made after the insertion, the
+/// timeouts adjusted in the heap have to be pushed again.
+///
+/// # Example
+/// ```
+/// use std::{cmp::Reverse, collections::BinaryHeap, time::Duration};
+/// use dyn_timeout::std_thread::DynTimeout;
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut heap = BinaryHeap::new();
+/// heap.push(Reverse(DynTimeout::new(TWENTY * 2, || println!("second"))));
+/// heap.push(Reverse(DynTimeout::new(TWENTY, || println!("first"))));
+/// let Reverse(next) = heap.pop().unwrap();
+/// assert!(next.remaining().unwrap() <= TWENTY);
+/// ```
+impl Ord for DynTimeout {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        let order = |timeout: &Self| {
+            timeout
+                .shared
+                .deadline
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .order()
+        };
+        order(self)
+            .cmp(&order(other))
+            .then_with(|| Arc::as_ptr(&self.shared).cmp(&Arc::as_ptr(&other.shared)))
+    }
+}
+
+impl PartialOrd for DynTimeout {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Drop for DynTimeout {
+    fn drop(&mut self) {
+        match self.drop_behavior {
+            DropBehavior::Join => {
+                // a re-armable timeout waits for the next `add` otherwise
+                self.shared.stopped.stop();
+                let _ = self.sender.wake();
+                // dropped by its own callback, the thread can't join itself
+                if self.shared.check_reentrant().is_ok() {
+                    self.join().unwrap()
+                }
+            }
+            DropBehavior::Cancel => {
+                // also when dropped by its own callback, which can't cancel
+                self.shared.stopped.stop();
+                let _ = self.sender.wake();
+                let _ = self.cancel();
+            }
+            DropBehavior::Detach => {
+                self.thread.take();
+            }
+        }
     }
 }
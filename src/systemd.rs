@@ -0,0 +1,113 @@
+//! Keep-alive of the systemd service watchdog, built on the std thread
+//! implementation
+use crate::{
+    config::{DropBehavior, TimeoutConfig},
+    std_thread::{DynTimeout, TimeoutHandle},
+};
+use anyhow::{bail, Result};
+use std::{
+    env,
+    os::unix::{ffi::OsStrExt, net::UnixDatagram},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Recurring timeout sending `WATCHDOG=1` to systemd, for the services
+/// configured with `WatchdogSec`. The interval between two pings can be
+/// changed at any time, and the watchdog timeout of systemd can be raised
+/// during a known long operation. Dropping the watchdog stops the pings.
+///
+/// Outside of systemd (no `NOTIFY_SOCKET` in the environment) the pings are
+/// dismissed.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::systemd::SystemdWatchdog;
+///
+/// let watchdog = SystemdWatchdog::new(Duration::from_secs(5));
+/// // a long migration, systemd has to wait the pings for two minutes
+/// watchdog.set_watchdog_timeout(Duration::from_secs(120)).unwrap();
+/// watchdog.set_interval(Duration::from_secs(60));
+/// ```
+pub struct SystemdWatchdog {
+    _timeout: DynTimeout,
+    interval: Arc<Mutex<Duration>>,
+}
+
+impl SystemdWatchdog {
+    /// Start pinging systemd at the given interval, the first ping is sent
+    /// immediately.
+    pub fn new(interval: Duration) -> Self {
+        let shared_interval = Arc::new(Mutex::new(interval));
+        let thread_interval = shared_interval.clone();
+        let config = TimeoutConfig::new(Duration::ZERO).drop_behavior(DropBehavior::Cancel);
+        let ping = move |handle: &TimeoutHandle| {
+            let _ = notify("WATCHDOG=1");
+            let _ = handle.rearm(*thread_interval.lock().unwrap());
+        };
+        Self {
+            _timeout: DynTimeout::spawn(&config, true, ping),
+            interval: shared_interval,
+        }
+    }
+    /// Start pinging systemd at half of the watchdog timeout set by systemd
+    /// in `WATCHDOG_USEC`, as recommended by `sd_watchdog_enabled`.
+    ///
+    /// # Return
+    /// Return a result with an error if the watchdog of the service isn't
+    /// enabled.
+    /// Otherwise it return the started watchdog.
+    pub fn from_env() -> Result<Self> {
+        let usec: u64 = match env::var("WATCHDOG_USEC") {
+            Ok(usec) => usec.parse()?,
+            Err(_) => bail!("Systemd watchdog not enabled"),
+        };
+        Ok(Self::new(Duration::from_micros(usec) / 2))
+    }
+    /// Change the interval between two pings, applied from the next ping.
+    /// The interval has to stay below the watchdog timeout of systemd, see
+    /// `set_watchdog_timeout` to raise it.
+    pub fn set_interval(&self, interval: Duration) {
+        *self.interval.lock().unwrap() = interval;
+    }
+    /// Interval between two pings.
+    pub fn interval(&self) -> Duration {
+        *self.interval.lock().unwrap()
+    }
+    /// Change the watchdog timeout of the service, systemd also resets its
+    /// countdown.
+    ///
+    /// # Return
+    /// Return a result with an error if the notification can't be sent.
+    /// Otherwise it return an empty success.
+    pub fn set_watchdog_timeout(&self, timeout: Duration) -> Result<()> {
+        notify(&format!("WATCHDOG_USEC={}", timeout.as_micros()))
+    }
+}
+
+/// Send a state to the service manager, like `sd_notify`. Dismissed if the
+/// process isn't started by systemd.
+///
+/// # Return
+/// Return a result with an error if the notification can't be sent.
+/// Otherwise it return an empty success.
+pub fn notify(state: &str) -> Result<()> {
+    let path = match env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let socket = UnixDatagram::unbound()?;
+    match path.as_bytes() {
+        #[cfg(target_os = "linux")]
+        [b'@', name @ ..] => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            let addr = SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        _ => {
+            socket.send_to(state.as_bytes(), path)?;
+        }
+    }
+    Ok(())
+}
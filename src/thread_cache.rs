@@ -0,0 +1,114 @@
+//! Threads of the std timeouts kept parked after their timeout ended, to
+//! run the next timeout without spawning a new thread
+use anyhow::{bail, Result};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+/// Job and the channel receiving its result.
+type Task = (Job, mpsc::Sender<thread::Result<()>>);
+
+/// Time a parked thread waits for a new timeout before it ends.
+const IDLE: Duration = Duration::from_secs(10);
+
+lazy_static::lazy_static! {
+    /// Parked threads, each one identified by a number and waiting on the
+    /// channel.
+    static ref IDLE_THREADS: Mutex<Vec<(u64, mpsc::Sender<Task>)>> = Mutex::new(vec![]);
+}
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Thread running the loop of a timeout, joined like a `JoinHandle`.
+pub(crate) enum Worker {
+    /// Thread spawned for the timeout.
+    Spawned(JoinHandle<()>),
+    /// Thread of the cache, ended once the channel receives the result of
+    /// the timeout loop. Behind a mutex to share the timeout between
+    /// threads.
+    Cached(Mutex<mpsc::Receiver<thread::Result<()>>>),
+}
+
+impl Worker {
+    /// Handle of the thread, none if the thread comes from the cache.
+    pub(crate) fn join_handle(&self) -> Option<&JoinHandle<()>> {
+        match self {
+            Worker::Spawned(thread) => Some(thread),
+            Worker::Cached(_) => None,
+        }
+    }
+    /// Wait for the end of the timeout loop.
+    pub(crate) fn join(self) -> Result<()> {
+        let joined = match self {
+            Worker::Spawned(thread) => thread.join().is_ok(),
+            Worker::Cached(done) => {
+                let done = done.into_inner().unwrap_or_else(PoisonError::into_inner);
+                matches!(done.recv(), Ok(Ok(())))
+            }
+        };
+        if !joined {
+            bail!("Cannot join dyn-timeout")
+        }
+        Ok(())
+    }
+}
+
+/// Execute the job in a parked thread, or in a new thread if none is
+/// parked. The thread is parked again once the job ended.
+pub(crate) fn execute(job: Job) -> Worker {
+    let (done_sender, done) = mpsc::channel();
+    let mut task = (job, done_sender);
+    let mut idle = IDLE_THREADS.lock().unwrap();
+    // sent with the lock held, so a thread removed from the list always
+    // receives its job
+    while let Some((_, sender)) = idle.pop() {
+        match sender.send(task) {
+            Ok(()) => return Worker::Cached(Mutex::new(done)),
+            // a parked thread leaves the list before it ends, but never
+            // lose the job
+            Err(mpsc::SendError(unsent)) => task = unsent,
+        }
+    }
+    drop(idle);
+    thread::spawn(move || park(task));
+    Worker::Cached(Mutex::new(done))
+}
+
+/// Run the job, then wait for the next one until the thread stays idle for
+/// too long.
+fn park(mut task: Task) {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let (sender, receiver) = mpsc::channel::<Task>();
+    loop {
+        let (job, done) = task;
+        let result = panic::catch_unwind(AssertUnwindSafe(job));
+        // parked before the join returns, for a timeout created just after
+        IDLE_THREADS.lock().unwrap().push((id, sender.clone()));
+        let _ = done.send(result);
+        task = match receiver.recv_timeout(IDLE) {
+            Ok(task) => task,
+            Err(RecvTimeoutError::Timeout) => {
+                let mut idle = IDLE_THREADS.lock().unwrap();
+                match idle.iter().position(|(parked, _)| *parked == id) {
+                    Some(index) => {
+                        idle.swap_remove(index);
+                        return;
+                    }
+                    // already taken, the job was sent with the lock held
+                    None => match receiver.try_recv() {
+                        Ok(task) => task,
+                        Err(_) => return,
+                    },
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+    }
+}
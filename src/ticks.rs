@@ -0,0 +1,139 @@
+//! Recurring ticks as a `futures_core::Stream`, driven by a std timeout
+//! thread so it works with any executor
+use crate::{
+    config::{DropBehavior, TimeoutConfig},
+    error::TimeoutError,
+    std_thread::{DynTimeout, SharedDynTimeout},
+};
+use anyhow::Result;
+use futures_core::Stream;
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+struct State {
+    period: Duration,
+    /// Instants of the ticks not yielded yet.
+    ticks: VecDeque<Instant>,
+    waker: Option<Waker>,
+}
+
+/// Stream yielding the instant of a tick every period, like
+/// `interval::DynInterval` but without any tokio type: the ticks are
+/// produced by the thread of a std timeout, so the stream can be consumed
+/// by any executor and any Stream combinator library. The first tick is
+/// immediate, the ticks missed by a late consumer are yielded in a burst.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use futures::{executor::block_on, StreamExt};
+/// use dyn_timeout::ticks::Ticks;
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let ticks = Ticks::new(TWENTY);
+/// let handle = ticks.handle();
+/// let ticks = block_on(ticks.take(3).collect::<Vec<_>>());
+/// assert!(ticks[2] - ticks[0] >= TWENTY * 2);
+/// handle.set_period(TWENTY * 2).unwrap();
+/// ```
+pub struct Ticks {
+    handle: TicksHandle,
+}
+
+/// Handle to change the period of a `Ticks` stream consumed somewhere
+/// else, the handle can be cloned. The timeout thread ends once the stream
+/// and its handles are dropped.
+#[derive(Clone)]
+pub struct TicksHandle {
+    timeout: SharedDynTimeout,
+    state: Arc<Mutex<State>>,
+}
+
+impl Ticks {
+    /// Create a stream ticking every `period`.
+    pub fn new(period: Duration) -> Self {
+        let state = Arc::new(Mutex::new(State {
+            period,
+            ticks: VecDeque::new(),
+            waker: None,
+        }));
+        let thread_state = state.clone();
+        let config = TimeoutConfig::new(Duration::ZERO).drop_behavior(DropBehavior::Cancel);
+        let timeout = DynTimeout::spawn(&config, true, move |handle| {
+            let mut state = lock(&thread_state);
+            state.ticks.push_back(Instant::now());
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            // fails only once the stream is dropped
+            let _ = handle.rearm(state.period);
+        });
+        Self {
+            handle: TicksHandle {
+                timeout: timeout.into_shared(),
+                state,
+            },
+        }
+    }
+    /// Create a handle to change the period while the stream is consumed
+    /// somewhere else.
+    pub fn handle(&self) -> TicksHandle {
+        self.handle.clone()
+    }
+}
+
+impl TicksHandle {
+    /// Change the period. The tick in progress is moved by the difference,
+    /// it's immediate if the new period already elapsed.
+    ///
+    /// # Return
+    /// Return a result with an error if the tick in progress can't be
+    /// moved, see `DynTimeout::add`.
+    /// Otherwise it return an empty success.
+    pub fn set_period(&self, period: Duration) -> Result<()> {
+        // locked during the adjustment, so a tick rearms the timeout either
+        // before it or with the new period
+        let mut state = lock(&self.state);
+        let previous = state.period;
+        state.period = period;
+        let moved = if period > previous {
+            self.timeout.add(period - previous)
+        } else {
+            self.timeout.sub(previous - period)
+        };
+        match moved {
+            // the tick is executing, it rearms with the new period
+            Err(err) if matches!(err.downcast_ref(), Some(TimeoutError::Reached)) => Ok(()),
+            moved => moved,
+        }
+    }
+    /// Current period of the stream.
+    pub fn period(&self) -> Duration {
+        lock(&self.state).period
+    }
+}
+
+impl Stream for Ticks {
+    type Item = Instant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Instant>> {
+        let mut state = lock(&self.handle.state);
+        match state.ticks.pop_front() {
+            Some(at) => Poll::Ready(Some(at)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
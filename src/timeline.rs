@@ -0,0 +1,151 @@
+//! Sequence of named timeouts, built on the std thread implementation
+use crate::std_thread::DynTimeout;
+use anyhow::{bail, Result};
+use std::time::Duration;
+
+/// Ordered list of named phases, each phase is a dynamic timeout started
+/// when the previous one is reached. A phase not reached yet can be increased
+/// or decreased while the previous phases are running.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::timeline::Timeline;
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let timeline = Timeline::new("auth", TWENTY, || println!("authenticated"))
+///     .phase("negotiate", TWENTY, || println!("negotiated"))
+///     .unwrap()
+///     .phase("transfer", TWENTY, || println!("transfered"))
+///     .unwrap();
+/// timeline.add("transfer", TWENTY).unwrap();
+/// ```
+pub struct Timeline {
+    phases: Vec<(String, DynTimeout)>,
+}
+
+impl Timeline {
+    /// Create a new timeline with its first phase, started immediately.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::timeline::Timeline;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let timeline = Timeline::new("auth", TWENTY, || println!("authenticated"));
+    /// ```
+    pub fn new(name: &str, dur: Duration, callback: fn() -> ()) -> Self {
+        Self {
+            phases: vec![(name.to_string(), DynTimeout::new(dur, callback))],
+        }
+    }
+    /// Append a phase started when the last phase is reached.
+    ///
+    /// # Return
+    /// Return a result with an error if the last phase already appened.
+    /// Otherwise it return the timeline.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::timeline::Timeline;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let timeline = Timeline::new("auth", TWENTY, || println!("authenticated"))
+    ///     .phase("negotiate", TWENTY, || println!("negotiated"))
+    ///     .unwrap();
+    /// ```
+    pub fn phase(mut self, name: &str, dur: Duration, callback: fn() -> ()) -> Result<Self> {
+        // a timeline always has at least one phase
+        let (_, last) = self.phases.last().unwrap();
+        let next = last.then(dur, callback)?;
+        self.phases.push((name.to_string(), next));
+        Ok(self)
+    }
+    /// Increase the delay of the given phase.
+    ///
+    /// # Return
+    /// Return a result with an error if the phase doesn't exist or already
+    /// appened.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::timeline::Timeline;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let timeline = Timeline::new("auth", TWENTY, || println!("authenticated"))
+    ///     .phase("negotiate", TWENTY, || println!("negotiated"))
+    ///     .unwrap();
+    /// timeline.add("negotiate", TWENTY).unwrap();
+    /// ```
+    #[track_caller]
+    pub fn add(&self, name: &str, dur: Duration) -> Result<()> {
+        self.get(name)?.add(dur)
+    }
+    /// Decrease the delay of the given phase.
+    ///
+    /// # Return
+    /// Return a result with an error if the phase doesn't exist or already
+    /// appened.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::timeline::Timeline;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    /// const TEN: Duration = Duration::from_millis(10);
+    ///
+    /// let timeline = Timeline::new("auth", TWENTY, || println!("authenticated"))
+    ///     .phase("negotiate", TWENTY, || println!("negotiated"))
+    ///     .unwrap();
+    /// timeline.sub("negotiate", TEN).unwrap();
+    /// ```
+    #[track_caller]
+    pub fn sub(&self, name: &str, dur: Duration) -> Result<()> {
+        self.get(name)?.sub(dur)
+    }
+    /// Time left before the end of the given phase, the phase not started
+    /// yet return their whole duration.
+    ///
+    /// # Return
+    /// Return a result with an error if the phase doesn't exist.
+    /// Otherwise it return the remaining time of the phase.
+    pub fn remaining(&self, name: &str) -> Result<Duration> {
+        self.get(name)?.remaining()
+    }
+    /// Dismiss all the phases not reached yet.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::timeline::Timeline;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut timeline = Timeline::new("auth", TWENTY, || println!("never append"))
+    ///     .phase("negotiate", TWENTY, || println!("never append"))
+    ///     .unwrap();
+    /// timeline.cancel();
+    /// ```
+    pub fn cancel(&mut self) {
+        for (_, phase) in self.phases.iter_mut() {
+            // the phases already reached can't be cancelled
+            let _ = phase.cancel();
+        }
+    }
+    fn get(&self, name: &str) -> Result<&DynTimeout> {
+        match self.phases.iter().find(|(phase, _)| phase == name) {
+            Some((_, phase)) => Ok(phase),
+            None => bail!("Unknown phase {}", name),
+        }
+    }
+}
@@ -1,21 +1,153 @@
-///! Implementation of the dynamic timeout using the tokio library
+//! Implementation of the dynamic timeout using the tokio library
+use crate::{
+    command::Adjust,
+    config::{
+        AddPolicy, CallbackExecution, DropBehavior, PanicPolicy, Precision, SendPolicy, SubPolicy,
+        TimeoutConfig,
+    },
+    countdown::Countdown,
+    deadline::{scale, share, Claim, Deadline, KeepAliveState, Stop, Wait},
+    error::{Elapsed, TimeoutError},
+    event::{Expiration, Outcome},
+    history::{Adjustment, AdjustmentKind, History},
+    hook::ErrorHook,
+    recent,
+    snapshot::{TimeoutRecord, TimeoutSnapshot, TimeoutState, TimeoutStats},
+    std_thread,
+    wake::{task_channel, TaskWaker},
+};
 use anyhow::{bail, Result};
 use std::{
+    cmp::Ordering as CmpOrdering,
+    fmt::Debug,
+    future::{poll_fn, Future},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    panic::{self, AssertUnwindSafe},
+    pin::pin,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicU64, Ordering},
         Arc,
     },
-    time::Duration,
+    task::Poll,
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::{
     sync::{
-        mpsc::{self, Sender},
-        Mutex,
+        mpsc::{self, error::TrySendError, Sender},
+        watch, Mutex,
+    },
+    task::{self, JoinHandle},
+};
+#[cfg(feature = "tokio-util")]
+use tokio_util::sync::CancellationToken;
+#[cfg(feature = "chrono")]
+use {
+    crate::deadline::until,
+    chrono::{DateTime, Utc},
+};
+#[cfg(feature = "futures-sink")]
+use {
+    futures_sink::Sink,
+    std::{
+        pin::Pin,
+        task::{ready, Context},
     },
-    task::JoinHandle,
 };
 
-type DurationVec = Arc<Mutex<Vec<Duration>>>;
+/// State shared by a timeout, its task and its guards, in a single
+/// allocation.
+struct Shared {
+    /// Never locked across an await point, see `lock_deadline`.
+    deadline: std::sync::Mutex<Deadline>,
+    /// Claimed by either the callback or a cancellation.
+    claim: Claim,
+    /// No more re-arm, the task ends after the cycle in progress, see
+    /// `stop_after_current`.
+    stopped: Stop,
+    /// Timeouts started when this one is reached, none once the task ended.
+    followers: Mutex<Option<Vec<Linked>>>,
+    /// Timeouts cancelled when this one ends, none once the task ended.
+    children: Mutex<Option<Vec<Linked>>>,
+    keep_alive: KeepAliveState,
+    /// Number of times the thread (or task) woke up, see `wakeups`.
+    wakeups: AtomicU64,
+    max_callback_runtime: Option<Duration>,
+    /// Number of callbacks abandoned, see `overruns`.
+    overruns: AtomicU64,
+    history: History,
+    panic_policy: PanicPolicy,
+    /// Message of the last panic caught in a callback.
+    panicked: std::sync::Mutex<Option<String>>,
+    /// Task executing the callback inline, see `check_reentrant`.
+    callback_task: std::sync::Mutex<Option<task::Id>>,
+    /// Set once the task ended, see `wait_ended`.
+    ended: watch::Sender<bool>,
+    /// How the current cycle ended, none while it runs, see `Completion`.
+    completion: watch::Sender<Option<Outcome>>,
+}
+
+impl Shared {
+    /// Execute a callback in the timeout task.
+    async fn inline<T>(&self, callback: impl Future<Output = T>) -> T {
+        *self.callback_task.lock().unwrap() = task::try_id();
+        let output = callback.await;
+        *self.callback_task.lock().unwrap() = None;
+        output
+    }
+    /// Reject a call into the timeout from its own callback, which would
+    /// wait for the end of the callback (or adjust a deadline the task
+    /// isn't waiting for anymore). The callback re-arms the timeout with
+    /// its `TimeoutHandle` instead.
+    /// Lock the deadline, from the sync and the async methods: the lock is
+    /// never held across an await point.
+    fn lock_deadline(&self) -> std::sync::MutexGuard<'_, Deadline> {
+        self.deadline
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+    fn check_reentrant(&self) -> Result<()> {
+        let callback_task = *self
+            .callback_task
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if callback_task.is_some() && callback_task == task::try_id() {
+            bail!(TimeoutError::ReentrantAdjust)
+        }
+        Ok(())
+    }
+    /// Wait for the end of the task, without its handle.
+    async fn wait_ended(&self) {
+        let _ = self.ended.subscribe().wait_for(|ended| *ended).await;
+    }
+}
+/// Mark the task as ended when it's dropped, even if it's aborted.
+struct Ended(Arc<Shared>);
+
+impl Drop for Ended {
+    fn drop(&mut self) {
+        // the task ended in the middle of a cycle, during the callback if
+        // the timeout was claimed
+        let fired = self.0.claim.is_fired();
+        self.0.completion.send_if_modified(|completion| {
+            let ended = completion.is_none();
+            if ended {
+                *completion = Some(match fired {
+                    true => Outcome::Panicked,
+                    false => Outcome::Cancelled,
+                });
+            }
+            ended
+        });
+        self.0.ended.send_replace(true);
+    }
+}
+
+/// Another timeout and the channel to wake up its task.
+type Linked = (Arc<Shared>, TaskWaker);
+/// Token cancelled when the timeout is reached, see `cancel_token_on_fire`.
+#[cfg(feature = "tokio-util")]
+type FireToken = Arc<std::sync::Mutex<Option<CancellationToken>>>;
 
 /// Dynamic timeout, async implementation with the tokio library.
 /// # Example
@@ -34,12 +166,24 @@ type DurationVec = Arc<Mutex<Vec<Duration>>>;
 /// });
 /// ```
 pub struct DynTimeout {
-    cancelled: Arc<AtomicBool>,
-    durations: DurationVec,
-    sender: mpsc::Sender<()>,
+    shared: Arc<Shared>,
+    sender: TaskWaker,
     thread: Option<JoinHandle<()>>,
-    receiver: mpsc::Receiver<()>,
+    receiver: Mutex<mpsc::Receiver<()>>,
     max_waiting_time: Option<Duration>,
+    floor: Option<Duration>,
+    drop_behavior: DropBehavior,
+    sub_policy: SubPolicy,
+    add_policy: AddPolicy,
+    label: Option<String>,
+    created_at: SystemTime,
+    /// Cancelled when the task ends.
+    #[cfg(feature = "tokio-util")]
+    finished: CancellationToken,
+    #[cfg(feature = "tokio-util")]
+    link: Option<CancellationToken>,
+    #[cfg(feature = "tokio-util")]
+    on_fire: FireToken,
 }
 
 impl DynTimeout {
@@ -62,38 +206,124 @@ impl DynTimeout {
     /// });
     /// ```
     pub fn new(dur: Duration, callback: fn() -> ()) -> Self {
-        let durations: DurationVec = Arc::new(Mutex::new(vec![Duration::ZERO, dur]));
-        let thread_vec = durations.clone();
-        let cancelled = Arc::new(AtomicBool::new(false));
-        let thread_cancelled = cancelled.clone();
-        let (sender, mut receiver) = mpsc::channel::<()>(1);
-        let (tx, rx) = mpsc::channel::<()>(1);
-        Self {
-            cancelled,
-            durations,
-            sender,
-            receiver: rx,
-            thread: Some(tokio::task::spawn(async move {
-                loop {
-                    let dur = {
-                        match thread_vec.lock().await.pop() {
-                            Some(dur) => dur,
-                            None => break,
-                        }
-                    };
-                    let _ = tokio::time::timeout(dur, async { receiver.recv().await }).await;
-                }
-                if !thread_cancelled.load(Ordering::Relaxed) {
-                    //println!("hey");
-                    callback();
-                }
-                tx.send(()).await.unwrap();
-            })),
-            max_waiting_time: None,
-        }
+        Self::spawn(&TimeoutConfig::new(dur), true, move |_| async move {
+            callback()
+        })
+    }
+    /// Create a new dynamic timeout with the policies of the given
+    /// configuration.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::TimeoutConfig, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let config = TimeoutConfig::new(TWENTY).max_total(TWENTY);
+    ///    let dyn_timeout = DynTimeout::with_config(&config, || {
+    ///        println!("after twenty milliseconds");
+    ///    });
+    ///    // dismissed, the max waiting time is already reached
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    /// });
+    /// ```
+    pub fn with_config(config: &TimeoutConfig, callback: fn() -> ()) -> Self {
+        Self::spawn(config, true, move |_| async move { callback() })
+    }
+    /// Create a new dynamic timeout with a fallible callback. If the callback
+    /// returns an error, it is given to the `hook` in the spawned task.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{hook::ErrorHook, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::new_fallible(
+    ///        TWENTY,
+    ///        || Err("after forty milliseconds"),
+    ///        ErrorHook::Log,
+    ///    );
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    /// });
+    /// ```
+    pub fn new_fallible<E>(
+        dur: Duration,
+        callback: fn() -> Result<(), E>,
+        hook: ErrorHook<E>,
+    ) -> Self
+    where
+        E: Debug + Send + 'static,
+    {
+        Self::spawn(&TimeoutConfig::new(dur), true, move |_| {
+            if let Err(err) = callback() {
+                hook.handle(err)
+            }
+            async {}
+        })
+    }
+    /// Create a new dynamic timeout whose callback receives a handle to the
+    /// timeout, so it can start it again and run periodically with a delay
+    /// chosen at each execution. The callback is executed without any lock
+    /// held, re-arming the timeout with the handle never deadlocks. The
+    /// methods of the timeout itself return `TimeoutError::ReentrantAdjust`
+    /// when called from the callback.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::TimeoutConfig, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let heartbeat = DynTimeout::with_handle(&TimeoutConfig::new(TWENTY), |handle| {
+    ///        println!("heartbeat");
+    ///        handle.rearm(TWENTY).unwrap();
+    ///    });
+    /// });
+    /// ```
+    pub fn with_handle(config: &TimeoutConfig, callback: fn(&TimeoutHandle)) -> Self {
+        Self::spawn(config, true, move |handle| async move { callback(&handle) })
+    }
+    /// Create a new dynamic timeout whose callback is an async function,
+    /// awaited in the timeout task (or in its own task with
+    /// `CallbackExecution::Spawned`). Its execution time can be limited
+    /// with `TimeoutConfig::max_callback_runtime`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::TimeoutConfig, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let mut dyn_timeout = DynTimeout::new_async(&TimeoutConfig::new(TWENTY), || async {
+    ///        tokio::time::sleep(Duration::from_millis(1)).await;
+    ///        println!("session flushed");
+    ///    });
+    ///    dyn_timeout.wait().await.unwrap();
+    /// });
+    /// ```
+    pub fn new_async<F, Fut>(config: &TimeoutConfig, callback: F) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self::spawn(config, true, move |_| callback())
     }
     /// Create a new dynamic timeout in a new thread. Call the mpsc sender on
-    /// timeout reached.
+    /// timeout reached. Dropping the receiver is a valid way to lose interest
+    /// in the timeout, the message is then dismissed.
     ///
     /// # Example
     /// ```
@@ -112,43 +342,417 @@ impl DynTimeout {
     /// });
     /// ```
     pub fn with_sender(dur: Duration, sender_in: Sender<()>) -> Self {
-        let durations: DurationVec = Arc::new(Mutex::new(vec![Duration::ZERO, dur]));
-        let thread_vec = durations.clone();
-        let cancelled = Arc::new(AtomicBool::new(false));
-        let thread_cancelled = cancelled.clone();
-        let (sender, mut receiver) = mpsc::channel::<()>(1);
+        Self::with_sender_policy(dur, sender_in, SendPolicy::Wait)
+    }
+    /// Create a new dynamic timeout calling the mpsc sender on timeout
+    /// reached, like `with_sender`. The policy decides what to do if the
+    /// channel is full at that moment: wait for the receiver, with a limit
+    /// or not, dismiss the notification or execute a fallback callback.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::SendPolicy, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let (sender, mut receiver) = tokio::sync::mpsc::channel::<()>(1);
+    ///    sender.send(()).await.unwrap();
+    ///    let policy = SendPolicy::Fallback(|| println!("notification lost"));
+    ///    let mut dyn_timeout = DynTimeout::with_sender_policy(TWENTY, sender, policy);
+    ///    dyn_timeout.wait().await.unwrap();
+    /// });
+    /// ```
+    pub fn with_sender_policy(dur: Duration, sender_in: Sender<()>, policy: SendPolicy) -> Self {
+        Self::spawn(&TimeoutConfig::new(dur), true, move |_| {
+            let sender_in = sender_in.clone();
+            async move {
+                // nobody waits the timeout anymore if the receiver is dropped
+                match policy {
+                    SendPolicy::Wait => {
+                        let _ = sender_in.send(()).await;
+                    }
+                    SendPolicy::Drop => {
+                        let _ = sender_in.try_send(());
+                    }
+                    SendPolicy::WaitFor(limit) => {
+                        let _ = sender_in.send_timeout((), limit).await;
+                    }
+                    SendPolicy::Fallback(callback) => {
+                        if let Err(TrySendError::Full(_)) = sender_in.try_send(()) {
+                            callback()
+                        }
+                    }
+                }
+            }
+        })
+    }
+    /// Create a new dynamic timeout aborting the given task when it's
+    /// reached, a dynamic version of timeout-and-kill: the deadline can still
+    /// be increased while the task runs. Aborting a task already completed
+    /// has no effect.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let task = tokio::spawn(tokio::time::sleep(Duration::from_secs(60)));
+    ///    let dyn_timeout = DynTimeout::guard_task(TWENTY, &task);
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    ///    assert!(task.await.unwrap_err().is_cancelled());
+    /// });
+    /// ```
+    pub fn guard_task<T>(dur: Duration, task: &JoinHandle<T>) -> Self {
+        let task = task.abort_handle();
+        Self::spawn(&TimeoutConfig::new(dur), true, move |_| {
+            task.abort();
+            async {}
+        })
+    }
+    /// Create a new dynamic timeout controlled only through the returned
+    /// sender, which can be cloned and given to other tasks or threads. The
+    /// timeout is owned by a spawned task which applies the commands, it's
+    /// dropped when all the senders are dropped or after an `Adjust::Cancel`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{command::Adjust, config::TimeoutConfig, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let commands = DynTimeout::actor(&TimeoutConfig::new(TWENTY), || {
+    ///        println!("after forty milliseconds");
+    ///    });
+    ///    commands.send(Adjust::Add(TWENTY)).unwrap();
+    /// });
+    /// ```
+    pub fn actor(config: &TimeoutConfig, callback: fn() -> ()) -> mpsc::UnboundedSender<Adjust> {
+        let mut dyn_timeout = Self::with_config(config, callback);
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        spawn_task("dyn-timeout-actor", config.label.as_deref(), async move {
+            while let Some(command) = receiver.recv().await {
+                // the errors are dismissed, there is nobody to report them
+                match command {
+                    Adjust::Add(dur) => {
+                        let _ = dyn_timeout.add(dur).await;
+                    }
+                    Adjust::Sub(dur) => {
+                        let _ = dyn_timeout.sub(dur).await;
+                    }
+                    Adjust::Cancel => {
+                        let _ = dyn_timeout.cancel().await;
+                        break;
+                    }
+                }
+            }
+        });
+        sender
+    }
+    /// Create a new dynamic timeout from a human readable duration such as
+    /// `"1m30s"` or `"250ms"`.
+    ///
+    /// # Return
+    /// Return a result with an error if the duration can't be parsed.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::new_human("20ms", || {
+    ///        println!("after forty milliseconds");
+    ///    }).unwrap();
+    ///    dyn_timeout.add_human("20ms").await.unwrap();
+    /// });
+    /// ```
+    #[cfg(feature = "humantime")]
+    pub fn new_human(dur: &str, callback: fn() -> ()) -> Result<Self> {
+        Ok(Self::new(humantime::parse_duration(dur)?, callback))
+    }
+    /// Create a new dynamic timeout reached at the given date. The date is
+    /// converted into a delay from now, if it's already passed the callback
+    /// is executed immediately.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use chrono::{Duration, Utc};
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::at(Utc::now() + Duration::milliseconds(20), || {
+    ///        println!("after twenty milliseconds");
+    ///    });
+    /// });
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn at(deadline: DateTime<Utc>, callback: fn() -> ()) -> Self {
+        Self::new(until(deadline), callback)
+    }
+    fn spawn<F, Fut>(config: &TimeoutConfig, started: bool, on_timeout: F) -> Self
+    where
+        F: Fn(TimeoutHandle) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            deadline: std::sync::Mutex::new(
+                Deadline::new(config.initial_duration(), started).with_precision(config.precision),
+            ),
+            claim: Claim::new(config.memory_ordering),
+            stopped: Stop::new(config.memory_ordering),
+            followers: Mutex::new(Some(vec![])),
+            children: Mutex::new(Some(vec![])),
+            keep_alive: KeepAliveState::default(),
+            wakeups: AtomicU64::new(0),
+            max_callback_runtime: config.max_callback_runtime,
+            overruns: AtomicU64::new(0),
+            history: History::new(config.record_history),
+            panic_policy: config.panic_policy,
+            panicked: std::sync::Mutex::new(None),
+            callback_task: std::sync::Mutex::new(None),
+            ended: watch::Sender::new(false),
+            completion: watch::Sender::new(None),
+        });
+        let thread_shared = shared.clone();
+        let handle = TimeoutHandle {
+            shared: shared.clone(),
+            detached: false,
+        };
+        let rearm = config.add_policy == AddPolicy::Rearm;
+        let spawned = config.callback_execution == CallbackExecution::Spawned;
+        let label = config.label.clone();
+        let stages = config.stages.clone();
+        let (sender, mut receiver) = task_channel(config.wake_channel);
         let (tx, rx) = mpsc::channel::<()>(1);
+        #[cfg(feature = "tokio-util")]
+        let finished = CancellationToken::new();
+        #[cfg(feature = "tokio-util")]
+        let thread_finished = finished.clone();
+        #[cfg(feature = "tokio-util")]
+        let on_fire: FireToken = Default::default();
+        #[cfg(feature = "tokio-util")]
+        let thread_on_fire = on_fire.clone();
         Self {
-            cancelled,
-            durations,
+            shared,
             sender,
-            receiver: rx,
-            thread: Some(tokio::task::spawn(async move {
-                loop {
-                    let dur = {
-                        match thread_vec.lock().await.pop() {
-                            Some(dur) => dur,
-                            None => break,
+            receiver: Mutex::new(rx),
+            thread: Some(spawn_task(
+                "dyn-timeout",
+                config.label.as_deref(),
+                async move {
+                    let _ended = Ended(thread_shared.clone());
+                    #[cfg(feature = "tokio-util")]
+                    let _finished = thread_finished.drop_guard();
+                    'rearm: loop {
+                        let mut next_stages = stages.iter();
+                        let reached = loop {
+                            while !thread_shared.claim.is_cancelled() {
+                                let wait = thread_shared
+                                    .lock_deadline()
+                                    .next_wait(&thread_shared.keep_alive);
+                                match wait {
+                                    Wait::For(dur) => {
+                                        let woken = tokio::time::timeout(dur, async {
+                                            receiver.recv().await
+                                        })
+                                        .await;
+                                        // nobody can wake up a detached timeout
+                                        if let Ok(None) = woken {
+                                            tokio::time::sleep(dur).await;
+                                        }
+                                        thread_shared.wakeups.fetch_add(1, Ordering::Relaxed);
+                                    }
+                                    // wait for the start, the last keep-alive guard or a
+                                    // cancellation, nobody can ever set a deadline on a
+                                    // dropped timeout without one
+                                    Wait::Signal => {
+                                        if receiver.recv().await.is_none() {
+                                            return;
+                                        }
+                                        thread_shared.wakeups.fetch_add(1, Ordering::Relaxed);
+                                    }
+                                    // the deadline is too close to sleep, the
+                                    // other tasks run meanwhile
+                                    Wait::Spin(_) => task::yield_now().await,
+                                    Wait::Reached => break,
+                                }
+                            }
+                            if thread_shared.claim.is_cancelled() {
+                                break false;
+                            }
+                            match next_stages.next() {
+                                // execute the intermediate callback and wait the
+                                // next stage
+                                Some(&(stage_callback, dur)) => {
+                                    let shared = thread_shared.clone();
+                                    let job = async move {
+                                        let panicked = &shared.panicked;
+                                        shared.panic_policy.call(panicked, stage_callback)
+                                    };
+                                    if spawned {
+                                        spawn_task("dyn-timeout-callback", label.as_deref(), job);
+                                    } else {
+                                        job.await;
+                                    }
+                                    thread_shared.lock_deadline().rearm(dur);
+                                }
+                                // the cancellation may have claimed the timeout
+                                // in the meantime
+                                None => break thread_shared.claim.fire(),
+                            }
+                        };
+                        let mut overran = false;
+                        if reached {
+                            if spawned {
+                                let handle = TimeoutHandle {
+                                    shared: handle.shared.clone(),
+                                    detached: true,
+                                };
+                                let shared = thread_shared.clone();
+                                let callback = on_timeout(handle);
+                                spawn_task("dyn-timeout-callback", label.as_deref(), async move {
+                                    guard(&shared, callback).await;
+                                });
+                            } else {
+                                let callback = guard(&thread_shared, on_timeout(handle.clone()));
+                                overran = !thread_shared.inline(callback).await;
+                            }
+                            #[cfg(feature = "tokio-util")]
+                            if let Some(token) = thread_on_fire.lock().unwrap().as_ref() {
+                                token.cancel();
+                            }
                         }
-                    };
-                    let _ = tokio::time::timeout(dur, async { receiver.recv().await }).await;
-                }
-                if !thread_cancelled.load(Ordering::Relaxed) {
-                    sender_in.send(()).await.unwrap();
-                }
-                tx.send(()).await.unwrap();
-            })),
-            max_waiting_time: None,
+                        let children = thread_shared.children.lock().await.take();
+                        for (child, sender) in children.unwrap_or_default() {
+                            child.claim.cancel();
+                            child.lock_deadline().clear();
+                            sender.wake();
+                        }
+                        let followers = thread_shared.followers.lock().await.take();
+                        for (next, sender) in followers.unwrap_or_default() {
+                            if reached {
+                                next.lock_deadline().start();
+                            } else {
+                                next.claim.cancel();
+                            }
+                            sender.wake();
+                        }
+                        let outcome = match reached {
+                            true if overran => Outcome::Overrun,
+                            // not waited yet, see `PanicPolicy::propagate`
+                            true if thread_shared.panic_policy == PanicPolicy::Propagate
+                                && thread_shared.panicked.lock().unwrap().is_some() =>
+                            {
+                                Outcome::Panicked
+                            }
+                            true => Outcome::Fired,
+                            false => Outcome::Cancelled,
+                        };
+                        let expiration = match reached {
+                            true => thread_shared.lock_deadline().expiration(),
+                            false => None,
+                        };
+                        let overshoot = expiration.map(|expiration| expiration.lateness());
+                        recent::record(label.as_deref(), outcome, overshoot);
+                        thread_shared.completion.send_replace(Some(outcome));
+                        let _ = tx.try_send(());
+                        if !reached {
+                            break;
+                        }
+                        if !rearm {
+                            // the callback may have started the timeout again
+                            // with its handle
+                            if thread_shared.lock_deadline().is_reached() {
+                                break;
+                            }
+                        }
+                        // wait for a re-arm, a cancellation or the drop
+                        while thread_shared.lock_deadline().is_reached() {
+                            if thread_shared.claim.is_cancelled()
+                                || thread_shared.stopped.is_stopped()
+                                || receiver.recv().await.is_none()
+                            {
+                                break 'rearm;
+                            }
+                        }
+                        thread_shared.claim.rearm();
+                        thread_shared.completion.send_replace(None);
+                    }
+                },
+            )),
+            max_waiting_time: config.max_total,
+            floor: None,
+            drop_behavior: config.drop_behavior,
+            sub_policy: config.sub_policy,
+            add_policy: config.add_policy,
+            label: config.label.clone(),
+            created_at: SystemTime::now(),
+            #[cfg(feature = "tokio-util")]
+            finished,
+            #[cfg(feature = "tokio-util")]
+            link: None,
+            #[cfg(feature = "tokio-util")]
+            on_fire,
         }
     }
     /// Set a muximum time we can wait, dismiss the `add` call if overflow.
     pub fn set_max_waiting_time(&mut self, duration: Duration) {
         self.max_waiting_time = Some(duration)
     }
-    /// Increase the delay before the timeout.
+    /// Set a minimum remaining time: the `sub` calls (and
+    /// `shrink_by_fraction`) can't bring the timeout closer than `floor`
+    /// from now, a larger decrease is reduced to stop at the floor. It
+    /// protects an operation of a minimum processing time whatever the other
+    /// components remove. The timeout is reached normally once the floor
+    /// itself has elapsed.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let mut dyn_timeout = DynTimeout::new(TWENTY * 5, || {
+    ///        println!("after forty milliseconds");
+    ///    });
+    ///    dyn_timeout.set_floor(TWENTY * 2);
+    ///    dyn_timeout.sub(TWENTY * 4).await.unwrap();
+    ///    assert!(dyn_timeout.remaining().await > TWENTY);
+    /// });
+    /// ```
+    pub fn set_floor(&mut self, floor: Duration) {
+        self.floor = Some(floor)
+    }
+    /// Increase the delay before the timeout. If the timeout already appened
+    /// and the configuration uses `AddPolicy::Rearm`, the timeout is started
+    /// again with the given duration.
+    ///
+    /// The task decides to execute the callback with the deadline locked, so
+    /// a successful `add` guarantees the callback isn't executed before the
+    /// new deadline, even if the task was about to wake up.
     ///
     /// # Return
-    /// Return a result with an error if the timeout already appened.
+    /// Return a result with `TimeoutError::Reached` if the timeout already
+    /// appened, unless it's re-armed: the `add` lost the race against the
+    /// expiry. Or `TimeoutError::InvalidDuration` if the new deadline
+    /// overflows, `TimeoutError::ReentrantAdjust` if it's called from the
+    /// callback.
     /// Otherwise it return an empty success.
     ///
     /// # Example
@@ -167,26 +771,58 @@ impl DynTimeout {
     /// });
     /// ```
     pub async fn add(&self, dur: Duration) -> Result<()> {
-        let mut durations = self.durations.lock().await;
-        if durations.is_empty() {
-            bail!("Timeout already reached")
-        }
-        if let Some(m) = self.max_waiting_time {
-            let mut tt = Duration::from_millis(0);
-            for d in durations.iter() {
-                tt += *d;
-            }
-            if tt >= m {
+        self.shared.check_reentrant()?;
+        let mut deadline = self.shared.lock_deadline();
+        if deadline.is_reached() {
+            if self.add_policy == AddPolicy::Rearm
+                && !self.shared.claim.is_cancelled()
+                && !self.shared.stopped.is_stopped()
+                && deadline.rearm(dur)
+            {
+                self.shared.history.record(AdjustmentKind::Rearm(dur), None);
+                self.sender.wake();
                 return Ok(());
             }
+            bail!(TimeoutError::Reached)
         }
-        durations.push(dur);
-        Ok(())
+        self.extend(&mut deadline, dur)
+    }
+    fn extend(&self, deadline: &mut Deadline, dur: Duration) -> Result<()> {
+        extend(deadline, self.max_waiting_time, &self.shared.history, dur)
     }
-    /// Try to decrease the delay before the timeout. (bad precision, work in progress)
+    /// Increase the delay before the timeout by `ms` milliseconds, see
+    /// `add`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let dyn_timeout = DynTimeout::new(Duration::from_millis(20), || {
+    ///        println!("after forty milliseconds");
+    ///    });
+    ///    dyn_timeout.add_millis(20).await.unwrap();
+    /// });
+    /// ```
+    pub async fn add_millis(&self, ms: u64) -> Result<()> {
+        self.add(Duration::from_millis(ms)).await
+    }
+    /// Increase the delay before the timeout by `secs` seconds, see `add`.
+    pub async fn add_secs(&self, secs: u64) -> Result<()> {
+        self.add(Duration::from_secs(secs)).await
+    }
+    /// Increase the delay before the timeout like `add`, but a deadline
+    /// which can't be represented is pushed to `Duration::MAX` instead of
+    /// returning `TimeoutError::InvalidDuration`: the timeout is then never
+    /// reached until a delay is `set`, like a timeout armed with
+    /// `Duration::MAX`.
     ///
     /// # Return
-    /// Return a result with an error if the timeout already appened.
+    /// Return a result with an error for the same reasons as `add`, except
+    /// an overflow.
     /// Otherwise it return an empty success.
     ///
     /// # Example
@@ -195,38 +831,109 @@ impl DynTimeout {
     /// use dyn_timeout::tokio_impl::DynTimeout;
     /// use std::time::Duration;
     ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let dyn_timeout = DynTimeout::new(Duration::from_millis(20), || {
+    ///        println!("not before a delay is set");
+    ///    });
+    ///    dyn_timeout.saturating_add(Duration::MAX).await.unwrap();
+    ///    assert_eq!(dyn_timeout.remaining().await, Duration::MAX);
+    /// });
+    /// ```
+    pub async fn saturating_add(&self, dur: Duration) -> Result<()> {
+        match self.add(dur).await {
+            Err(err) if err.downcast_ref() == Some(&TimeoutError::InvalidDuration) => {
+                let mut deadline = self.shared.lock_deadline();
+                // reached in the meantime
+                if deadline.is_reached() {
+                    bail!(TimeoutError::Reached)
+                }
+                deadline.saturate();
+                self.shared.history.record(AdjustmentKind::Add(dur), None);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+    /// Increase the delay before the timeout with a human readable duration
+    /// such as `"1m30s"` or `"250ms"`.
+    ///
+    /// # Return
+    /// Return a result with an error if the duration can't be parsed, or for
+    /// the same reasons as `add`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after some milliseconds");
+    ///    });
+    ///    dyn_timeout.add_human("20ms").await.unwrap();
+    /// });
+    /// ```
+    #[cfg(feature = "humantime")]
+    pub async fn add_human(&self, dur: &str) -> Result<()> {
+        self.add(humantime::parse_duration(dur)?).await
+    }
+    /// Increase the delay so the timeout isn't reached before the given date.
+    /// Do nothing if the timeout is already expected after this date.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use chrono::Utc;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
     /// const TWENTY: Duration = Duration::from_millis(20);
-    /// const TEN: Duration = Duration::from_millis(10);
     ///
     /// let mut rt = Runtime::new().unwrap();
     /// rt.spawn(async {
     ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
     ///        println!("after some milliseconds");
     ///    });
-    ///    dyn_timeout.add(TEN).await.unwrap();
-    ///    dyn_timeout.add(TWENTY).await.unwrap();
-    ///    dyn_timeout.sub(TEN).await.unwrap();
+    ///    dyn_timeout
+    ///        .extend_until(Utc::now() + chrono::Duration::milliseconds(40))
+    ///        .await
+    ///        .unwrap();
     /// });
     /// ```
-    pub async fn sub(&self, dur: Duration) -> Result<()> {
-        let mut durations = self.durations.lock().await;
-        if durations.is_empty() {
-            bail!("Timeout already reached")
+    #[cfg(feature = "chrono")]
+    pub async fn extend_until(&self, date: DateTime<Utc>) -> Result<()> {
+        let mut deadline = self.shared.lock_deadline();
+        if deadline.is_reached() {
+            bail!(TimeoutError::Reached)
         }
-        let mut pop_dur = Duration::default();
-        while pop_dur < dur && durations.len() > 1 {
-            pop_dur += durations.pop().unwrap();
-        }
-        if pop_dur > dur {
-            durations.push(pop_dur - dur);
+        let until = until(date);
+        let remaining = deadline.remaining();
+        if until > remaining {
+            self.extend(&mut deadline, until - remaining)?;
         }
         Ok(())
     }
-    /// Dismiss the timeout callback and cancel all delays added.
-    /// Stop immediatelly all waiting process and join the created thread.
+    /// Decrease the delay before the timeout. If more than the remaining time
+    /// is removed, the timeout is reached immediately, an error is returned
+    /// with `SubPolicy::Error` or the remaining time is clamped with
+    /// `SubPolicy::Clamp`.
+    ///
+    /// Concurrent `add` and `sub` calls are applied one at a time, and until
+    /// the timeout is reached the deadline moves by the sum of their
+    /// durations whatever their order: the time removed beyond the remaining
+    /// time isn't forgotten, an `add` applied before the task notices the
+    /// timeout is reached pays it back first.
     ///
     /// # Return
-    /// Return a result with an error if the timeout already appened.
+    /// Return a result with an error if the timeout already appened or if the
+    /// duration exceeds the remaining time with `SubPolicy::Error`.
     /// Otherwise it return an empty success.
     ///
     /// # Example
@@ -240,23 +947,1962 @@ impl DynTimeout {
     ///
     /// let mut rt = Runtime::new().unwrap();
     /// rt.spawn(async {
-    ///    let mut dyn_timeout = DynTimeout::new(TWENTY, || {
-    ///        println!("never append");
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after some milliseconds");
     ///    });
-    ///    dyn_timeout.cancel().await.unwrap();
+    ///    dyn_timeout.add(TEN).await.unwrap();
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    ///    dyn_timeout.sub(TEN).await.unwrap();
     /// });
     /// ```
-    pub async fn cancel(&mut self) -> Result<()> {
-        self.cancelled.store(true, Ordering::Relaxed);
-        self.durations.lock().await.clear();
-        self.sender.send(()).await?;
-        self.thread = None;
-        Ok(())
+    pub async fn sub(&self, dur: Duration) -> Result<()> {
+        self.shared.check_reentrant()?;
+        let mut deadline = self.shared.lock_deadline();
+        if deadline.is_reached() {
+            bail!(TimeoutError::Reached)
+        }
+        self.shorten(&mut deadline, dur, false)
     }
-
-    /// Wait for the end of the timeout
-    pub async fn wait(&mut self) -> Result<()> {
-        self.receiver.recv().await;
-        Ok(())
+    /// Decrease the delay before the timeout by at most the remaining time:
+    /// the timeout is reached immediately if `dur` exceeds it, without error
+    /// even with `SubPolicy::Error`, and the excess isn't deducted from a
+    /// later `add` as with `sub`. The `SubPolicy::Clamp` minimum and the
+    /// floor still apply.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::{SubPolicy, TimeoutConfig}, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let config = TimeoutConfig::new(Duration::from_millis(20)).sub_policy(SubPolicy::Error);
+    ///    let mut dyn_timeout = DynTimeout::with_config(&config, || {
+    ///        println!("right now");
+    ///    });
+    ///    assert!(dyn_timeout.sub(Duration::from_secs(1)).await.is_err());
+    ///    dyn_timeout.saturating_sub(Duration::from_secs(1)).await.unwrap();
+    ///    dyn_timeout.wait().await.unwrap();
+    /// });
+    /// ```
+    pub async fn saturating_sub(&self, dur: Duration) -> Result<()> {
+        self.shared.check_reentrant()?;
+        let mut deadline = self.shared.lock_deadline();
+        if deadline.is_reached() {
+            bail!(TimeoutError::Reached)
+        }
+        self.shorten(&mut deadline, dur, true)
+    }
+    /// Replace the delay before the timeout, the timeout is reached `dur`
+    /// from now whatever the time left. A timeout armed with `Duration::MAX`
+    /// is never reached until a delay is set, so it can be created eagerly
+    /// and activated later. On such a timeout, `sub` counts from
+    /// `Duration::MAX` and `add` overflows.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let mut dyn_timeout = DynTimeout::new(Duration::MAX, || {
+    ///        println!("twenty milliseconds after the activation");
+    ///    });
+    ///    dyn_timeout.set(TWENTY).await.unwrap();
+    ///    dyn_timeout.wait().await.unwrap();
+    /// });
+    /// ```
+    pub async fn set(&self, dur: Duration) -> Result<()> {
+        self.shared.check_reentrant()?;
+        let mut deadline = self.shared.lock_deadline();
+        if deadline.is_reached() {
+            bail!(TimeoutError::Reached)
+        }
+        self.shared.history.record(AdjustmentKind::Set(dur), None);
+        if deadline.set(dur) {
+            // wake up the task to wait the new deadline
+            self.sender.wake();
+        }
+        Ok(())
+    }
+    /// Remove `dur` from the deadline, at most the remaining time if
+    /// `saturating`.
+    fn shorten(&self, deadline: &mut Deadline, dur: Duration, saturating: bool) -> Result<()> {
+        let remaining = deadline.remaining();
+        let dur = match saturating {
+            true => dur.min(remaining),
+            false => dur,
+        };
+        let mut removed = self.sub_policy.apply(dur, remaining)?;
+        if let Some(floor) = self.floor {
+            // nothing removed if the remaining time is already under it
+            removed = removed.min(remaining.saturating_sub(floor));
+        }
+        self.shared.history.record(AdjustmentKind::Sub(dur), None);
+        if deadline.sub(removed) {
+            // wake up the task to wait the new deadline
+            self.sender.wake();
+        }
+        Ok(())
+    }
+    /// Increase the remaining time by a fraction of itself, `0.5` gives 50%
+    /// more time than what remains.
+    ///
+    /// # Return
+    /// Return a result with an error if the fraction is negative or if the
+    /// timeout already appened.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after about thirty milliseconds");
+    ///    });
+    ///    dyn_timeout.extend_by_fraction(0.5).await.unwrap();
+    /// });
+    /// ```
+    pub async fn extend_by_fraction(&self, fraction: f64) -> Result<()> {
+        if !fraction.is_finite() || fraction < 0.0 {
+            bail!("Invalid fraction {}", fraction)
+        }
+        let mut deadline = self.shared.lock_deadline();
+        if deadline.is_reached() {
+            bail!(TimeoutError::Reached)
+        }
+        let dur = scale(deadline.remaining(), fraction)?;
+        self.extend(&mut deadline, dur)
+    }
+    /// Decrease the remaining time by a fraction of itself, `0.25` removes a
+    /// quarter of what remains.
+    ///
+    /// # Return
+    /// Return a result with an error if the fraction isn't between 0 and 1
+    /// or if the timeout already appened.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after some milliseconds");
+    ///    });
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    ///    dyn_timeout.shrink_by_fraction(0.25).await.unwrap();
+    /// });
+    /// ```
+    pub async fn shrink_by_fraction(&self, fraction: f64) -> Result<()> {
+        if !(0.0..=1.0).contains(&fraction) {
+            bail!("Invalid fraction {}", fraction)
+        }
+        let mut deadline = self.shared.lock_deadline();
+        if deadline.is_reached() {
+            bail!(TimeoutError::Reached)
+        }
+        let dur = scale(deadline.remaining(), fraction)?;
+        self.shorten(&mut deadline, dur, false)
+    }
+    /// Decrease the delay before the timeout with a human readable
+    /// duration such as `"1m30s"` or `"250ms"`.
+    ///
+    /// # Return
+    /// Return a result with an error if the duration can't be parsed, or for
+    /// the same reasons as `sub`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after some milliseconds");
+    ///    });
+    ///    dyn_timeout.add_human("20ms").await.unwrap();
+    ///    dyn_timeout.sub_human("10ms").await.unwrap();
+    /// });
+    /// ```
+    #[cfg(feature = "humantime")]
+    pub async fn sub_human(&self, dur: &str) -> Result<()> {
+        self.sub(humantime::parse_duration(dur)?).await
+    }
+    /// Time left before the timeout, zero if the timeout is already reached
+    /// or cancelled.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after forty milliseconds");
+    ///    });
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    ///    assert!(dyn_timeout.remaining().await <= TWENTY * 2);
+    /// });
+    /// ```
+    pub async fn remaining(&self) -> Duration {
+        self.shared.lock_deadline().remaining()
+    }
+    /// Time left before the timeout, formatted like `2m 13s`, see
+    /// `Countdown`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let mut dyn_timeout = DynTimeout::new(Duration::from_secs(90), || {
+    ///        println!("never append");
+    ///    });
+    ///    assert_eq!(dyn_timeout.remaining_human().await, "1m 30s");
+    ///    dyn_timeout.cancel().await.unwrap();
+    /// });
+    /// ```
+    pub async fn remaining_human(&self) -> String {
+        Countdown(self.remaining().await).to_string()
+    }
+    /// Watch the absolute deadline, for an event loop arming its own timer
+    /// on it instead of polling `remaining`. A new value is published each
+    /// time the deadline moves, none once the timeout is reached or
+    /// cancelled, or while it's not started yet.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after forty milliseconds");
+    ///    });
+    ///    let mut deadline = dyn_timeout.watch_deadline().await;
+    ///    let first = deadline.borrow_and_update().unwrap();
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    ///    deadline.changed().await.unwrap();
+    ///    assert_eq!(*deadline.borrow(), Some(first + TWENTY));
+    /// });
+    /// ```
+    pub async fn watch_deadline(&self) -> watch::Receiver<Option<Instant>> {
+        self.shared.lock_deadline().watch()
+    }
+    /// Instant the timeout will be reached at, none if it's not started
+    /// yet, already reached or cancelled.
+    pub async fn deadline(&self) -> Option<Instant> {
+        self.shared.lock_deadline().at()
+    }
+    /// Whether this timeout will be reached before the `other` one. A
+    /// running timeout is reached before a timeout not started, reached or
+    /// cancelled, see `deadline`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let request = DynTimeout::new(TWENTY, || println!("request timeout"));
+    ///    let session = DynTimeout::new(TWENTY * 2, || println!("session timeout"));
+    ///    assert!(request.expires_before(&session).await);
+    ///    request.add(TWENTY * 2).await.unwrap();
+    ///    assert!(session.expires_before(&request).await);
+    /// });
+    /// ```
+    pub async fn expires_before(&self, other: &DynTimeout) -> bool {
+        match (self.deadline().await, other.deadline().await) {
+            (Some(at), Some(other)) => at < other,
+            (at, _) => at.is_some(),
+        }
+    }
+    /// Adjustments made on the timeout, with their date. Empty unless the
+    /// configuration records the history.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::TimeoutConfig, history::AdjustmentKind, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let config = TimeoutConfig::new(TWENTY).record_history(true);
+    ///    let dyn_timeout = DynTimeout::with_config(&config, || {
+    ///        println!("after forty milliseconds");
+    ///    });
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    ///    assert_eq!(dyn_timeout.history()[0].kind, AdjustmentKind::Add(TWENTY));
+    /// });
+    /// ```
+    pub fn history(&self) -> Vec<Adjustment> {
+        self.shared.history.list()
+    }
+    /// Number of times the timeout task woke up since its start. The task
+    /// sleeps until the deadline known when it fell asleep: an `add` costs
+    /// a wake up at the previous deadline, and a `sub` wakes it up
+    /// immediately. Compared to the number of expirations, it measures the
+    /// overhead of an extension pattern.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let mut dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after forty milliseconds");
+    ///    });
+    ///    tokio::time::sleep(TWENTY / 2).await;
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    ///    dyn_timeout.wait().await.unwrap();
+    ///    // asleep again after twenty milliseconds, then reached, the exact
+    ///    // count depends on the scheduler
+    ///    assert!(dyn_timeout.wakeups() >= 1);
+    /// });
+    /// ```
+    pub fn wakeups(&self) -> u64 {
+        self.shared.wakeups.load(Ordering::Relaxed)
+    }
+    /// Number of callbacks abandoned because they ran longer than
+    /// `TimeoutConfig::max_callback_runtime`.
+    pub fn overruns(&self) -> u64 {
+        self.shared.overruns.load(Ordering::Relaxed)
+    }
+    /// Live state of the timeout, with its label, the time left and the
+    /// number of extensions, for a debug endpoint dumping the timers.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::TimeoutConfig, snapshot::TimeoutState, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let config = TimeoutConfig::new(TWENTY).label("session");
+    ///    let dyn_timeout = DynTimeout::with_config(&config, || {
+    ///        println!("after forty milliseconds");
+    ///    });
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    ///    let snapshot = dyn_timeout.snapshot().await;
+    ///    assert_eq!(snapshot.label.as_deref(), Some("session"));
+    ///    assert_eq!(snapshot.state, TimeoutState::Running);
+    ///    assert_eq!(snapshot.extensions, 1);
+    /// });
+    /// ```
+    pub async fn snapshot(&self) -> TimeoutSnapshot {
+        self.observer().snapshot().await
+    }
+    /// Accuracy of the timeout: how late its last expiration was, and
+    /// whether it met the contract of `TimeoutConfig::precision`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{
+    ///     config::{Precision, TimeoutConfig},
+    ///     tokio_impl::DynTimeout,
+    /// };
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let config = TimeoutConfig::new(TWENTY).precision(Precision::Millisecond);
+    ///    let mut dyn_timeout = DynTimeout::with_config(&config, || {});
+    ///    assert_eq!(dyn_timeout.stats().await.contract_met, None);
+    ///    dyn_timeout.wait().await.unwrap();
+    ///    let stats = dyn_timeout.stats().await;
+    ///    println!("late of {:?}, met: {:?}", stats.lateness, stats.contract_met);
+    /// });
+    /// ```
+    pub async fn stats(&self) -> TimeoutStats {
+        let deadline = self.shared.lock_deadline();
+        let expiration = deadline.expiration();
+        TimeoutStats::new(
+            deadline.precision(),
+            self.shared.wakeups.load(Ordering::Relaxed),
+            expiration.map(|expiration| expiration.lateness()),
+        )
+    }
+    /// Change the accuracy required by the live timeout, its remaining time
+    /// is kept. A service can switch to `Precision::BestEffort` when idle,
+    /// so the task stops yielding before the deadlines, and back to a
+    /// stricter precision when busy.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::{Precision, TimeoutConfig}, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let mut dyn_timeout = DynTimeout::new(TWENTY, || {});
+    ///    // the service became busy
+    ///    dyn_timeout.set_precision(Precision::Millisecond).await;
+    ///    dyn_timeout.wait().await.unwrap();
+    ///    assert_eq!(dyn_timeout.stats().await.precision, Precision::Millisecond);
+    /// });
+    /// ```
+    pub async fn set_precision(&self, precision: Precision) {
+        if self.shared.lock_deadline().set_precision(precision) {
+            // wake up the task to wait with the new margin
+            self.sender.wake();
+        }
+    }
+    /// Export the pending timeout, its time left and its policies, so
+    /// another process can re-arm it with `import`. The timeout itself keeps
+    /// running, the exporting process cancels it once handed over.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return the record.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let mut draining = DynTimeout::new(TWENTY * 10, || {});
+    ///    let record = draining.export(1).await.unwrap();
+    ///    draining.cancel().await.unwrap();
+    ///    // in the replacement instance
+    ///    let imported = DynTimeout::import(&record, || println!("session expired"));
+    ///    assert!(imported.remaining().await <= TWENTY * 10);
+    /// });
+    /// ```
+    pub async fn export(&self, payload_id: u64) -> Result<TimeoutRecord> {
+        let (remaining, precision) = {
+            let deadline = self.shared.lock_deadline();
+            if deadline.is_reached() {
+                bail!(TimeoutError::Reached)
+            }
+            (deadline.remaining(), deadline.precision())
+        };
+        Ok(TimeoutRecord {
+            payload_id,
+            remaining,
+            exported_at: SystemTime::now(),
+            label: self.label.clone(),
+            max_total: self.max_waiting_time,
+            floor: self.floor,
+            drop_behavior: self.drop_behavior,
+            sub_policy: self.sub_policy,
+            add_policy: self.add_policy,
+            precision,
+        })
+    }
+    /// Re-arm a timeout exported by another process with `export`, the time
+    /// elapsed since the export is deduced.
+    pub fn import(record: &TimeoutRecord, callback: fn() -> ()) -> Self {
+        let mut timeout = Self::with_config(&record.config(), callback);
+        timeout.floor = record.floor;
+        timeout
+    }
+    /// Read-only handle to the timeout, for a monitoring component: it
+    /// reads the state, the remaining time and the deadline, and subscribes
+    /// to the deadline changes, but it can't adjust nor cancel the timeout.
+    /// The observer doesn't keep the timeout alive.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{snapshot::TimeoutState, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after forty milliseconds");
+    ///    });
+    ///    let observer = dyn_timeout.observer();
+    ///    let mut deadline = observer.watch_deadline().await;
+    ///    dyn_timeout.add(TWENTY).await.unwrap();
+    ///    deadline.changed().await.unwrap();
+    ///    assert_eq!(observer.state().await, TimeoutState::Running);
+    /// });
+    /// ```
+    pub fn observer(&self) -> Observer {
+        Observer {
+            shared: self.shared.clone(),
+            label: self.label.clone(),
+            created_at: self.created_at,
+        }
+    }
+    /// Create a new dynamic timeout started when this one is reached, after
+    /// the execution of its callback. The new timeout can be increased or
+    /// decreased before it's started, and it's dismissed if this one is
+    /// cancelled.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return the new timeout.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let handshake = DynTimeout::new(TWENTY, || {
+    ///        println!("handshake deadline reached");
+    ///    });
+    ///    let idle = handshake.then(TWENTY, || {
+    ///        println!("idle deadline reached");
+    ///    }).await.unwrap();
+    ///    idle.add(TWENTY).await.unwrap();
+    /// });
+    /// ```
+    pub async fn then(&self, dur: Duration, callback: fn() -> ()) -> Result<DynTimeout> {
+        match self.shared.followers.lock().await.as_mut() {
+            Some(followers) => {
+                let next = Self::spawn(&TimeoutConfig::new(dur), false, move |_| async move {
+                    callback()
+                });
+                followers.push((next.shared.clone(), next.sender.clone()));
+                Ok(next)
+            }
+            None => bail!(TimeoutError::Reached),
+        }
+    }
+    /// Create a new dynamic timeout for a nested operation, its duration is
+    /// clamped to the remaining time of this one and it's cancelled when this
+    /// one ends, so the deadline is propagated to the nested operations.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return the new timeout.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let request = DynTimeout::new(TWENTY, || {
+    ///        println!("request deadline reached");
+    ///    });
+    ///    let query = request.child(TWENTY * 10, || {
+    ///        println!("query deadline reached");
+    ///    }).await.unwrap();
+    ///    assert!(query.remaining().await <= TWENTY);
+    /// });
+    /// ```
+    pub async fn child(&self, dur: Duration, callback: fn() -> ()) -> Result<DynTimeout> {
+        let remaining = self.remaining().await;
+        match self.shared.children.lock().await.as_mut() {
+            Some(children) => {
+                let child = Self::spawn(
+                    &TimeoutConfig::new(dur.min(remaining)),
+                    true,
+                    move |_| async move { callback() },
+                );
+                children.push((child.shared.clone(), child.sender.clone()));
+                Ok(child)
+            }
+            None => bail!(TimeoutError::Reached),
+        }
+    }
+    /// Split the remaining time between the successive steps of an
+    /// operation, each step gets a child timeout with a fraction of the
+    /// budget, see `Budget::take`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let request = DynTimeout::new(TWENTY * 10, || {
+    ///        println!("request deadline reached");
+    ///    });
+    ///    let mut budget = request.budget();
+    ///    let connect = budget.take(0.4, || println!("connect too long")).await.unwrap();
+    ///    assert!(connect.remaining().await <= TWENTY * 4);
+    ///    // the query gets what the connection left
+    ///    let query = budget.take(0.6, || println!("query too long")).await.unwrap();
+    ///    assert!(query.remaining().await > TWENTY * 4);
+    /// });
+    /// ```
+    pub fn budget(&self) -> Budget<'_> {
+        Budget {
+            timeout: self,
+            allocated: 0.0,
+        }
+    }
+    /// Suspend the timeout while the returned guard is alive. The callback
+    /// can't be executed while there is at least one guard, and the timeout
+    /// restarts with its initial duration when the last guard is dropped.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened.
+    /// Otherwise it return the guard.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("twenty milliseconds after the work");
+    ///    });
+    ///    let guard = dyn_timeout.keep_alive().await.unwrap();
+    ///    tokio::time::sleep(TWENTY * 2).await; // do some work
+    ///    drop(guard);
+    /// });
+    /// ```
+    pub async fn keep_alive(&self) -> Result<KeepAlive> {
+        let deadline = self.shared.lock_deadline();
+        if deadline.is_reached() {
+            bail!(TimeoutError::Reached)
+        }
+        self.shared.keep_alive.acquire();
+        Ok(KeepAlive {
+            shared: self.shared.clone(),
+            sender: self.sender.clone(),
+        })
+    }
+    /// Dismiss the timeout callback and cancel all delays added.
+    /// Stop immediatelly all waiting process and join the created thread.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already appened,
+    /// `TimeoutError::AlreadyFired` if the callback was already started. The
+    /// callback is never started after a success, and cancelling twice
+    /// succeeds.
+    /// Otherwise it return an empty success.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    /// const TEN: Duration = Duration::from_millis(10);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let mut dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("never append");
+    ///    });
+    ///    dyn_timeout.cancel().await.unwrap();
+    /// });
+    /// ```
+    pub async fn cancel(&mut self) -> Result<()> {
+        self.dismiss().await?;
+        self.thread = None;
+        Ok(())
+    }
+    /// Stop a re-armable timeout after the cycle in progress: unlike
+    /// `cancel`, the running countdown is still reached and its callback
+    /// completes, but the timeout isn't re-armed anymore, neither by `add`
+    /// with `AddPolicy::Rearm` nor by `TimeoutHandle::rearm`, and the task
+    /// ends.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::{AddPolicy, TimeoutConfig}, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let config = TimeoutConfig::new(TWENTY).add_policy(AddPolicy::Rearm);
+    ///    let mut heartbeat = DynTimeout::with_config(&config, || {
+    ///        println!("heartbeat missed, executed one last time");
+    ///    });
+    ///    heartbeat.stop_after_current();
+    ///    heartbeat.wait().await.unwrap();
+    ///    assert!(heartbeat.add(TWENTY).await.is_err());
+    /// });
+    /// ```
+    pub fn stop_after_current(&self) {
+        self.shared.stopped.stop();
+        // wake up the task waiting for a re-arm
+        self.sender.wake();
+    }
+    async fn dismiss(&self) -> Result<()> {
+        self.shared.history.record(AdjustmentKind::Cancel, None);
+        // a callback already running can't re-arm the timeout anymore
+        self.shared.stopped.stop();
+        let dismissed = self.shared.claim.cancel();
+        self.shared.lock_deadline().clear();
+        // the task may be already ended, fired or cancelled before, and a
+        // full channel already wakes it up
+        self.sender.wake();
+        if !dismissed {
+            bail!(TimeoutError::AlreadyFired)
+        }
+        Ok(())
+    }
+
+    /// Cancel the timeout when the given token is cancelled, so the timeout
+    /// follows the graceful shutdown of the application.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use tokio_util::sync::CancellationToken;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let shutdown = CancellationToken::new();
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("never append");
+    ///    })
+    ///    .linked_to(shutdown.clone());
+    ///    shutdown.cancel();
+    /// });
+    /// ```
+    #[cfg(feature = "tokio-util")]
+    pub fn linked_to(mut self, token: CancellationToken) -> Self {
+        let shared = self.shared.clone();
+        // a weak sender, so a re-armable timeout isn't kept alive by the link
+        let sender = self.sender.downgrade();
+        let finished = self.finished.clone();
+        let link = token.clone();
+        spawn_task("dyn-timeout-link", self.label.as_deref(), async move {
+            tokio::select! {
+                _ = link.cancelled() => {
+                    shared.claim.cancel();
+                    shared.lock_deadline().clear();
+                    if let Some(sender) = sender.upgrade() {
+                        sender.wake();
+                    }
+                }
+                _ = finished.cancelled() => {}
+            }
+        });
+        self.link = Some(token);
+        self
+    }
+    /// Cancel the token given to `linked_to` when the timeout is reached,
+    /// after the execution of the callback. Has no effect if the timeout
+    /// isn't linked to a token.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use tokio_util::sync::CancellationToken;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let shutdown = CancellationToken::new();
+    ///    let _dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("no activity, shutting down");
+    ///    })
+    ///    .linked_to(shutdown.clone())
+    ///    .cancel_token_on_fire();
+    ///    shutdown.cancelled().await;
+    /// });
+    /// ```
+    #[cfg(feature = "tokio-util")]
+    pub fn cancel_token_on_fire(self) -> Self {
+        if let Some(token) = &self.link {
+            *self.on_fire.lock().unwrap() = Some(token.clone());
+        }
+        self
+    }
+
+    /// Create a sink increasing the delay before the timeout with each
+    /// duration sent, so a stream of activity events can be forwarded into
+    /// the timeout. The sink fails once the timeout is reached.
+    ///
+    /// # Example
+    /// ```
+    /// use futures::{stream, StreamExt};
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("no activity since twenty milliseconds");
+    ///    });
+    ///    let progress = stream::iter(0..3);
+    ///    progress
+    ///        .map(|_| Ok(TWENTY))
+    ///        .forward(dyn_timeout.sink())
+    ///        .await
+    ///        .unwrap();
+    /// });
+    /// ```
+    #[cfg(feature = "futures-sink")]
+    pub fn sink(&self) -> TimeoutSink {
+        TimeoutSink {
+            shared: self.shared.clone(),
+            max_waiting_time: self.max_waiting_time,
+            sending: None,
+        }
+    }
+    /// Handle of the timeout task, none once the timeout is cancelled.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.spawn(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after twenty milliseconds");
+    ///    });
+    ///    assert!(!dyn_timeout.join_handle().unwrap().is_finished());
+    /// });
+    /// ```
+    pub fn join_handle(&self) -> Option<&JoinHandle<()>> {
+        self.thread.as_ref()
+    }
+    /// Id of the timeout task, to find it in tokio-console or in the
+    /// runtime metrics. With the `tracing` feature and the `tokio_unstable`
+    /// cfg, the task is also named `dyn-timeout:<label>`, see
+    /// `TimeoutConfig::label`.
+    ///
+    /// # Return
+    /// Return the id, or none if the timeout was cancelled.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::TimeoutConfig, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let config = TimeoutConfig::new(TWENTY).label("session");
+    ///    let dyn_timeout = DynTimeout::with_config(&config, || {
+    ///        println!("after twenty milliseconds");
+    ///    });
+    ///    println!("session timeout in task {}", dyn_timeout.task_id().unwrap());
+    /// });
+    /// ```
+    pub fn task_id(&self) -> Option<task::Id> {
+        self.thread.as_ref().map(JoinHandle::id)
+    }
+    /// Take the handle of the timeout task, to await it, abort it or attach
+    /// it to another supervision. The timeout is detached and can't be
+    /// adjusted anymore, the task ends after the execution of the callback.
+    ///
+    /// # Return
+    /// Return the handle, or none if the timeout was cancelled.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::tokio_impl::DynTimeout;
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after twenty milliseconds");
+    ///    });
+    ///    dyn_timeout.into_join_handle().unwrap().await.unwrap();
+    /// });
+    /// ```
+    pub fn into_join_handle(mut self) -> Option<JoinHandle<()>> {
+        self.drop_behavior = DropBehavior::Detach;
+        self.thread.take()
+    }
+
+    /// Wait for the end of the timeout
+    ///
+    /// # Return
+    /// Return a `Panicked` error if the callback panicked with
+    /// `PanicPolicy::Propagate`.
+    /// Otherwise it return an empty success.
+    pub async fn wait(&mut self) -> Result<()> {
+        self.shared.check_reentrant()?;
+        self.receiver.get_mut().recv().await;
+        self.shared.panic_policy.propagate(&self.shared.panicked)
+    }
+    /// Message of the last panic of the callback, caught with
+    /// `PanicPolicy::Record` or `PanicPolicy::Propagate`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{config::{PanicPolicy, TimeoutConfig}, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let config = TimeoutConfig::new(TWENTY).panic_policy(PanicPolicy::Record);
+    ///    let mut dyn_timeout = DynTimeout::with_config(&config, || {
+    ///        panic!("cleanup failed");
+    ///    });
+    ///    dyn_timeout.wait().await.unwrap();
+    ///    assert_eq!(dyn_timeout.panicked().as_deref(), Some("cleanup failed"));
+    /// });
+    /// ```
+    pub fn panicked(&self) -> Option<String> {
+        self.shared
+            .panicked
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+    /// Handle to await the end of the timeout from any number of tasks, each
+    /// one receives the outcome. Unlike `wait`, the handle doesn't need the
+    /// timeout itself and doesn't keep it alive.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{event::Outcome, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("after twenty milliseconds");
+    ///    });
+    ///    let waiters: Vec<_> = (0..3)
+    ///        .map(|_| {
+    ///            let completion = dyn_timeout.completion();
+    ///            tokio::spawn(async move { completion.wait().await })
+    ///        })
+    ///        .collect();
+    ///    for waiter in waiters {
+    ///        assert_eq!(waiter.await.unwrap(), Outcome::Fired);
+    ///    }
+    /// });
+    /// ```
+    pub fn completion(&self) -> Completion {
+        Completion(self.shared.completion.subscribe())
+    }
+    /// Run the future until the timeout is reached, see `with_deadline!`.
+    /// If the timeout is cancelled, the future runs until its end.
+    ///
+    /// # Return
+    /// Return the output of the future, or `Elapsed` if the timeout was
+    /// reached first.
+    pub async fn with_deadline<F: Future>(&mut self, future: F) -> Result<F::Output, Elapsed> {
+        let shared = self.shared.clone();
+        race_end(&shared, self.wait(), future).await
+    }
+    /// Cancel the timeout if it's still pending and wait for the end of its
+    /// task, so no task outlives the scope of the timeout. A callback
+    /// already started is waited.
+    ///
+    /// # Return
+    /// Return how the timeout ended, for a re-armed timeout how its last
+    /// cycle ended.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{event::Outcome, tokio_impl::DynTimeout};
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+    ///        println!("never append");
+    ///    });
+    ///    assert_eq!(dyn_timeout.close().await, Outcome::Cancelled);
+    /// });
+    /// ```
+    pub async fn close(mut self) -> Outcome {
+        let outcome = match self.dismiss().await {
+            Ok(()) => Outcome::Cancelled,
+            Err(_) => Outcome::Fired,
+        };
+        match self.join().await {
+            true => outcome,
+            false => Outcome::Panicked,
+        }
+    }
+    /// Wait for the end of the task without cancelling the timeout.
+    ///
+    /// # Return
+    /// Return false if the callback panicked.
+    async fn join(&mut self) -> bool {
+        self.drop_behavior = DropBehavior::Detach;
+        if let Some(task) = self.thread.take() {
+            if let Err(err) = task.await {
+                if err.is_panic() {
+                    return false;
+                }
+            }
+        }
+        // a panic caught with `PanicPolicy::Propagate` and not waited yet
+        self.shared
+            .panic_policy
+            .propagate(&self.shared.panicked)
+            .is_ok()
+    }
+    /// How the last cycle of a timeout ended.
+    fn ended(&self) -> Outcome {
+        match self.shared.claim.is_cancelled() {
+            true => Outcome::Cancelled,
+            false => Outcome::Fired,
+        }
+    }
+    /// Share the timeout between several owners and tasks, see
+    /// `SharedDynTimeout`.
+    pub fn into_shared(self) -> SharedDynTimeout {
+        SharedDynTimeout(Arc::new(self))
+    }
+}
+
+/// Scope owning the timeouts created in it: `close` cancels all of them and
+/// waits for the end of their tasks, so no timer task outlives a test or a
+/// request handler. The drop can't wait, it only cancels them. The timeouts
+/// are shared with the scope, a callback already started is waited by
+/// `close`.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::tokio_impl::TimeoutScope;
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let scope = TimeoutScope::new();
+///    let request = scope.spawn(TWENTY * 10, || println!("request timeout"));
+///    let idle = scope.spawn(TWENTY * 20, || println!("idle timeout"));
+///    request.add(TWENTY).await.unwrap();
+///    idle.sub(TWENTY).await.unwrap();
+///    // both cancelled, their tasks are ended
+///    scope.close().await;
+/// });
+/// ```
+#[derive(Default)]
+pub struct TimeoutScope {
+    timeouts: std::sync::Mutex<Vec<SharedDynTimeout>>,
+    /// Policies inherited by the timeouts created with `spawn`.
+    defaults: Option<TimeoutConfig>,
+}
+
+impl TimeoutScope {
+    /// Create an empty scope.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Create an empty scope whose timeouts created with `spawn` inherit
+    /// the policies of `defaults` (drop behavior, jitter, max total,
+    /// callback execution...), so they are configured in one place rather
+    /// than at every construction site. The duration of `defaults` is
+    /// replaced by the one given to `spawn`.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{
+    ///    config::{CallbackExecution, DropBehavior, TimeoutConfig},
+    ///    tokio_impl::TimeoutScope,
+    /// };
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    // configured once for the whole service
+    ///    let defaults = TimeoutConfig::new(TWENTY)
+    ///        .drop_behavior(DropBehavior::Cancel)
+    ///        .jitter(TWENTY / 4)
+    ///        .max_total(TWENTY * 50)
+    ///        .callback_execution(CallbackExecution::Spawned);
+    ///    let scope = TimeoutScope::with_defaults(defaults);
+    ///    let request = scope.spawn(TWENTY * 10, || println!("request timeout"));
+    ///    let idle = scope.spawn_with_config(&scope.config(TWENTY * 20).label("idle"), || {
+    ///        println!("idle timeout")
+    ///    });
+    ///    request.add(TWENTY).await.unwrap();
+    ///    assert_eq!(idle.snapshot().await.label.as_deref(), Some("idle"));
+    ///    scope.close().await;
+    /// });
+    /// ```
+    pub fn with_defaults(defaults: TimeoutConfig) -> Self {
+        Self {
+            timeouts: Default::default(),
+            defaults: Some(defaults),
+        }
+    }
+    /// Configuration of a timeout of `dur` with the default policies of the
+    /// scope, to override some of them for a single timeout with
+    /// `spawn_with_config`.
+    pub fn config(&self, dur: Duration) -> TimeoutConfig {
+        match &self.defaults {
+            Some(defaults) => TimeoutConfig {
+                duration: dur,
+                ..defaults.clone()
+            },
+            None => TimeoutConfig::new(dur),
+        }
+    }
+    /// Create a timeout in the scope with the default policies of the
+    /// scope, see `DynTimeout::new` and `with_defaults`.
+    pub fn spawn(&self, dur: Duration, callback: fn() -> ()) -> SharedDynTimeout {
+        self.insert(DynTimeout::with_config(&self.config(dur), callback))
+    }
+    /// Create a timeout with the given configuration in the scope, see
+    /// `DynTimeout::with_config`.
+    pub fn spawn_with_config(
+        &self,
+        config: &TimeoutConfig,
+        callback: fn() -> (),
+    ) -> SharedDynTimeout {
+        self.insert(DynTimeout::with_config(config, callback))
+    }
+    /// Move a timeout created elsewhere into the scope.
+    pub fn insert(&self, timeout: DynTimeout) -> SharedDynTimeout {
+        let timeout = timeout.into_shared();
+        self.lock().push(timeout.clone());
+        timeout
+    }
+    /// Number of timeouts in the scope.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+    /// Whether the scope has no timeout.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+    /// Exit the scope: cancel all the timeouts, then wait for the end of
+    /// their tasks.
+    pub async fn close(self) {
+        let timeouts = std::mem::take(&mut *self.lock());
+        for timeout in &timeouts {
+            let _ = timeout.0.dismiss().await;
+        }
+        for timeout in &timeouts {
+            // a callback closing its own scope can't wait for itself
+            if timeout.shared.check_reentrant().is_ok() {
+                timeout.shared.wait_ended().await;
+            }
+        }
+    }
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<SharedDynTimeout>> {
+        self.timeouts
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl Drop for TimeoutScope {
+    fn drop(&mut self) {
+        for timeout in self.lock().iter() {
+            timeout.shared.claim.cancel();
+            timeout.sender.wake();
+        }
+    }
+}
+
+/// Read-only handle to a timeout, created with `DynTimeout::observer`. The
+/// clones are cheap.
+#[derive(Clone)]
+pub struct Observer {
+    shared: Arc<Shared>,
+    label: Option<String>,
+    created_at: SystemTime,
+}
+
+impl Observer {
+    /// State of the timeout.
+    pub async fn state(&self) -> TimeoutState {
+        let deadline = self.shared.lock_deadline();
+        deadline.state(self.shared.claim.is_cancelled())
+    }
+    /// Time left before the timeout, see `DynTimeout::remaining`.
+    pub async fn remaining(&self) -> Duration {
+        self.shared.lock_deadline().remaining()
+    }
+    /// Instant the timeout will be reached at, see `DynTimeout::deadline`.
+    pub async fn deadline(&self) -> Option<Instant> {
+        self.shared.lock_deadline().at()
+    }
+    /// Watch the absolute deadline, see `DynTimeout::watch_deadline`.
+    pub async fn watch_deadline(&self) -> watch::Receiver<Option<Instant>> {
+        self.shared.lock_deadline().watch()
+    }
+    /// Adjustments made on the timeout, see `DynTimeout::history`.
+    pub fn history(&self) -> Vec<Adjustment> {
+        self.shared.history.list()
+    }
+    /// Live state of the timeout, see `DynTimeout::snapshot`.
+    pub async fn snapshot(&self) -> TimeoutSnapshot {
+        let deadline = self.shared.lock_deadline();
+        TimeoutSnapshot {
+            label: self.label.clone(),
+            state: deadline.state(self.shared.claim.is_cancelled()),
+            remaining: deadline.remaining(),
+            extensions: self.shared.history.extensions(),
+            wakeups: self.shared.wakeups.load(Ordering::Relaxed),
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Several timeouts awaited together, like one timeout per shard. The
+/// timeouts are still adjusted one by one with `get` and `get_mut`.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::{event::Outcome, tokio_impl::{DynTimeout, DynTimeoutSet}};
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let mut shards: DynTimeoutSet = (1..=3)
+///        .map(|shard| DynTimeout::new(TWENTY * shard, || println!("shard timeout")))
+///        .collect();
+///    shards.get(0).unwrap().add(TWENTY).await.unwrap();
+///    assert_eq!(shards.join_all().await, vec![Outcome::Fired; 3]);
+/// });
+/// ```
+#[derive(Default)]
+pub struct DynTimeoutSet {
+    timeouts: Vec<DynTimeout>,
+}
+
+impl DynTimeoutSet {
+    /// Create an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a timeout to the set.
+    ///
+    /// # Return
+    /// Return the index of the timeout, to get it back with `get`.
+    pub fn push(&mut self, timeout: DynTimeout) -> usize {
+        self.timeouts.push(timeout);
+        self.timeouts.len() - 1
+    }
+    /// Timeout at the given index, to adjust it.
+    pub fn get(&self, index: usize) -> Option<&DynTimeout> {
+        self.timeouts.get(index)
+    }
+    /// Timeout at the given index, to cancel it.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut DynTimeout> {
+        self.timeouts.get_mut(index)
+    }
+    /// Number of timeouts in the set.
+    pub fn len(&self) -> usize {
+        self.timeouts.len()
+    }
+    /// Whether the set has no timeout.
+    pub fn is_empty(&self) -> bool {
+        self.timeouts.is_empty()
+    }
+    /// Wait for the end of every timeout, like `DynTimeout::wait` on each
+    /// of them at the same time. The timeouts stay in the set.
+    ///
+    /// # Return
+    /// Return the first error of a `wait`, without waiting for the other
+    /// timeouts, like a `Panicked` error with `PanicPolicy::Propagate`.
+    /// Otherwise it return how each timeout ended, in the order of the set.
+    ///
+    /// # Example
+    /// ```
+    /// use tokio::runtime::Runtime;
+    /// use dyn_timeout::{
+    ///     config::{PanicPolicy, TimeoutConfig},
+    ///     tokio_impl::{DynTimeout, DynTimeoutSet},
+    /// };
+    /// use std::time::Duration;
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let mut rt = Runtime::new().unwrap();
+    /// rt.block_on(async {
+    ///    let config = TimeoutConfig::new(TWENTY).panic_policy(PanicPolicy::Propagate);
+    ///    let mut shards = DynTimeoutSet::new();
+    ///    shards.push(DynTimeout::new(TWENTY * 10, || {}));
+    ///    shards.push(DynTimeout::with_config(&config, || panic!("shard lost")));
+    ///    // returns as soon as the second shard failed
+    ///    assert!(shards.wait_all().await.is_err());
+    /// });
+    /// ```
+    pub async fn wait_all(&mut self) -> Result<Vec<Outcome>> {
+        let mut waits: Vec<_> = self
+            .timeouts
+            .iter_mut()
+            .map(|timeout| Some(Box::pin(timeout.wait())))
+            .collect();
+        poll_fn(|cx| {
+            for wait in waits.iter_mut() {
+                if let Some(pending) = wait {
+                    if let Poll::Ready(result) = pending.as_mut().poll(cx) {
+                        if let Err(err) = result {
+                            return Poll::Ready(Err(err));
+                        }
+                        *wait = None;
+                    }
+                }
+            }
+            match waits.iter().all(Option::is_none) {
+                true => Poll::Ready(Ok(())),
+                false => Poll::Pending,
+            }
+        })
+        .await?;
+        drop(waits);
+        Ok(self.timeouts.iter().map(DynTimeout::ended).collect())
+    }
+    /// Wait for the end of the task of every timeout, none is cancelled. A
+    /// re-armable timeout ends once it's stopped, see
+    /// `DynTimeout::stop_after_current`.
+    ///
+    /// # Return
+    /// Return how each timeout ended, in the order of the set.
+    pub async fn join_all(self) -> Vec<Outcome> {
+        let mut outcomes = Vec::with_capacity(self.timeouts.len());
+        // the tasks run at the same time, the order of the joins doesn't
+        // matter
+        for mut timeout in self.timeouts {
+            outcomes.push(match timeout.join().await {
+                true => timeout.ended(),
+                false => Outcome::Panicked,
+            });
+        }
+        outcomes
+    }
+}
+
+impl FromIterator<DynTimeout> for DynTimeoutSet {
+    fn from_iter<I: IntoIterator<Item = DynTimeout>>(iter: I) -> Self {
+        Self {
+            timeouts: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<DynTimeout> for DynTimeoutSet {
+    fn extend<I: IntoIterator<Item = DynTimeout>>(&mut self, iter: I) {
+        self.timeouts.extend(iter)
+    }
+}
+
+/// Dynamic timeout with several owners, created with
+/// `DynTimeout::into_shared`. The clones are cheap and all adjust the same
+/// timeout, the methods of `DynTimeout` taking a reference are available on
+/// each of them. The drop behavior applies when the last clone is dropped.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::tokio_impl::DynTimeout;
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let dyn_timeout = DynTimeout::new(TWENTY, || {
+///        println!("after forty milliseconds");
+///    })
+///    .into_shared();
+///    let remote = dyn_timeout.clone();
+///    tokio::spawn(async move { remote.add(TWENTY).await.unwrap() });
+///    dyn_timeout.wait().await.unwrap();
+/// });
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SharedDynTimeout(Arc<DynTimeout>);
+
+impl SharedDynTimeout {
+    /// Dismiss the timeout callback for all the owners.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout already ended.
+    /// Otherwise it return an empty success.
+    pub async fn cancel(&self) -> Result<()> {
+        self.0.dismiss().await
+    }
+    /// Wait for the end of the timeout, every owner waiting observes it,
+    /// see `DynTimeout::completion`.
+    pub async fn wait(&self) -> Result<()> {
+        self.0.shared.check_reentrant()?;
+        self.0.completion().wait().await;
+        self.0
+            .shared
+            .panic_policy
+            .propagate(&self.0.shared.panicked)
+    }
+    /// Run the future until the timeout is reached, see
+    /// `DynTimeout::with_deadline`.
+    pub async fn with_deadline<F: Future>(&self, future: F) -> Result<F::Output, Elapsed> {
+        race_end(&self.0.shared, self.wait(), future).await
+    }
+}
+
+/// Completion of a tokio timeout shared by several tasks, see
+/// `DynTimeout::completion`.
+#[derive(Clone)]
+pub struct Completion(watch::Receiver<Option<Outcome>>);
+
+impl Completion {
+    /// Wait for the end of the current cycle of the timeout, return
+    /// immediately if it already ended and wasn't re-armed.
+    ///
+    /// # Return
+    /// Return how the timeout ended, `Outcome::Panicked` if the callback
+    /// panicked with `PanicPolicy::Unwind` (or its task was aborted), or
+    /// with `PanicPolicy::Propagate` before any `wait` took the panic.
+    pub async fn wait(&self) -> Outcome {
+        let mut receiver = self.0.clone();
+        // the sender lives until the end of the task, which sets the outcome
+        let _ = receiver.wait_for(Option::is_some).await;
+        let outcome = *receiver.borrow();
+        outcome.unwrap_or(Outcome::Cancelled)
+    }
+    /// Outcome of the current cycle, none while the timeout is pending.
+    pub fn outcome(&self) -> Option<Outcome> {
+        *self.0.borrow()
+    }
+}
+
+/// Execute the callback future, abandoned after the max runtime of the
+/// timeout.
+///
+/// # Return
+/// Return false if the callback was abandoned.
+async fn guard<Fut: Future<Output = ()>>(shared: &Shared, callback: Fut) -> bool {
+    let limit = match shared.max_callback_runtime {
+        Some(limit) => limit,
+        None => {
+            catch(shared, callback).await;
+            return true;
+        }
+    };
+    let finished = tokio::time::timeout(limit, catch(shared, callback))
+        .await
+        .is_ok();
+    if !finished {
+        shared.overruns.fetch_add(1, Ordering::Relaxed);
+    }
+    finished
+}
+
+/// Execute the callback future, a panic is handled by the panic policy of
+/// the timeout.
+async fn catch<Fut: Future<Output = ()>>(shared: &Shared, callback: Fut) {
+    if shared.panic_policy == PanicPolicy::Unwind {
+        return callback.await;
+    }
+    let mut callback = pin!(callback);
+    let caught =
+        poll_fn(
+            |cx| match panic::catch_unwind(AssertUnwindSafe(|| callback.as_mut().poll(cx))) {
+                Ok(poll) => poll.map(Ok),
+                Err(payload) => Poll::Ready(Err(payload)),
+            },
+        )
+        .await;
+    if let Err(payload) = caught {
+        shared.panic_policy.handle(payload, &shared.panicked);
+    }
+}
+
+/// Race a future against the end of a timeout, the future wins a tie.
+async fn race_end<F, W>(shared: &Shared, end: W, future: F) -> Result<F::Output, Elapsed>
+where
+    F: Future,
+    W: Future<Output = Result<()>>,
+{
+    tokio::pin!(future);
+    tokio::select! {
+        biased;
+        output = &mut future => Ok(output),
+        _ = end => match shared.claim.is_cancelled() {
+            // there is no deadline anymore
+            true => Ok(future.await),
+            false => Err(Elapsed),
+        },
+    }
+}
+
+/// Race an async expression against a tokio `DynTimeout` (or a
+/// `SharedDynTimeout`), in an async context. Evaluates to the output of the
+/// expression, or `Err(Elapsed)` if the timeout is reached first. The
+/// deadline can still be adjusted from elsewhere while the expression runs.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::{error::Elapsed, tokio_impl::DynTimeout, with_deadline};
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let mut dyn_timeout = DynTimeout::new(TWENTY, || {});
+///    let slow = with_deadline!(dyn_timeout, tokio::time::sleep(TWENTY * 10));
+///    assert_eq!(slow, Err(Elapsed));
+///
+///    let dyn_timeout = DynTimeout::new(TWENTY * 10, || {}).into_shared();
+///    let fast = with_deadline!(dyn_timeout, async { 42 });
+///    assert_eq!(fast, Ok(42));
+/// });
+/// ```
+#[macro_export]
+macro_rules! with_deadline {
+    ($timeout:expr, $future:expr $(,)?) => {
+        $timeout.with_deadline($future).await
+    };
+}
+
+impl Deref for SharedDynTimeout {
+    type Target = DynTimeout;
+
+    fn deref(&self) -> &DynTimeout {
+        &self.0
+    }
+}
+
+/// Spawn a task of a timeout. With the `tracing` feature and the
+/// `tokio_unstable` cfg, the task is named after its kind and the label of
+/// the timeout, so it's recognizable in tokio-console.
+#[track_caller]
+fn spawn_task<F>(kind: &str, label: Option<&str>, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(all(tokio_unstable, feature = "tracing"))]
+    {
+        let name = match label {
+            Some(label) => format!("{}:{}", kind, label),
+            None => kind.to_string(),
+        };
+        // only fails for a blocking task
+        task::Builder::new()
+            .name(&name)
+            .spawn(future)
+            .expect("Cannot spawn dyn-timeout task")
+    }
+    #[cfg(not(all(tokio_unstable, feature = "tracing")))]
+    {
+        let _ = (kind, label);
+        task::spawn(future)
+    }
+}
+
+fn extend(
+    deadline: &mut Deadline,
+    max: Option<Duration>,
+    history: &History,
+    dur: Duration,
+) -> Result<()> {
+    // dismissed once the max waiting time is reached
+    if matches!(max, Some(m) if deadline.remaining() >= m) {
+        return Ok(());
+    }
+    if !deadline.add(dur) {
+        bail!(TimeoutError::InvalidDuration)
+    }
+    history.record(AdjustmentKind::Add(dur), None);
+    Ok(())
+}
+
+/// Sink returned by `DynTimeout::sink`, each duration sent is added to the
+/// timeout. The duration is added while the sink is flushed or polled for the
+/// next one.
+#[cfg(feature = "futures-sink")]
+pub struct TimeoutSink {
+    shared: Arc<Shared>,
+    max_waiting_time: Option<Duration>,
+    sending: Option<Pin<Box<dyn Future<Output = Result<()>> + Send>>>,
+}
+
+#[cfg(feature = "futures-sink")]
+impl Sink<Duration> for TimeoutSink {
+    type Error = anyhow::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_flush(cx)
+    }
+    fn start_send(self: Pin<&mut Self>, dur: Duration) -> Result<()> {
+        let this = self.get_mut();
+        if this.sending.is_some() {
+            bail!("Sink not ready")
+        }
+        let shared = this.shared.clone();
+        let max = this.max_waiting_time;
+        this.sending = Some(Box::pin(async move {
+            let mut deadline = shared.lock_deadline();
+            if deadline.is_reached() {
+                bail!(TimeoutError::Reached)
+            }
+            extend(&mut deadline, max, &shared.history, dur)
+        }));
+        Ok(())
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        if let Some(sending) = this.sending.as_mut() {
+            let sent = ready!(sending.as_mut().poll(cx));
+            this.sending = None;
+            sent?;
+        }
+        Poll::Ready(Ok(()))
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Wait for the first of the given timeouts to end and cancel all the
+/// others, useful when several limits apply and the first one reached wins.
+///
+/// # Return
+/// Return a result with an error if there is no timeout to wait, the
+/// timeouts already waited are skipped.
+/// Otherwise it return the index of the first timeout reached.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::tokio_impl::{race, DynTimeout};
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.spawn(async {
+///    let idle = DynTimeout::new(TWENTY, || println!("idle timeout"));
+///    let session = DynTimeout::new(TWENTY * 10, || println!("never append"));
+///    let mut timeouts = [idle, session];
+///    timeouts[0].add(TWENTY).await.unwrap();
+///    assert_eq!(race(&mut timeouts).await.unwrap(), 0);
+/// });
+/// ```
+pub async fn race(timeouts: &mut [DynTimeout]) -> Result<usize> {
+    if timeouts.is_empty() {
+        bail!("No timeout to wait")
+    }
+    let first = poll_fn(|cx| {
+        let mut pending = false;
+        for (index, timeout) in timeouts.iter_mut().enumerate() {
+            match timeout.receiver.get_mut().poll_recv(cx) {
+                Poll::Ready(Some(())) => return Poll::Ready(Some(index)),
+                // already waited
+                Poll::Ready(None) => {}
+                Poll::Pending => pending = true,
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(None)
+        }
+    })
+    .await;
+    let Some(first) = first else {
+        bail!("No timeout to wait")
+    };
+    for (index, timeout) in timeouts.iter_mut().enumerate() {
+        if index != first {
+            let _ = timeout.cancel().await;
+        }
+    }
+    Ok(first)
+}
+
+/// Earliest deadline of the given timeouts, the next one to be reached.
+///
+/// # Return
+/// Return none if none of the timeouts is running, see `DynTimeout::deadline`.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::tokio_impl::{earliest, latest, DynTimeout};
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let idle = DynTimeout::new(TWENTY, || println!("idle timeout"));
+///    let session = DynTimeout::new(TWENTY * 2, || println!("session timeout"));
+///    assert_eq!(earliest(&[&idle, &session]).await, idle.deadline().await);
+///    assert_eq!(latest(&[&idle, &session]).await, session.deadline().await);
+/// });
+/// ```
+pub async fn earliest(timeouts: &[&DynTimeout]) -> Option<Instant> {
+    let mut earliest = None;
+    for timeout in timeouts {
+        earliest = earliest.into_iter().chain(timeout.deadline().await).min();
+    }
+    earliest
+}
+
+/// Latest deadline of the given timeouts, once it's reached all of them are.
+///
+/// # Return
+/// Return none if none of the timeouts is running, see `DynTimeout::deadline`.
+pub async fn latest(timeouts: &[&DynTimeout]) -> Option<Instant> {
+    let mut latest = None;
+    for timeout in timeouts {
+        latest = latest.max(timeout.deadline().await);
+    }
+    latest
+}
+
+/// Remaining time of a timeout split between successive steps, returned by
+/// `DynTimeout::budget`.
+pub struct Budget<'a> {
+    timeout: &'a DynTimeout,
+    allocated: f64,
+}
+
+impl Budget<'_> {
+    /// Create a child timeout for the next step with `fraction` of the
+    /// budget, `0.4` for 40%. The fraction is applied to the time remaining
+    /// when the step starts, in proportion of the fractions not allocated
+    /// yet: a late step shrinks the next ones and an early one leaves them
+    /// more time.
+    ///
+    /// # Return
+    /// Return a result with an error if the fraction exceeds what's left of
+    /// the budget or if the timeout already appened.
+    /// Otherwise it return the timeout of the step.
+    pub async fn take(&mut self, fraction: f64, callback: fn() -> ()) -> Result<DynTimeout> {
+        let dur = match share(self.timeout.remaining().await, fraction, self.allocated) {
+            Some(dur) => dur,
+            None => bail!("Invalid fraction {}", fraction),
+        };
+        let step = self.timeout.child(dur, callback).await?;
+        self.allocated += fraction;
+        Ok(step)
+    }
+    /// Fraction of the budget not allocated yet.
+    pub fn unallocated(&self) -> f64 {
+        (1.0 - self.allocated).max(0.0)
+    }
+}
+
+/// Guard returned by `DynTimeout::keep_alive`, the timeout is suspended
+/// while the guard is alive.
+pub struct KeepAlive {
+    shared: Arc<Shared>,
+    sender: TaskWaker,
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        if self.shared.keep_alive.release() && self.shared.lock_deadline().restart() {
+            self.sender.wake();
+        }
+    }
+}
+
+/// Take over a std timeout with its callback, its remaining time and its
+/// policies, for an application set up synchronously before entering the
+/// runtime. The std timeout is stopped without executing its callback. A
+/// callback created with `std_thread::DynTimeout::with_handle` still
+/// receives a handle to the std timeout, it can't re-arm the new one.
+///
+/// # Return
+/// Return a result with an error if the std timeout already appened, or if
+/// it's not started yet (see `std_thread::DynTimeout::then`).
+/// Otherwise it return the tokio timeout.
+///
+/// # Example
+/// ```
+/// use tokio::runtime::Runtime;
+/// use dyn_timeout::{std_thread, tokio_impl::DynTimeout};
+/// use std::time::Duration;
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// // synchronous bootstrap
+/// let setup = std_thread::DynTimeout::new(TWENTY * 10, || println!("startup took too long"));
+///
+/// let mut rt = Runtime::new().unwrap();
+/// rt.block_on(async {
+///    let startup = DynTimeout::try_from(setup).unwrap();
+///    assert!(startup.remaining().await <= TWENTY * 10);
+///    startup.add(TWENTY).await.unwrap();
+/// });
+/// ```
+impl TryFrom<std_thread::DynTimeout> for DynTimeout {
+    type Error = anyhow::Error;
+
+    #[track_caller]
+    fn try_from(timeout: std_thread::DynTimeout) -> Result<Self> {
+        let (record, callback) = timeout.hand_over()?;
+        let mut timeout = Self::spawn(&record.config(), true, move |_| {
+            let callback = callback.clone();
+            async move { callback() }
+        });
+        timeout.floor = record.floor;
+        Ok(timeout)
+    }
+}
+
+/// Timeouts are equal only to themselves, and hashed by identity, so they can
+/// be used as keys of a `HashMap`.
+impl PartialEq for DynTimeout {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl Eq for DynTimeout {}
+
+impl Hash for DynTimeout {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.shared).hash(state)
+    }
+}
+
+/// Timeouts are ordered by the instant of their deadline, the first to be
+/// reached first, an expired timeout by the deadline it reached. The ones
+/// without a deadline (not started, infinite or cancelled) are after them.
+/// A `BinaryHeap` doesn't see the adjustments made after the insertion, the
+/// timeouts adjusted in the heap have to be pushed again.
+impl Ord for DynTimeout {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        let order = self.shared.lock_deadline().order();
+        order
+            .cmp(&other.shared.lock_deadline().order())
+            .then_with(|| Arc::as_ptr(&self.shared).cmp(&Arc::as_ptr(&other.shared)))
+    }
+}
+
+impl PartialOrd for DynTimeout {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Handle given to the callback of a timeout created with
+/// `DynTimeout::with_handle`.
+#[derive(Clone)]
+pub struct TimeoutHandle {
+    shared: Arc<Shared>,
+    /// The callback is executed in its own task, see `CallbackExecution`.
+    detached: bool,
+}
+
+impl TimeoutHandle {
+    /// Start the timeout again with the given duration, the callback is
+    /// executed again when it's reached. Unlike `AddPolicy::Rearm`, it's
+    /// decided by the callback itself at each execution.
+    ///
+    /// # Return
+    /// Return a result with an error if the timeout was cancelled or stopped
+    /// with `stop_after_current`, if it's already running again, or if the
+    /// callback is executed in its own task.
+    /// Otherwise it return an empty success.
+    pub fn rearm(&self, dur: Duration) -> Result<()> {
+        if self.detached {
+            bail!("Cannot rearm outside of the timeout task")
+        }
+        let mut deadline = self.shared.lock_deadline();
+        // checked with the deadline locked, a concurrent `cancel` stops the
+        // timeout before clearing the deadline
+        if self.shared.claim.is_cancelled() || self.shared.stopped.is_stopped() {
+            bail!("Timeout cancelled")
+        }
+        if !deadline.rearm(dur) {
+            bail!("Timeout not reached")
+        }
+        drop(deadline);
+        self.shared.history.record(AdjustmentKind::Rearm(dur), None);
+        Ok(())
+    }
+    /// Time left before the timeout, zero while the callback is executed.
+    pub fn remaining(&self) -> Duration {
+        self.shared.lock_deadline().remaining()
+    }
+    /// Last expiration of the timeout, the one handled while the callback
+    /// is executed.
+    pub fn expiration(&self) -> Option<Expiration> {
+        self.shared.lock_deadline().expiration()
+    }
+}
+
+impl Drop for DynTimeout {
+    fn drop(&mut self) {
+        if self.drop_behavior == DropBehavior::Cancel {
+            self.shared.stopped.stop();
+            self.shared.claim.cancel();
+            self.sender.wake();
+        }
     }
 }
@@ -0,0 +1,374 @@
+//! How the thread of a std timeout is woken up when the timeout is adjusted,
+//! see `TimeoutConfig::wake_strategy`, and the task of a tokio timeout, see
+//! `TimeoutConfig::wake_channel`
+use crate::config::WakeChannel;
+use anyhow::{bail, Result};
+#[cfg(unix)]
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, OnceLock, PoisonError,
+    },
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc, Notify};
+
+/// Mechanism interrupting the sleep of a std timeout thread, so an
+/// adjustment is applied immediately. A single thread waits on it, any
+/// thread may wake it up. Embedders on platforms where the default condvar
+/// doesn't fit can implement it with their own primitive (eventfd, a
+/// hardware timer interrupt...).
+///
+/// # Example
+/// ```
+/// use std::{sync::Arc, time::Duration};
+/// use dyn_timeout::{
+///     config::TimeoutConfig,
+///     std_thread::DynTimeout,
+///     wake::{ParkWake, WakeStrategy},
+/// };
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// fn park() -> Arc<dyn WakeStrategy> {
+///     Arc::new(ParkWake::default())
+/// }
+///
+/// let config = TimeoutConfig::new(TWENTY).wake_strategy(park);
+/// let dyn_timeout = DynTimeout::with_config(&config, || {
+///    println!("after ten milliseconds");
+/// });
+/// dyn_timeout.sub(TWENTY / 2).unwrap();
+/// ```
+pub trait WakeStrategy: Send + Sync {
+    /// Wake up the thread in `wait`, or make its next `wait` return
+    /// immediately if it isn't waiting.
+    fn wake(&self);
+    /// Sleep until `wake` is called, at most `timeout` if any. Returning
+    /// earlier is allowed, the thread checks its deadline again anyway.
+    fn wait(&self, timeout: Option<Duration>);
+}
+
+/// Wake up with a mutex and a condition variable, the default strategy.
+#[derive(Debug, Default)]
+pub struct CondvarWake {
+    woken: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl WakeStrategy for CondvarWake {
+    fn wake(&self) {
+        *self.woken.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.condvar.notify_one();
+    }
+    fn wait(&self, timeout: Option<Duration>) {
+        let woken = self.woken.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut woken = match timeout {
+            Some(timeout) => {
+                self.condvar
+                    .wait_timeout_while(woken, timeout, |woken| !*woken)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0
+            }
+            None => self
+                .condvar
+                .wait_while(woken, |woken| !*woken)
+                .unwrap_or_else(PoisonError::into_inner),
+        };
+        *woken = false;
+    }
+}
+
+/// Wake up with `thread::park` and `Thread::unpark`, without any lock.
+#[derive(Debug, Default)]
+pub struct ParkWake {
+    /// Thread waiting, known after its first wait.
+    thread: OnceLock<Thread>,
+    woken: AtomicBool,
+}
+
+impl WakeStrategy for ParkWake {
+    fn wake(&self) {
+        self.woken.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.get() {
+            thread.unpark();
+        }
+    }
+    fn wait(&self, timeout: Option<Duration>) {
+        self.thread.get_or_init(thread::current);
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        // park can return spuriously
+        while !self.woken.swap(false, Ordering::AcqRel) {
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return;
+                    }
+                    thread::park_timeout(deadline - now);
+                }
+                None => thread::park(),
+            }
+        }
+    }
+}
+
+/// Wake up by writing a byte in a unix socket pair, like the self-pipe
+/// trick of the event loops.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct PipeWake {
+    reader: UnixStream,
+    writer: UnixStream,
+}
+
+#[cfg(unix)]
+impl PipeWake {
+    /// Create the socket pair.
+    ///
+    /// # Return
+    /// Return a result with an error if the sockets can't be created.
+    pub fn new() -> Result<Self> {
+        let (reader, writer) = UnixStream::pair()?;
+        // a full socket already has a wake up pending
+        writer.set_nonblocking(true)?;
+        Ok(Self { reader, writer })
+    }
+}
+
+#[cfg(unix)]
+impl WakeStrategy for PipeWake {
+    fn wake(&self) {
+        let _ = (&self.writer).write(&[1]);
+    }
+    fn wait(&self, timeout: Option<Duration>) {
+        // a zero read timeout is rejected by the socket
+        if timeout == Some(Duration::ZERO) || self.reader.set_read_timeout(timeout).is_err() {
+            return;
+        }
+        let mut buf = [0; 64];
+        let _ = (&self.reader).read(&mut buf);
+    }
+}
+
+/// State shared by the wakers of a timeout and its thread.
+struct Channel {
+    strategy: Arc<dyn WakeStrategy>,
+    wakers: AtomicUsize,
+    /// The thread ended.
+    closed: AtomicBool,
+}
+
+/// Wakes up the thread of a timeout. Like with the senders of a channel, the
+/// thread knows when all the wakers are dropped.
+pub(crate) struct Waker(Arc<Channel>);
+
+/// Side of the thread.
+pub(crate) struct Sleeper(Arc<Channel>);
+
+/// The wakers are all dropped, nobody can wake up the thread anymore.
+#[derive(Debug)]
+pub(crate) struct Disconnected;
+
+pub(crate) fn channel(strategy: Arc<dyn WakeStrategy>) -> (Waker, Sleeper) {
+    let channel = Arc::new(Channel {
+        strategy,
+        wakers: AtomicUsize::new(1),
+        closed: AtomicBool::new(false),
+    });
+    (Waker(channel.clone()), Sleeper(channel))
+}
+
+impl Waker {
+    /// Wake up the thread.
+    ///
+    /// # Return
+    /// Return a result with an error if the thread already ended.
+    pub(crate) fn wake(&self) -> Result<()> {
+        if self.0.closed.load(Ordering::Acquire) {
+            bail!("Timeout thread ended")
+        }
+        self.0.strategy.wake();
+        Ok(())
+    }
+}
+
+impl Clone for Waker {
+    fn clone(&self) -> Self {
+        self.0.wakers.fetch_add(1, Ordering::Relaxed);
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for Waker {
+    fn drop(&mut self) {
+        if self.0.wakers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // the thread waiting for a wake up notices the disconnection
+            self.0.strategy.wake();
+        }
+    }
+}
+
+impl Sleeper {
+    fn disconnected(&self) -> bool {
+        self.0.wakers.load(Ordering::Acquire) == 0
+    }
+    /// Sleep until a wake up or the timeout.
+    pub(crate) fn wait_timeout(&self, timeout: Duration) -> Result<(), Disconnected> {
+        if self.disconnected() {
+            return Err(Disconnected);
+        }
+        self.0.strategy.wait(Some(timeout));
+        Ok(())
+    }
+    /// Sleep until a wake up.
+    pub(crate) fn wait(&self) -> Result<(), Disconnected> {
+        if self.disconnected() {
+            return Err(Disconnected);
+        }
+        self.0.strategy.wait(None);
+        match self.disconnected() {
+            true => Err(Disconnected),
+            false => Ok(()),
+        }
+    }
+}
+
+impl Drop for Sleeper {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Release);
+    }
+}
+
+/// State shared by the notify wakers of a tokio timeout and its task.
+pub(crate) struct NotifyChannel {
+    notify: Notify,
+    wakers: AtomicUsize,
+}
+
+/// Wakes up the task of a tokio timeout, ignored if the task ended or a
+/// wake up is already pending.
+pub(crate) enum TaskWaker {
+    Mpsc(mpsc::Sender<()>),
+    Notify(Arc<NotifyChannel>),
+}
+
+/// A task waker that doesn't keep the task waiting for wake ups.
+#[cfg(feature = "tokio-util")]
+pub(crate) enum WeakTaskWaker {
+    Mpsc(mpsc::WeakSender<()>),
+    Notify(std::sync::Weak<NotifyChannel>),
+}
+
+/// Side of the task.
+pub(crate) enum TaskSleeper {
+    Mpsc(mpsc::Receiver<()>),
+    Notify(Arc<NotifyChannel>),
+}
+
+pub(crate) fn task_channel(channel: WakeChannel) -> (TaskWaker, TaskSleeper) {
+    match channel {
+        WakeChannel::Mpsc(capacity) => {
+            let (sender, receiver) = mpsc::channel(capacity.max(1));
+            (TaskWaker::Mpsc(sender), TaskSleeper::Mpsc(receiver))
+        }
+        WakeChannel::Notify => {
+            let channel = Arc::new(NotifyChannel {
+                notify: Notify::new(),
+                wakers: AtomicUsize::new(1),
+            });
+            (
+                TaskWaker::Notify(channel.clone()),
+                TaskSleeper::Notify(channel),
+            )
+        }
+    }
+}
+
+impl TaskWaker {
+    /// Wake up the task.
+    pub(crate) fn wake(&self) {
+        match self {
+            TaskWaker::Mpsc(sender) => {
+                let _ = sender.try_send(());
+            }
+            TaskWaker::Notify(channel) => channel.notify.notify_one(),
+        }
+    }
+    #[cfg(feature = "tokio-util")]
+    pub(crate) fn downgrade(&self) -> WeakTaskWaker {
+        match self {
+            TaskWaker::Mpsc(sender) => WeakTaskWaker::Mpsc(sender.downgrade()),
+            TaskWaker::Notify(channel) => WeakTaskWaker::Notify(Arc::downgrade(channel)),
+        }
+    }
+}
+
+impl Clone for TaskWaker {
+    fn clone(&self) -> Self {
+        match self {
+            TaskWaker::Mpsc(sender) => TaskWaker::Mpsc(sender.clone()),
+            TaskWaker::Notify(channel) => {
+                channel.wakers.fetch_add(1, Ordering::Relaxed);
+                TaskWaker::Notify(channel.clone())
+            }
+        }
+    }
+}
+
+impl Drop for TaskWaker {
+    fn drop(&mut self) {
+        if let TaskWaker::Notify(channel) = self {
+            if channel.wakers.fetch_sub(1, Ordering::AcqRel) == 1 {
+                // the task waiting for a wake up notices the disconnection
+                channel.notify.notify_one();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio-util")]
+impl WeakTaskWaker {
+    /// Get a waker back if some are still alive.
+    pub(crate) fn upgrade(&self) -> Option<TaskWaker> {
+        match self {
+            WeakTaskWaker::Mpsc(sender) => sender.upgrade().map(TaskWaker::Mpsc),
+            WeakTaskWaker::Notify(channel) => {
+                let channel = channel.upgrade()?;
+                // like a weak sender, a disconnected channel stays disconnected
+                channel
+                    .wakers
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |wakers| {
+                        (wakers > 0).then_some(wakers + 1)
+                    })
+                    .ok()?;
+                Some(TaskWaker::Notify(channel))
+            }
+        }
+    }
+}
+
+impl TaskSleeper {
+    /// Wait for a wake up, like the `recv` of a channel.
+    ///
+    /// # Return
+    /// Return `None` once all the wakers are dropped.
+    pub(crate) async fn recv(&mut self) -> Option<()> {
+        match self {
+            TaskSleeper::Mpsc(receiver) => receiver.recv().await,
+            TaskSleeper::Notify(channel) => {
+                let disconnected = || channel.wakers.load(Ordering::Acquire) == 0;
+                if disconnected() {
+                    return None;
+                }
+                channel.notify.notified().await;
+                (!disconnected()).then_some(())
+            }
+        }
+    }
+}
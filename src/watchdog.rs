@@ -0,0 +1,77 @@
+//! Last resort protection of a process, built on the std thread implementation
+use crate::{
+    config::{DropBehavior, TimeoutConfig},
+    std_thread::DynTimeout,
+};
+use anyhow::Result;
+use std::time::Duration;
+
+/// Watchdog aborting the process if it isn't fed within the deadline, to
+/// protect a daemon against an event loop wedged forever. Dropping the
+/// watchdog stops the watch.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use dyn_timeout::watchdog::ProcessWatchdog;
+///
+/// const TWENTY: Duration = Duration::from_millis(20);
+///
+/// let watchdog = ProcessWatchdog::new(TWENTY * 5);
+/// for _ in 0..3 {
+///     std::thread::sleep(TWENTY); // an iteration of the event loop
+///     watchdog.feed().unwrap();
+/// }
+/// ```
+pub struct ProcessWatchdog {
+    timeout: DynTimeout,
+}
+
+impl ProcessWatchdog {
+    /// Start a watchdog calling `std::process::abort` if it isn't fed within
+    /// the given duration.
+    pub fn new(dur: Duration) -> Self {
+        Self::with_hook(dur, || {
+            eprintln!("dyn-timeout watchdog not fed in time, aborting");
+            std::process::abort()
+        })
+    }
+    /// Start a watchdog calling the given hook, instead of aborting the
+    /// process, if it isn't fed within the given duration.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use dyn_timeout::watchdog::ProcessWatchdog;
+    ///
+    /// const TWENTY: Duration = Duration::from_millis(20);
+    ///
+    /// let watchdog = ProcessWatchdog::with_hook(TWENTY, || {
+    ///     eprintln!("the event loop is wedged");
+    ///     std::process::exit(1);
+    /// });
+    /// watchdog.feed().unwrap();
+    /// ```
+    pub fn with_hook(dur: Duration, hook: fn()) -> Self {
+        let config = TimeoutConfig::new(dur).drop_behavior(DropBehavior::Cancel);
+        Self {
+            timeout: DynTimeout::with_config(&config, hook),
+        }
+    }
+    /// Wait again the whole duration from now.
+    ///
+    /// # Return
+    /// Return a result with an error if the watchdog already fired.
+    /// Otherwise it return an empty success.
+    pub fn feed(&self) -> Result<()> {
+        self.timeout.restart()
+    }
+    /// Time left before the watchdog fires.
+    ///
+    /// # Return
+    /// Return a result with an error if it failed to read the deadline.
+    /// Otherwise it return the remaining time.
+    pub fn remaining(&self) -> Result<Duration> {
+        self.timeout.remaining()
+    }
+}